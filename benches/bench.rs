@@ -1,4 +1,9 @@
-use skiplist::SkipList;
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+
+use skiplist::{AllocError, Allocator, Geometric, Global, OrdComparator, SkipList, UnrolledSkipList};
 use criterion::{criterion_main, criterion_group, Criterion};
 
 fn basic<const N: usize>(c: &mut Criterion) {
@@ -7,15 +12,121 @@ fn basic<const N: usize>(c: &mut Criterion) {
     let mut nums = Vec::with_capacity(N);
     for _ in 0..N {
         let i = rng.i32(..);
-        l.insert(i, |curr, next| curr.cmp(next));
+        l.insert(i);
         nums.push(i);
     }
 
     c.bench_function(format!("contains(): N = {}", N).as_str(), |b| b.iter(|| {
         let i = rng.usize(0..nums.len());
-        assert!(l.contains(|v| v.cmp(&nums[i])));
+        assert!(l.contains(&nums[i]));
+    }));
+}
+
+fn unrolled<const N: usize>(c: &mut Criterion) {
+    let mut l = UnrolledSkipList::<i32, 32, 16>::new();
+    let rng = fastrand::Rng::new();
+    let mut nums = Vec::with_capacity(N);
+    for _ in 0..N {
+        let i = rng.i32(..);
+        l.insert(i);
+        nums.push(i);
+    }
+
+    c.bench_function(format!("unrolled contains(): N = {}", N).as_str(), |b| b.iter(|| {
+        let i = rng.usize(0..nums.len());
+        assert!(l.contains(&nums[i]));
+    }));
+}
+
+/// Allocates every node on cache lines of its own, by aligning it to 64 bytes and padding it
+/// to a multiple of them, to compare against the list's own packed nodes.
+#[derive(Clone, Copy)]
+struct CacheLines;
+
+fn cache_lines(layout: Layout) -> Layout {
+    layout.align_to(64).unwrap().pad_to_align()
+}
+
+unsafe impl Allocator for CacheLines {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        Global.allocate(cache_lines(layout))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        Global.deallocate(ptr, cache_lines(layout))
+    }
+}
+
+// contains() on an i32 list, whose nodes take 40-56 bytes at the two lowest levels, over two
+// runs on an x86_64 Xeon with a 48 KiB L1 and a 2 MiB L2:
+//
+//     N          packed             cache lines
+//     1000       104-115 ns         113-144 ns
+//     100000     447-584 ns         642-826 ns
+//     1000000    1.66-1.87 us       2.09-2.22 us
+fn node_layout<const N: usize>(c: &mut Criterion) {
+    let rng = fastrand::Rng::with_seed(N as u64);
+    let nums: Vec<i32> = (0..N).map(|_| rng.i32(..)).collect();
+    let mut packed = SkipList::<i32, 32>::new();
+    for &i in &nums {
+        packed.insert(i);
+    }
+    let mut aligned = SkipList::<i32, 32, OrdComparator, Geometric, CacheLines>::new_in(CacheLines);
+    for &i in &nums {
+        aligned.insert(i);
+    }
+
+    let mut group = c.benchmark_group(format!("node layout: N = {}", N));
+    group.bench_function("packed", |b| b.iter(|| {
+        let i = rng.usize(0..nums.len());
+        assert!(packed.contains(&nums[i]));
+    }));
+    group.bench_function("cache lines", |b| b.iter(|| {
+        let i = rng.usize(0..nums.len());
+        assert!(aligned.contains(&nums[i]));
+    }));
+    group.finish();
+}
+
+// contains() on a list of random elements, with the search prefetch and without it. To compare,
+// run once as is and once with RUSTFLAGS="--cfg skiplist_no_prefetch". Times from alternating
+// runs on a single core x86_64 VM, where the numbers drift 10-20% between runs:
+//
+//       N   always prefetching   gated (>= 1 MiB of nodes)   no prefetch
+//    1000               112 ns                       96 ns         92 ns
+//   10000               207 ns                      191 ns        207 ns
+//   30000               257 ns                      269 ns        262 ns
+//  100000               450 ns                      504 ns        444 ns
+//  300000               766 ns                      722 ns        840 ns
+// 1000000              1.55 µs                     1.35 µs       1.46 µs
+//
+// Prefetching every search costs ~20% on lists that fit in cache, so searches only prefetch
+// past SkipList::prefetches' threshold (~26000 i32 elements here), where it's within the noise
+// up to 100000 elements and a 5-15% gain from 300000 on.
+fn search_prefetch<const N: usize>(c: &mut Criterion) {
+    let rng = fastrand::Rng::with_seed(N as u64);
+    let nums: Vec<i32> = (0..N).map(|_| rng.i32(..)).collect();
+    let mut l = SkipList::<i32, 32>::new();
+    for &i in &nums {
+        l.insert(i);
+    }
+
+    c.bench_function(format!("search prefetch: N = {}", N).as_str(), |b| b.iter(|| {
+        let i = rng.usize(0..nums.len());
+        assert!(l.contains(&nums[i]));
     }));
 }
 
 criterion_group!(basics, basic<100>, basic<1000>, basic<10000>, basic<100000>, basic<1000000>);
-criterion_main!(basics);
\ No newline at end of file
+criterion_group!(unrolled_basics, unrolled<1000>, unrolled<1000000>);
+criterion_group!(layouts, node_layout<1000>, node_layout<100000>, node_layout<1000000>);
+criterion_group!(
+    prefetches,
+    search_prefetch<1000>,
+    search_prefetch<10000>,
+    search_prefetch<30000>,
+    search_prefetch<100000>,
+    search_prefetch<300000>,
+    search_prefetch<1000000>,
+);
+criterion_main!(basics, unrolled_basics, layouts, prefetches);
\ No newline at end of file
@@ -0,0 +1,85 @@
+//! The allocator trait a `SkipList` allocates its nodes through.
+//!
+//! The standard library's `Allocator` needs a nightly compiler, so on stable this is a small
+//! stand-in with the two methods the list uses, which custom allocators can implement. With the
+//! `allocator_api` feature it's the standard trait itself, and any allocator written for it works.
+
+#[cfg(feature = "allocator_api")]
+pub use alloc::alloc::{AllocError, Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+pub use stable::{AllocError, Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+mod stable {
+    use core::alloc::Layout;
+    use core::fmt;
+    use core::ptr::{self, NonNull};
+
+    /// The error an [`Allocator`] returns when it can't allocate memory.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct AllocError;
+
+    impl fmt::Display for AllocError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("memory allocation failed")
+        }
+    }
+
+    /// Allocates and frees memory for a `SkipList`, like the standard library's unstable
+    /// `Allocator` trait, which it mirrors.
+    ///
+    /// # Safety
+    ///
+    /// Memory returned by `allocate` must stay valid, and not be handed out again, until it's
+    /// passed to `deallocate` on this allocator or one of its clones. Moving the allocator must
+    /// not invalidate it.
+    ///
+    /// A zero-sized allocator has no state telling its instances apart, so any instance of its
+    /// type must accept memory returned by another: `SkipList::append` relinks nodes between
+    /// lists as they are when their allocator is zero-sized. The standard library's allocators
+    /// are held to the same rule with the `allocator_api` feature.
+    pub unsafe trait Allocator {
+        /// Returns memory fitting `layout`, or an error if there is none.
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+        /// Frees memory returned by `allocate`.
+        ///
+        /// # Safety
+        ///
+        /// `ptr` must have been returned by `allocate` on this allocator or one of its clones,
+        /// with the same `layout`, and not been freed since.
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+    }
+
+    /// The global allocator, which is what `Box` and `Vec` allocate from.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Global;
+
+    unsafe impl Allocator for Global {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let ptr = match layout.size() {
+                0 => ptr::without_provenance_mut(layout.align()),
+                // SAFETY: the layout isn't zero-sized
+                _ => unsafe { alloc::alloc::alloc(layout) },
+            };
+            NonNull::new(ptr).map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size())).ok_or(AllocError)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            if layout.size() != 0 {
+                alloc::alloc::dealloc(ptr.as_ptr(), layout);
+            }
+        }
+    }
+
+    unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            (**self).allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            (**self).deallocate(ptr, layout)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use alloc::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::mem::{self, MaybeUninit};
+use core::ptr::NonNull;
+use alloc::sync::Arc;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+// chunks start small so short lists stay cheap, and stop growing at about a megabyte
+const FIRST_CHUNK_LEN: usize = 16;
+const MAX_CHUNK_BYTES: usize = 1 << 20;
+
+/// A bump allocator handing out memory for values shaped like `T`, which may be followed by a
+/// variable number of trailing fields, from chunks that double in size.
+///
+/// Memory is never handed out twice: dropping a value leaves its memory unused until the arena
+/// and every other arena sharing the chunk are dropped. Chunks are reference counted, so lists
+/// that take over each other's nodes can keep the memory alive after the arena that allocated
+/// it is gone.
+pub(crate) struct Arena<T> {
+    chunks: Vec<Arc<Chunk<T>>>,
+    // the number of blocks handed out from the last chunk
+    used: usize,
+}
+
+/// The unit chunks are made of, aligned for `T`.
+#[repr(C)]
+struct Block<T> {
+    _align: [T; 0],
+    _bytes: [u8; 16],
+}
+
+struct Chunk<T> {
+    blocks: Box<[UnsafeCell<MaybeUninit<Block<T>>>]>,
+}
+
+// SAFETY: a chunk is plain memory. Every value in it is owned, accessed, and dropped by
+// whoever allocated its memory, never through the chunk.
+unsafe impl<T: Send> Send for Chunk<T> {}
+unsafe impl<T: Sync> Sync for Chunk<T> {}
+
+impl<T> Arena<T> {
+    pub(crate) fn new() -> Self {
+        Arena { chunks: Vec::new(), used: 0 }
+    }
+
+    /// Returns memory fitting `layout`, whose alignment must not be greater than `T`'s, or None
+    /// if a new chunk was needed and couldn't be allocated. The caller owns whatever it writes
+    /// there, and must drop it in place before the arena is dropped if it needs dropping.
+    pub(crate) fn alloc(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        assert!(layout.align() <= mem::align_of::<Block<T>>());
+        let blocks = layout.size().div_ceil(mem::size_of::<Block<T>>()).max(1);
+
+        let full = self.chunks.last().is_none_or(|chunk| self.used + blocks > chunk.blocks.len());
+        if full {
+            let max_len = MAX_CHUNK_BYTES / mem::size_of::<Block<T>>();
+            let len = self.chunks.last().map_or(FIRST_CHUNK_LEN, |chunk| chunk.blocks.len() * 2).min(max_len).max(blocks);
+            let mut blocks = Vec::new();
+            blocks.try_reserve_exact(len).ok()?;
+            self.chunks.try_reserve(1).ok()?;
+            blocks.extend((0..len).map(|_| UnsafeCell::new(MaybeUninit::uninit())));
+            self.chunks.push(Arc::new(Chunk { blocks: blocks.into_boxed_slice() }));
+            self.used = 0;
+        }
+
+        // the pointer is made from the whole chunk, since the memory may span several blocks
+        let chunk = self.chunks.last().unwrap().blocks.as_ptr();
+        // SAFETY: the chunk has room for the blocks past used, which were never handed out, so
+        // nothing else refers to them
+        let block = UnsafeCell::raw_get(unsafe { chunk.add(self.used) });
+        self.used += blocks;
+        // SAFETY: as above
+        Some(unsafe { NonNull::new_unchecked(block.cast()) })
+    }
+
+    /// Returns the size of every chunk this arena keeps alive, used or not.
+    pub(crate) fn capacity_bytes(&self) -> usize {
+        self.chunks.iter().map(|chunk| mem::size_of_val::<[_]>(&chunk.blocks)).sum()
+    }
+
+    /// Returns an arena that keeps all of this one's chunks alive, but allocates from its own.
+    pub(crate) fn share(&self) -> Self {
+        let used = self.chunks.last().map_or(0, |chunk| chunk.blocks.len());
+        Arena { chunks: self.chunks.clone(), used }
+    }
+
+    /// Keeps the chunks of `other` alive for as long as this arena's.
+    pub(crate) fn adopt(&mut self, other: Self) {
+        if self.chunks.is_empty() {
+            *self = other.share();
+        } else {
+            // the last chunk has to stay last, since it's the one still handing out memory
+            self.chunks.splice(0..0, other.chunks);
+        }
+    }
+}
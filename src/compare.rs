@@ -0,0 +1,63 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::ops::Bound;
+
+/// Defines the order a `SkipList` keeps its elements in.
+///
+/// Implemented by [`OrdComparator`], which uses the elements' `Ord` impl, by [`KeyComparator`],
+/// and by every closure `Fn(&T, &T) -> Ordering`. The comparator must be a total order, and
+/// must not change while it is used by a list.
+///
+/// Lookups compare elements against a probe of type `Q`, which is another element by default.
+/// [`OrdComparator`] also accepts any `Q` that `T` borrows as, so a list of `String`s can be
+/// searched with a `&str`.
+pub trait Comparator<T, Q: ?Sized = T> {
+    fn compare(&self, a: &T, b: &Q) -> Ordering;
+}
+
+/// Orders elements by their `Ord` impl. This is the default comparator of a `SkipList`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OrdComparator;
+
+impl<T: Borrow<Q>, Q: Ord + ?Sized> Comparator<T, Q> for OrdComparator {
+    fn compare(&self, a: &T, b: &Q) -> Ordering {
+        a.borrow().cmp(b)
+    }
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> Comparator<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Orders elements by the key `F` extracts from them, using the key's `Ord` impl.
+///
+/// Created by [`SkipList::with_key`]. The key is extracted again on every comparison, so `F`
+/// should be cheap, like reading a field.
+#[derive(Clone, Copy)]
+pub struct KeyComparator<F>(pub(crate) F);
+
+impl<T, K: Ord, F: Fn(&T) -> K> Comparator<T> for KeyComparator<F> {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        (self.0)(a).cmp(&(self.0)(b))
+    }
+}
+
+/// Returns true if `v` comes before a range whose start is `bound`.
+pub(crate) fn before_start<T, C: Comparator<T>>(cmp: &C, v: &T, bound: Bound<&T>) -> bool {
+    match bound {
+        Bound::Included(start) => cmp.compare(v, start) == Ordering::Less,
+        Bound::Excluded(start) => cmp.compare(v, start) != Ordering::Greater,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Returns true if `v` doesn't come after a range whose end is `bound`.
+pub(crate) fn through_end<T, C: Comparator<T>>(cmp: &C, v: &T, bound: Bound<&T>) -> bool {
+    match bound {
+        Bound::Included(end) => cmp.compare(v, end) != Ordering::Greater,
+        Bound::Excluded(end) => cmp.compare(v, end) == Ordering::Less,
+        Bound::Unbounded => true,
+    }
+}
@@ -0,0 +1,525 @@
+use std::borrow::Borrow;
+use std::cell::UnsafeCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::SeqCst};
+
+use super::{gen_height, MAX_HEIGHT};
+
+// a thread protects the last node before the key at every level, the first node not before it
+// at every level, the successor it's looking at, and the node it's removing
+const LEFT: usize = 0;
+const RIGHT: usize = MAX_HEIGHT;
+const SCRATCH: usize = 2 * MAX_HEIGHT;
+const HELD: usize = 2 * MAX_HEIGHT + 1;
+const SLOTS: usize = 2 * MAX_HEIGHT + 2;
+
+struct Node<T> {
+    val: T,
+    // the number of levels the node is linked at, plus one while its insertion is in progress.
+    // Whoever drops the last one retires the node
+    refs: AtomicUsize,
+    // one link per level. A link with its lowest bit set marks the node as removed at that level
+    next: Box<[AtomicPtr<Node<T>>]>,
+}
+
+fn is_marked<T>(p: *mut Node<T>) -> bool {
+    p as usize & 1 == 1
+}
+
+fn marked<T>(p: *mut Node<T>) -> *mut Node<T> {
+    (p as usize | 1) as *mut Node<T>
+}
+
+fn unmarked<T>(p: *mut Node<T>) -> *mut Node<T> {
+    (p as usize & !1) as *mut Node<T>
+}
+
+/// The hazard pointers and retired nodes of one thread at a time.
+///
+/// Records are never freed before the set, so a thread takes an inactive one for the length of
+/// an operation, or pushes a new one if all are taken.
+struct Record<T> {
+    next: *mut Record<T>,
+    active: AtomicBool,
+    hazards: [AtomicPtr<Node<T>>; SLOTS],
+    // only touched by the thread that has the record active, or through `&mut` on the set
+    retired: UnsafeCell<Vec<*mut Node<T>>>,
+}
+
+/// Where a search ended up on every level. Every node in it is protected by a hazard pointer.
+struct Position<T> {
+    // the first unremoved node equal to the key, or null
+    found: *mut Node<T>,
+    // the last node before the key at every level, or null for the head
+    left: [*mut Node<T>; MAX_HEIGHT],
+    // the first node not before the key at every level
+    right: [*mut Node<T>; MAX_HEIGHT],
+}
+
+/// A lock-free sorted set like [`SkipSet`](super::SkipSet), that reclaims removed nodes with
+/// hazard pointers instead of epochs.
+///
+/// Before reading a node, a thread publishes a pointer to it, and a removed node is only freed
+/// once no published pointer refers to it. A thread that stalls in the middle of an operation
+/// only keeps the few nodes it has published alive, instead of holding back every node removed
+/// after it was pinned, so the garbage waiting to be freed stays bounded. The price is an extra
+/// store and load for every node a search steps onto.
+pub struct HazardSkipSet<T> {
+    head: [AtomicPtr<Node<T>>; MAX_HEIGHT],
+    // an upper bound on the height of every node, so searches can skip the empty levels above
+    height: AtomicUsize,
+    len: AtomicUsize,
+    records: AtomicPtr<Record<T>>,
+    num_records: AtomicUsize,
+    _marker: PhantomData<*const T>,
+}
+
+// SAFETY: the set owns its elements, and any thread may drop one after removing it, so sharing
+// it moves elements between threads and hands out `&T` to all of them.
+unsafe impl<T: Send> Send for HazardSkipSet<T> {}
+unsafe impl<T: Send + Sync> Sync for HazardSkipSet<T> {}
+
+/// A record taken by the current thread, released when dropped.
+struct Local<'a, T> {
+    set: &'a HazardSkipSet<T>,
+    rec: &'a Record<T>,
+}
+
+impl<'a, T> Local<'a, T> {
+    /// Publishes `p`, which must already be protected by another slot or be null.
+    fn set(&self, slot: usize, p: *mut Node<T>) {
+        self.rec.hazards[slot].store(p, SeqCst);
+    }
+
+    /// Loads `link` and publishes the node it points to, retrying until the link is unchanged
+    /// after publishing, so the node can't have been freed in between.
+    fn protect(&self, slot: usize, link: &AtomicPtr<Node<T>>) -> *mut Node<T> {
+        let mut p = link.load(SeqCst);
+        loop {
+            self.set(slot, unmarked(p));
+            let again = link.load(SeqCst);
+            if again == p {
+                return p;
+            }
+            p = again;
+        }
+    }
+
+    /// Drops one reference to `node`, retiring it if that was the last one.
+    ///
+    /// SAFETY: `node` must be valid, and the caller must own the reference it drops.
+    unsafe fn release(&self, node: *mut Node<T>) {
+        if (*node).refs.fetch_sub(1, SeqCst) == 1 {
+            self.retire(node);
+        }
+    }
+
+    /// Frees `node` once no thread has it published.
+    ///
+    /// SAFETY: `node` must be unlinked from every level, and not retired before.
+    unsafe fn retire(&self, node: *mut Node<T>) {
+        let retired = &mut *self.rec.retired.get();
+        retired.push(node);
+        if retired.len() >= 2 * SLOTS * self.set.num_records.load(SeqCst) {
+            self.set.scan(retired);
+        }
+    }
+}
+
+impl<'a, T> Drop for Local<'a, T> {
+    fn drop(&mut self) {
+        for hazard in &self.rec.hazards {
+            hazard.store(ptr::null_mut(), SeqCst);
+        }
+        self.rec.active.store(false, SeqCst);
+    }
+}
+
+impl<T> HazardSkipSet<T> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        HazardSkipSet {
+            head: Default::default(),
+            height: AtomicUsize::new(1),
+            len: AtomicUsize::new(0),
+            records: AtomicPtr::new(ptr::null_mut()),
+            num_records: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements in the set. Other threads may change it at any moment.
+    pub fn len(&self) -> usize {
+        self.len.load(SeqCst)
+    }
+
+    /// Returns true if the set contains no elements. Other threads may change it at any moment.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes an inactive record for the current thread, or adds a new one.
+    fn local(&self) -> Local<'_, T> {
+        let mut rec = self.records.load(SeqCst);
+        // SAFETY: records are only freed when the set is dropped
+        while let Some(r) = unsafe { rec.as_ref() } {
+            if r.active.compare_exchange(false, true, SeqCst, SeqCst).is_ok() {
+                return Local { set: self, rec: r };
+            }
+            rec = r.next;
+        }
+
+        let rec = Box::into_raw(Box::new(Record {
+            next: ptr::null_mut(),
+            active: AtomicBool::new(true),
+            hazards: [(); SLOTS].map(|_| AtomicPtr::new(ptr::null_mut())),
+            retired: UnsafeCell::new(Vec::new()),
+        }));
+        let mut head = self.records.load(SeqCst);
+        loop {
+            // SAFETY: rec was just allocated and isn't shared until the exchange succeeds
+            unsafe { (*rec).next = head };
+            match self.records.compare_exchange(head, rec, SeqCst, SeqCst) {
+                Ok(_) => break,
+                Err(actual) => head = actual,
+            }
+        }
+        self.num_records.fetch_add(1, SeqCst);
+        // SAFETY: records are only freed when the set is dropped
+        Local { set: self, rec: unsafe { &*rec } }
+    }
+
+    /// Frees every retired node no thread has published.
+    fn scan(&self, retired: &mut Vec<*mut Node<T>>) {
+        let mut hazards = Vec::new();
+        let mut rec = self.records.load(SeqCst);
+        // SAFETY: records are only freed when the set is dropped
+        while let Some(r) = unsafe { rec.as_ref() } {
+            hazards.extend(r.hazards.iter().map(|h| h.load(SeqCst)).filter(|p| !p.is_null()));
+            rec = r.next;
+        }
+        hazards.sort_unstable();
+
+        retired.retain(|&node| {
+            if hazards.binary_search(&node).is_ok() {
+                return true;
+            }
+            // SAFETY: the node is unreachable from the set, and no thread published it before
+            // it became unreachable, so none can reach it anymore
+            drop(unsafe { Box::from_raw(node) });
+            false
+        });
+    }
+
+    /// Returns the links of `node`, or of the head if `node` is null.
+    ///
+    /// SAFETY: `node` must be null or protected by a hazard pointer of the current thread.
+    unsafe fn links(&self, node: *mut Node<T>) -> &[AtomicPtr<Node<T>>] {
+        match node.as_ref() {
+            Some(node) => &node.next,
+            None => &self.head,
+        }
+    }
+
+    /// Finds the position of `key` on every level, unlinking every removed node it walks past,
+    /// and publishes every node in it through `local`.
+    fn search<Q>(&self, key: &Q, local: &Local<'_, T>) -> Position<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        'retry: loop {
+            let mut pos = Position { found: ptr::null_mut(), left: [ptr::null_mut(); MAX_HEIGHT], right: [ptr::null_mut(); MAX_HEIGHT] };
+            let mut pred: *mut Node<T> = ptr::null_mut();
+            for level in (0..self.height.load(SeqCst)).rev() {
+                // pred is the head or still published for the level above
+                local.set(LEFT + level, pred);
+                // SAFETY: pred was just published, and everything reached below is published
+                // before it's read and checked to be still linked afterwards
+                unsafe {
+                    let mut links = self.links(pred);
+                    let mut curr = local.protect(RIGHT + level, &links[level]);
+                    // pred was removed since the search moved onto it
+                    if is_marked(curr) {
+                        continue 'retry;
+                    }
+
+                    while let Some(c) = curr.as_ref() {
+                        // while c is linked, its successor can't be unlinked at this level, so
+                        // it's alive whether or not c is marked
+                        let succ = local.protect(SCRATCH, &c.next[level]);
+                        if is_marked(succ) {
+                            match links[level].compare_exchange(curr, unmarked(succ), SeqCst, SeqCst) {
+                                // the link just replaced held one of curr's references
+                                Ok(_) => local.release(curr),
+                                Err(_) => continue 'retry,
+                            }
+                            curr = unmarked(succ);
+                            local.set(RIGHT + level, curr);
+                            continue;
+                        }
+
+                        match c.val.borrow().cmp(key) {
+                            Ordering::Less => {
+                                pred = curr;
+                                local.set(LEFT + level, pred);
+                                links = &c.next;
+                                curr = succ;
+                                local.set(RIGHT + level, curr);
+                            }
+                            Ordering::Equal if level == 0 => {
+                                pos.found = curr;
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+                    pos.left[level] = pred;
+                    pos.right[level] = curr;
+                }
+            }
+            return pos;
+        }
+    }
+}
+
+impl<T: Ord + Send> HazardSkipSet<T> {
+    /// Inserts `val` and returns true, or returns false and drops `val` if an equal element is
+    /// already in the set.
+    pub fn insert(&self, val: T) -> bool {
+        let local = &self.local();
+        let mut pos = self.search(&val, local);
+        if !pos.found.is_null() {
+            return false;
+        }
+
+        let height = gen_height();
+        self.height.fetch_max(height, SeqCst);
+        let next = (0..height).map(|_| AtomicPtr::new(ptr::null_mut())).collect();
+        // one reference for the level 0 link, and one held until the node is fully linked
+        let node = Box::into_raw(Box::new(Node { val, refs: AtomicUsize::new(2), next }));
+        // SAFETY: node was just allocated, and is only retired once every reference is released
+        let node_ref = unsafe { &*node };
+
+        // SAFETY: every node in pos stays published until the next search replaces pos
+        unsafe {
+            loop {
+                node_ref.next[0].store(pos.right[0], SeqCst);
+                if self.links(pos.left[0])[0].compare_exchange(pos.right[0], node, SeqCst, SeqCst).is_ok() {
+                    break;
+                }
+                pos = self.search(&node_ref.val, local);
+                if !pos.found.is_null() {
+                    // node was never linked, so no other thread can have seen it
+                    drop(Box::from_raw(node));
+                    return false;
+                }
+            }
+            self.len.fetch_add(1, SeqCst);
+
+            'build: for level in 1..height {
+                loop {
+                    let next = node_ref.next[level].load(SeqCst);
+                    // stop building once a removal has started marking the node
+                    if is_marked(next)
+                        || node_ref.next[level].compare_exchange(next, pos.right[level], SeqCst, SeqCst).is_err()
+                    {
+                        break 'build;
+                    }
+
+                    node_ref.refs.fetch_add(1, SeqCst);
+                    if self.links(pos.left[level])[level].compare_exchange(pos.right[level], node, SeqCst, SeqCst).is_ok() {
+                        break;
+                    }
+                    node_ref.refs.fetch_sub(1, SeqCst);
+
+                    pos = self.search(&node_ref.val, local);
+                    if pos.found != node {
+                        break 'build;
+                    }
+                }
+            }
+
+            // a removal may have marked a level after it was linked, and missed it while unlinking
+            if is_marked(node_ref.next[0].load(SeqCst)) {
+                self.search(&node_ref.val, local);
+            }
+            // this drops the reference held while building the tower
+            local.release(node);
+        }
+        true
+    }
+
+    /// Returns true if the set contains an element equal to `key`.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let local = &self.local();
+        !self.search(key, local).found.is_null()
+    }
+
+    /// Removes the element equal to `key` and returns true, or returns false if there is none.
+    /// When several threads remove the same element at once, exactly one of them gets true.
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let local = &self.local();
+        loop {
+            let node = self.search(key, local).found;
+            if node.is_null() {
+                return false;
+            }
+            // keep the node published through the search that unlinks it
+            local.set(HELD, node);
+            // SAFETY: node is published
+            let node_ref = unsafe { &*node };
+
+            // mark from the top down, so the node stays reachable on level 0 until the end
+            for link in node_ref.next[1..].iter().rev() {
+                let mut next = link.load(SeqCst);
+                while !is_marked(next) {
+                    match link.compare_exchange(next, marked(next), SeqCst, SeqCst) {
+                        Ok(_) => break,
+                        Err(actual) => next = actual,
+                    }
+                }
+            }
+            let mut next = node_ref.next[0].load(SeqCst);
+            while !is_marked(next) {
+                match node_ref.next[0].compare_exchange(next, marked(next), SeqCst, SeqCst) {
+                    Ok(_) => {
+                        self.len.fetch_sub(1, SeqCst);
+                        // unlink the node from every level it's on
+                        self.search(key, local);
+                        return true;
+                    }
+                    Err(actual) => next = actual,
+                }
+            }
+        }
+    }
+}
+
+impl<T> Default for HazardSkipSet<T> {
+    fn default() -> Self {
+        HazardSkipSet::new()
+    }
+}
+
+impl<T> Drop for HazardSkipSet<T> {
+    fn drop(&mut self) {
+        // SAFETY: no other thread can access the set anymore. Retired nodes are unlinked, and
+        // every linked node holds exactly one reference for every level it's linked at, so it's
+        // freed after its last level is walked
+        unsafe {
+            let mut rec = *self.records.get_mut();
+            while !rec.is_null() {
+                let r = Box::from_raw(rec);
+                for &node in &*r.retired.get() {
+                    drop(Box::from_raw(node));
+                }
+                rec = r.next;
+            }
+
+            for (level, link) in self.head.iter_mut().enumerate().rev() {
+                let mut node = *link.get_mut();
+                while let Some(n) = node.as_ref() {
+                    let next = unmarked(n.next[level].load(SeqCst));
+                    if n.refs.fetch_sub(1, SeqCst) == 1 {
+                        drop(Box::from_raw(node));
+                    }
+                    node = next;
+                }
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for HazardSkipSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HazardSkipSet").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HazardSkipSet, SLOTS};
+
+    #[test]
+    fn insert_contains_remove() {
+        let s = HazardSkipSet::new();
+        assert!(s.is_empty());
+        for i in (0..100).rev() {
+            assert!(s.insert(i));
+        }
+        assert!(!s.insert(50));
+        assert_eq!(s.len(), 100);
+        for i in 0..100 {
+            assert!(s.contains(&i));
+        }
+        assert!(!s.contains(&100));
+
+        for i in (0..100).step_by(2) {
+            assert!(s.remove(&i));
+            assert!(!s.remove(&i));
+        }
+        assert_eq!(s.len(), 50);
+        for i in 0..100 {
+            assert_eq!(s.contains(&i), i % 2 == 1);
+        }
+
+        let strings = HazardSkipSet::new();
+        strings.insert(String::from("a"));
+        assert!(strings.contains("a"));
+        assert!(strings.remove("a"));
+    }
+
+    #[test]
+    fn many_threads_bounded_garbage() {
+        let mut s = HazardSkipSet::new();
+        std::thread::scope(|scope| {
+            for t in 0..8u64 {
+                let s = &s;
+                scope.spawn(move || {
+                    for i in 0..2_000 {
+                        let n = i * 8 + t;
+                        assert!(s.insert(n));
+                        // every thread also races to remove numbers the others insert
+                        s.remove(&(fastrand::u64(0..16_000)));
+                        if n % 3 == 0 {
+                            s.remove(&n);
+                            assert!(!s.contains(&n));
+                        }
+                    }
+                });
+            }
+        });
+
+        let remaining = (0..16_000u64).filter(|n| s.contains(n)).count();
+        assert_eq!(remaining, s.len());
+
+        // thousands of nodes were removed, but each record scans once it keeps 2 * SLOTS retired
+        // nodes for every record there is
+        let records = *s.num_records.get_mut();
+        let mut retired = 0;
+        let mut rec = *s.records.get_mut();
+        // SAFETY: no thread is using the set
+        while let Some(r) = unsafe { rec.as_mut() } {
+            retired += r.retired.get_mut().len();
+            rec = r.next;
+        }
+        assert!(retired < records * records * 2 * SLOTS);
+
+        for n in 0..16_000u64 {
+            s.remove(&n);
+        }
+        assert!(s.is_empty());
+    }
+}
@@ -0,0 +1,479 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hint;
+use std::mem;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Mutex, MutexGuard};
+
+use super::{gen_height, MAX_HEIGHT};
+
+struct Node<T> {
+    // None for the head
+    val: Option<T>,
+    // one link per level, null past the last node
+    next: Box<[AtomicPtr<Node<T>>]>,
+    // set under the node's lock when it's logically removed, before it's unlinked
+    marked: AtomicBool,
+    // set once the node is linked at every level, which makes it logically present
+    fully_linked: AtomicBool,
+    lock: Mutex<()>,
+}
+
+impl<T> Node<T> {
+    fn new(val: Option<T>, height: usize) -> Self {
+        Node {
+            val,
+            next: (0..height).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
+            marked: AtomicBool::new(false),
+            fully_linked: AtomicBool::new(false),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        // the lock guards no data, so a panic while holding it can't leave anything inconsistent
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Where a search ended up on every level.
+struct Position<T> {
+    // the level the first node equal to the key was found at, if any
+    found: Option<usize>,
+    // the last node before the key at every level, or the head
+    preds: [*mut Node<T>; MAX_HEIGHT],
+    // the first node not before the key at every level
+    succs: [*mut Node<T>; MAX_HEIGHT],
+}
+
+/// A sorted set that any number of threads can update through a shared reference, using a
+/// lock per node instead of atomic-only algorithms.
+///
+/// This is the lazy skip list of Herlihy, Lev, Luchangco and Shavit. Searches and `contains`
+/// never lock. Updates search optimistically, then lock the predecessors they change, validate
+/// that nothing moved in between, and retry if it did. A removal first marks its node, which
+/// logically removes it, then unlinks it.
+///
+/// Nodes of removed elements can't be freed while other threads may still be reading them.
+/// Every operation counts itself in for the current epoch while it runs, and a removal retires
+/// its node under that epoch. After retiring, it moves the epoch on if no operation from the
+/// previous one is still running, and frees the nodes retired two epochs back, which nothing
+/// can reach anymore. A thread stalled mid-operation holds back every node retired since, and
+/// [`reclaim`](LazySkipSet::reclaim) frees them all given exclusive access.
+pub struct LazySkipSet<T> {
+    head: Box<Node<T>>,
+    // an upper bound on the height of every node, so searches can skip the empty levels above
+    height: AtomicUsize,
+    len: AtomicUsize,
+    // only moved on with retired locked
+    epoch: AtomicUsize,
+    // the number of operations running in every epoch, modulo 3
+    readers: [AtomicUsize; 3],
+    // nodes that were unlinked but may still be read by other threads, by epoch modulo 3
+    retired: Mutex<[Vec<NonNull<Node<T>>>; 3]>,
+}
+
+/// An operation counted in for `epoch`, until it's dropped.
+struct Pinned<'a> {
+    readers: &'a AtomicUsize,
+    epoch: usize,
+}
+
+impl Drop for Pinned<'_> {
+    fn drop(&mut self) {
+        self.readers.fetch_sub(1, SeqCst);
+    }
+}
+
+// SAFETY: the set owns its elements, and only frees a node once no operation can reach it.
+// Sharing it moves elements between threads and hands out `&T` to all of them.
+unsafe impl<T: Send> Send for LazySkipSet<T> {}
+unsafe impl<T: Send + Sync> Sync for LazySkipSet<T> {}
+
+impl<T> LazySkipSet<T> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        LazySkipSet {
+            head: Box::new(Node::new(None, MAX_HEIGHT)),
+            height: AtomicUsize::new(1),
+            len: AtomicUsize::new(0),
+            epoch: AtomicUsize::new(0),
+            readers: Default::default(),
+            retired: Mutex::new(Default::default()),
+        }
+    }
+
+    /// Returns the number of elements in the set. Other threads may change it at any moment.
+    pub fn len(&self) -> usize {
+        self.len.load(SeqCst)
+    }
+
+    /// Returns true if the set contains no elements. Other threads may change it at any moment.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frees the nodes of every removed element, including the ones removals couldn't free
+    /// yet because other operations were running.
+    pub fn reclaim(&mut self) {
+        let retired = self.retired.get_mut().unwrap_or_else(|e| e.into_inner());
+        for node in retired.iter_mut().flat_map(|nodes| nodes.drain(..)) {
+            // SAFETY: retired nodes are unlinked, and no other thread can be reading them
+            // since the set is borrowed mutably
+            drop(unsafe { Box::from_raw(node.as_ptr()) });
+        }
+    }
+
+    /// Counts an operation in for the current epoch. No node it reaches from the head is freed
+    /// until the returned guard is dropped.
+    fn pin(&self) -> Pinned<'_> {
+        loop {
+            let epoch = self.epoch.load(SeqCst);
+            let readers = &self.readers[epoch % 3];
+            readers.fetch_add(1, SeqCst);
+            // once the epoch has moved on, nodes retired before the count went up may be freed
+            if self.epoch.load(SeqCst) == epoch {
+                return Pinned { readers, epoch };
+            }
+            readers.fetch_sub(1, SeqCst);
+        }
+    }
+
+    /// Keeps an unlinked node until no operation that may have reached it is running.
+    fn retire(&self, node: NonNull<Node<T>>, pinned: &Pinned<'_>) {
+        self.retired.lock().unwrap_or_else(|e| e.into_inner())[pinned.epoch % 3].push(node);
+    }
+
+    /// Moves the epoch on, unless an operation from the previous one is still running, and
+    /// frees the nodes retired two epochs back.
+    fn collect(&self) {
+        let mut retired = self.retired.lock().unwrap_or_else(|e| e.into_inner());
+        let epoch = self.epoch.load(SeqCst);
+        if self.readers[(epoch + 2) % 3].load(SeqCst) != 0 {
+            return;
+        }
+        // those nodes were unlinked before this epoch began, by operations that have all
+        // finished, and every operation that could have reached them has finished too. None
+        // retire under the next epoch until it begins
+        let garbage = mem::take(&mut retired[(epoch + 1) % 3]);
+        self.epoch.store(epoch + 1, SeqCst);
+        drop(retired);
+        for node in garbage {
+            // SAFETY: as above, no thread can be reading the node
+            drop(unsafe { Box::from_raw(node.as_ptr()) });
+        }
+    }
+
+    /// Finds the position of `key` on every level. Never locks or changes anything.
+    ///
+    /// The caller must stay pinned for as long as it uses the nodes in the position.
+    fn search<Q>(&self, key: &Q, _pinned: &Pinned<'_>) -> Position<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let head = &*self.head as *const Node<T> as *mut Node<T>;
+        let mut pos = Position { found: None, preds: [head; MAX_HEIGHT], succs: [ptr::null_mut(); MAX_HEIGHT] };
+        let mut pred = head;
+        for level in (0..self.height.load(SeqCst)).rev() {
+            // SAFETY: nodes are only freed once no pinned operation can reach them, so every
+            // node reachable from the head, even one removed meanwhile, stays valid
+            unsafe {
+                let mut curr = (*pred).next[level].load(SeqCst);
+                while let Some(c) = curr.as_ref() {
+                    match c.val.as_ref().unwrap().borrow().cmp(key) {
+                        Ordering::Less => {
+                            pred = curr;
+                            curr = c.next[level].load(SeqCst);
+                        }
+                        Ordering::Equal if pos.found.is_none() => {
+                            pos.found = Some(level);
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                pos.preds[level] = pred;
+                pos.succs[level] = curr;
+            }
+        }
+        pos
+    }
+
+    /// Locks the distinct predecessors on the lowest `height` levels of `pos`, bottom-up, and
+    /// checks that each of them is unremoved and still links to its unremoved successor. When
+    /// removing `victim`, checks that they link to it instead.
+    ///
+    /// SAFETY: `pos` must come from a search of this set, by an operation that's still pinned.
+    unsafe fn lock_preds<'a>(
+        &'a self,
+        pos: &Position<T>,
+        height: usize,
+        victim: Option<*mut Node<T>>,
+    ) -> Option<Vec<MutexGuard<'a, ()>>> {
+        let mut guards = Vec::with_capacity(height);
+        let mut last_locked = ptr::null_mut();
+        for level in 0..height {
+            let pred = &*pos.preds[level];
+            // the same node is the predecessor on consecutive levels, and is locked only once
+            if pos.preds[level] != last_locked {
+                guards.push(pred.lock());
+                last_locked = pos.preds[level];
+            }
+
+            let succ = victim.unwrap_or(pos.succs[level]);
+            let succ_removed = victim.is_none() && succ.as_ref().is_some_and(|s| s.marked.load(SeqCst));
+            if pred.marked.load(SeqCst) || succ_removed || pred.next[level].load(SeqCst) != succ {
+                return None;
+            }
+        }
+        Some(guards)
+    }
+}
+
+impl<T: Ord> LazySkipSet<T> {
+    /// Inserts `val` and returns true, or returns false and drops `val` if an equal element is
+    /// already in the set.
+    pub fn insert(&self, val: T) -> bool {
+        let height = gen_height();
+        let pinned = self.pin();
+        loop {
+            let pos = self.search(&val, &pinned);
+            if let Some(level) = pos.found {
+                // SAFETY: the node was reached from the head while pinned
+                let found = unsafe { &*pos.succs[level] };
+                if found.marked.load(SeqCst) {
+                    // it's being removed, so try again once it's gone
+                    continue;
+                }
+                while !found.fully_linked.load(SeqCst) {
+                    hint::spin_loop();
+                }
+                return false;
+            }
+
+            // SAFETY: pos was just returned by search
+            let Some(guards) = (unsafe { self.lock_preds(&pos, height, None) }) else {
+                continue;
+            };
+
+            self.height.fetch_max(height, SeqCst);
+            let node = Box::into_raw(Box::new(Node::new(Some(val), height)));
+            // SAFETY: node was just allocated, and every predecessor is locked and validated,
+            // so none of their links can change until the guards are dropped
+            unsafe {
+                for level in 0..height {
+                    (*node).next[level].store(pos.succs[level], SeqCst);
+                }
+                for level in 0..height {
+                    (*pos.preds[level]).next[level].store(node, SeqCst);
+                }
+                (*node).fully_linked.store(true, SeqCst);
+            }
+            drop(guards);
+            self.len.fetch_add(1, SeqCst);
+            return true;
+        }
+    }
+
+    /// Returns true if the set contains an element equal to `key`. Never locks.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let pinned = self.pin();
+        let pos = self.search(key, &pinned);
+        pos.found.is_some_and(|level| {
+            // SAFETY: the node was reached from the head while pinned
+            let found = unsafe { &*pos.succs[level] };
+            found.fully_linked.load(SeqCst) && !found.marked.load(SeqCst)
+        })
+    }
+
+    /// Removes the element equal to `key` and returns true, or returns false if there is none.
+    /// When several threads remove the same element at once, exactly one of them gets true.
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // the node this call marked, which stays locked until it's unlinked
+        let mut victim = None;
+        let pinned = self.pin();
+        loop {
+            let pos = self.search(key, &pinned);
+            let (node_ptr, node, victim_guard) = match victim.take() {
+                Some(victim) => victim,
+                None => {
+                    let Some(level) = pos.found else {
+                        return false;
+                    };
+                    let node_ptr = pos.succs[level];
+                    // SAFETY: the node was reached from the head while pinned
+                    let node = unsafe { &*node_ptr };
+                    // only remove a node found on its top level, where it can be unlinked everywhere
+                    if !node.fully_linked.load(SeqCst) || node.next.len() != level + 1 || node.marked.load(SeqCst) {
+                        return false;
+                    }
+
+                    let guard = node.lock();
+                    if node.marked.load(SeqCst) {
+                        return false;
+                    }
+                    node.marked.store(true, SeqCst);
+                    self.len.fetch_sub(1, SeqCst);
+                    (node_ptr, node, guard)
+                }
+            };
+
+            // SAFETY: pos was just returned by search
+            let Some(guards) = (unsafe { self.lock_preds(&pos, node.next.len(), Some(node_ptr)) }) else {
+                victim = Some((node_ptr, node, victim_guard));
+                continue;
+            };
+
+            // SAFETY: the node and every predecessor are locked and validated, so no link
+            // involved can change until the guards are dropped
+            unsafe {
+                for level in (0..node.next.len()).rev() {
+                    (*pos.preds[level]).next[level].store(node.next[level].load(SeqCst), SeqCst);
+                }
+            }
+            drop(guards);
+            drop(victim_guard);
+            // SAFETY: node_ptr came from a link, so it isn't null, and unlike a pointer made from
+            // the shared reference to the node it can be used to free it
+            let node_ptr = unsafe { NonNull::new_unchecked(node_ptr) };
+            self.retire(node_ptr, &pinned);
+            drop(pinned);
+            self.collect();
+            return true;
+        }
+    }
+}
+
+impl<T> Default for LazySkipSet<T> {
+    fn default() -> Self {
+        LazySkipSet::new()
+    }
+}
+
+impl<T> Drop for LazySkipSet<T> {
+    fn drop(&mut self) {
+        self.reclaim();
+        let mut node = self.head.next[0].load(SeqCst);
+        while !node.is_null() {
+            // SAFETY: every node still linked on level 0 is owned by the set, and appears once
+            let next = unsafe { Box::from_raw(node) };
+            node = next.next[0].load(SeqCst);
+        }
+    }
+}
+
+impl<T> fmt::Debug for LazySkipSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazySkipSet").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LazySkipSet;
+
+    #[test]
+    fn insert_contains_remove() {
+        let mut s = LazySkipSet::new();
+        assert!(s.is_empty());
+        for i in (0..100).rev() {
+            assert!(s.insert(i));
+        }
+        assert!(!s.insert(50));
+        assert_eq!(s.len(), 100);
+        assert!((0..100).all(|i| s.contains(&i)));
+        assert!(!s.contains(&100));
+
+        for i in (0..100).step_by(2) {
+            assert!(s.remove(&i));
+            assert!(!s.remove(&i));
+        }
+        assert_eq!(s.len(), 50);
+        assert!((0..100).all(|i| s.contains(&i) == (i % 2 == 1)));
+        s.reclaim();
+        assert!(s.insert(0));
+
+        let strings = LazySkipSet::new();
+        strings.insert(String::from("a"));
+        assert!(strings.contains("a"));
+        assert!(strings.remove("a"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn many_threads() {
+        let s = LazySkipSet::new();
+        std::thread::scope(|scope| {
+            for t in 0..8u64 {
+                let s = &s;
+                scope.spawn(move || {
+                    for i in 0..2_000 {
+                        let n = i * 8 + t;
+                        assert!(s.insert(n));
+                        // every thread also races to remove numbers the others insert
+                        s.remove(&(fastrand::u64(0..16_000)));
+                        if n % 3 == 0 {
+                            s.remove(&n);
+                            assert!(!s.contains(&n));
+                        }
+                    }
+                });
+            }
+        });
+
+        let remaining = (0..16_000u64).filter(|n| s.contains(n)).count();
+        assert_eq!(remaining, s.len());
+
+        let removed: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let s = &s;
+                    scope.spawn(move || (0..16_000u64).filter(|n| s.remove(n)).count())
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(removed, remaining);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn removals_free_nodes() {
+        let s = LazySkipSet::new();
+        let retired = |s: &LazySkipSet<u64>| s.retired.lock().unwrap().iter().map(Vec::len).sum::<usize>();
+        std::thread::scope(|scope| {
+            for t in 0..4u64 {
+                let s = &s;
+                scope.spawn(move || {
+                    for i in 0..5_000 {
+                        let n = i * 4 + t;
+                        assert!(s.insert(n));
+                        assert!(s.remove(&n));
+                    }
+                });
+            }
+        });
+        assert!(s.is_empty());
+        // of the 20,000 removed nodes, only the ones removed in the last epochs are left
+        assert!(retired(&s) < 1_000);
+
+        // with nothing else running, every removal moves the epoch on
+        for n in 0..3 {
+            s.insert(n);
+            s.remove(&n);
+        }
+        assert!(retired(&s) <= 2);
+    }
+}
@@ -0,0 +1,478 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FusedIterator;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use super::{gen_height, MAX_HEIGHT};
+
+pub(super) struct Node<T> {
+    pub(super) val: T,
+    // the number of levels the node is linked at, plus one while its insertion is in progress.
+    // Whoever drops the last one retires the node, so it's never freed while still reachable
+    refs: AtomicUsize,
+    // one link per level. A link tagged with 1 marks the node as removed at that level
+    next: Box<[Atomic<Node<T>>]>,
+}
+
+impl<T> Node<T> {
+    /// Returns true once a removal has claimed the node.
+    pub(super) fn is_removed(&self, guard: &Guard) -> bool {
+        self.next[0].load(SeqCst, guard).tag() == 1
+    }
+
+    /// Drops one reference to `node`, retiring it if that was the last one.
+    ///
+    /// SAFETY: `node` must be valid, and the caller must own the reference it drops.
+    unsafe fn release(node: Shared<'_, Node<T>>, guard: &Guard) {
+        if node.deref().refs.fetch_sub(1, SeqCst) == 1 {
+            guard.defer_destroy(node);
+        }
+    }
+}
+
+/// The new node on success, or the node already holding an equal element and the rejected one.
+pub(super) type Inserted<'g, T> = Result<Shared<'g, Node<T>>, (Shared<'g, Node<T>>, T)>;
+
+/// Where a search ended up on every level.
+pub(super) struct Position<'g, T> {
+    // the first unremoved node equal to the key, if there is one
+    pub(super) found: Option<Shared<'g, Node<T>>>,
+    // the links of the last node before the key at every level, or of the head
+    left: [&'g [Atomic<Node<T>>]; MAX_HEIGHT],
+    // the first node not before the key at every level
+    pub(super) right: [Shared<'g, Node<T>>; MAX_HEIGHT],
+}
+
+/// A sorted set that any number of threads can insert into, remove from, and search at the
+/// same time through a shared reference, without taking locks.
+///
+/// This is the lock-free skip list of Fraser and Harris: a removal first marks every link of
+/// its node, then any search that walks past the node unlinks it. Nodes are reclaimed through
+/// `crossbeam-epoch`, so operations pin the current thread while they run.
+pub struct SkipSet<T> {
+    head: [Atomic<Node<T>>; MAX_HEIGHT],
+    // an upper bound on the height of every node, so searches can skip the empty levels above
+    height: AtomicUsize,
+    len: AtomicUsize,
+}
+
+impl<T> SkipSet<T> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        SkipSet { head: Default::default(), height: AtomicUsize::new(1), len: AtomicUsize::new(0) }
+    }
+
+    /// Returns the number of elements in the set. Other threads may change it at any moment.
+    pub fn len(&self) -> usize {
+        self.len.load(SeqCst)
+    }
+
+    /// Returns true if the set contains no elements. Other threads may change it at any moment.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the elements in sorted order, which stay valid for as long as
+    /// `guard` keeps the current thread pinned.
+    ///
+    /// The iterator walks the bottom level without taking locks and skips every node whose
+    /// removal has begun, so other threads can keep inserting and removing while it runs. Every
+    /// element it yields was in the set at some point during the iteration, elements present
+    /// for the whole iteration are always yielded, and elements are yielded in strictly
+    /// increasing order even if they're removed and inserted again along the way.
+    ///
+    /// Holding a guard delays freeing every node removed in the meantime, so long scans should
+    /// be split up when memory matters.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter::new(self.head[0].load(SeqCst, guard), guard)
+    }
+
+    /// Finds the position of a key on every level, unlinking every removed node it walks past.
+    /// `cmp` orders the element of a node against the key.
+    pub(super) fn search_by<'g>(&'g self, cmp: impl Fn(&T) -> Ordering, guard: &'g Guard) -> Position<'g, T> {
+        'retry: loop {
+            let mut pos = Position { found: None, left: [&self.head[..]; MAX_HEIGHT], right: [Shared::null(); MAX_HEIGHT] };
+            let mut pred = &self.head[..];
+            for level in (0..self.height.load(SeqCst)).rev() {
+                let mut curr = pred[level].load(SeqCst, guard);
+                // pred was removed since the search moved onto it
+                if curr.tag() == 1 {
+                    continue 'retry;
+                }
+
+                // SAFETY: the thread is pinned, so nodes reached from the list stay valid
+                while let Some(c) = unsafe { curr.as_ref() } {
+                    let succ = c.next[level].load(SeqCst, guard);
+                    if succ.tag() == 1 {
+                        match pred[level].compare_exchange(curr, succ.with_tag(0), SeqCst, SeqCst, guard) {
+                            // SAFETY: the link just replaced held one of curr's references
+                            Ok(_) => unsafe { Node::release(curr, guard) },
+                            Err(_) => continue 'retry,
+                        }
+                        curr = succ.with_tag(0);
+                        continue;
+                    }
+
+                    match cmp(&c.val) {
+                        Ordering::Less => {
+                            pred = &c.next;
+                            curr = succ;
+                        }
+                        Ordering::Equal if level == 0 => {
+                            pos.found = Some(curr);
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                pos.left[level] = pred;
+                pos.right[level] = curr;
+            }
+            return pos;
+        }
+    }
+
+    fn search<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Position<'g, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search_by(|val| val.borrow().cmp(key), guard)
+    }
+}
+
+impl<T: Ord + Send + 'static> SkipSet<T> {
+    /// Inserts `val` and returns true, or returns false and drops `val` if an equal element is
+    /// already in the set.
+    pub fn insert(&self, val: T) -> bool {
+        let guard = &epoch::pin();
+        self.insert_node(val, false, guard).is_ok()
+    }
+
+    /// Returns true if the set contains an element equal to `key`.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = &epoch::pin();
+        self.search(key, guard).found.is_some()
+    }
+
+    /// Removes the element equal to `key` and returns true, or returns false if there is none.
+    /// When several threads remove the same element at once, exactly one of them gets true.
+    pub fn remove<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = &epoch::pin();
+        self.remove_by(|val| val.borrow().cmp(key), guard).is_some()
+    }
+
+    /// Links a new node holding `val` and returns it. If an equal element is already in the set,
+    /// it's removed first when `replace` is true, and otherwise its node is returned along with
+    /// `val`.
+    pub(super) fn insert_node<'g>(
+        &'g self,
+        val: T,
+        replace: bool,
+        guard: &'g Guard,
+    ) -> Inserted<'g, T> {
+        let mut pos = self.search(&val, guard);
+        while let Some(found) = pos.found {
+            if !replace {
+                return Err((found, val));
+            }
+            self.remove_node(found, guard);
+            pos = self.search(&val, guard);
+        }
+
+        let height = gen_height();
+        self.height.fetch_max(height, SeqCst);
+        let next = (0..height).map(|_| Atomic::null()).collect();
+        // one reference for the level 0 link, and one held until the node is fully linked
+        let node = Owned::new(Node { val, refs: AtomicUsize::new(2), next }).into_shared(guard);
+        // SAFETY: node was just allocated, and is only freed once every reference is released
+        let node_ref = unsafe { node.deref() };
+
+        loop {
+            node_ref.next[0].store(pos.right[0], SeqCst);
+            if pos.left[0][0].compare_exchange(pos.right[0], node, SeqCst, SeqCst, guard).is_ok() {
+                break;
+            }
+            pos = self.search(&node_ref.val, guard);
+            while let Some(found) = pos.found {
+                if !replace {
+                    // SAFETY: node was never linked, so no other thread can have seen it
+                    let node = unsafe { node.into_owned() };
+                    return Err((found, node.into_box().val));
+                }
+                self.remove_node(found, guard);
+                pos = self.search(&node_ref.val, guard);
+            }
+        }
+        self.len.fetch_add(1, SeqCst);
+
+        'build: for level in 1..height {
+            loop {
+                let next = node_ref.next[level].load(SeqCst, guard);
+                // stop building once a removal has started marking the node
+                if next.tag() == 1
+                    || node_ref.next[level].compare_exchange(next, pos.right[level], SeqCst, SeqCst, guard).is_err()
+                {
+                    break 'build;
+                }
+
+                node_ref.refs.fetch_add(1, SeqCst);
+                if pos.left[level][level].compare_exchange(pos.right[level], node, SeqCst, SeqCst, guard).is_ok() {
+                    break;
+                }
+                node_ref.refs.fetch_sub(1, SeqCst);
+
+                pos = self.search(&node_ref.val, guard);
+                if pos.found != Some(node) {
+                    break 'build;
+                }
+            }
+        }
+
+        // a removal may have marked a level after it was linked, and missed it while unlinking
+        if node_ref.next[0].load(SeqCst, guard).tag() == 1 {
+            self.search(&node_ref.val, guard);
+        }
+        // SAFETY: this drops the reference held while building the tower
+        unsafe { Node::release(node, guard) };
+        Ok(node)
+    }
+
+    /// Removes the node whose element `cmp` orders as equal, and returns it if this call was the
+    /// one to remove it.
+    pub(super) fn remove_by<'g>(&'g self, cmp: impl Fn(&T) -> Ordering, guard: &'g Guard) -> Option<Shared<'g, Node<T>>> {
+        loop {
+            let node = self.search_by(&cmp, guard).found?;
+            if self.remove_node(node, guard) {
+                return Some(node);
+            }
+        }
+    }
+
+    /// Marks `node` as removed and unlinks it. Returns true if this call was the one to remove
+    /// it, and false if another thread got there first.
+    ///
+    /// `node` must have been reached from this set while `guard` was pinned.
+    pub(super) fn remove_node(&self, node: Shared<'_, Node<T>>, guard: &Guard) -> bool {
+        // SAFETY: the thread is pinned, so nodes reached from the list stay valid
+        let node_ref = unsafe { node.deref() };
+
+        // mark from the top down, so the node stays reachable on level 0 until the end
+        for link in node_ref.next[1..].iter().rev() {
+            link.fetch_or(1, SeqCst, guard);
+        }
+        if node_ref.next[0].fetch_or(1, SeqCst, guard).tag() != 0 {
+            return false;
+        }
+        self.len.fetch_sub(1, SeqCst);
+        // unlink the node from every level it's on
+        self.search(&node_ref.val, guard);
+        true
+    }
+}
+
+impl<T> Default for SkipSet<T> {
+    fn default() -> Self {
+        SkipSet::new()
+    }
+}
+
+impl<T> Drop for SkipSet<T> {
+    fn drop(&mut self) {
+        // SAFETY: no other thread can access the set anymore, and every node holds exactly one
+        // reference for every level it's linked at, so it's freed after its last level is walked
+        unsafe {
+            let guard = epoch::unprotected();
+            for (level, link) in self.head.iter().enumerate().rev() {
+                let mut node = link.load(SeqCst, guard);
+                while let Some(n) = node.as_ref() {
+                    let next = n.next[level].load(SeqCst, guard).with_tag(0);
+                    if n.refs.fetch_sub(1, SeqCst) == 1 {
+                        drop(node.into_owned());
+                    }
+                    node = next;
+                }
+            }
+        }
+    }
+}
+
+/// An iterator over the elements of a `SkipSet` in sorted order.
+///
+/// Created by [`SkipSet::iter`].
+pub struct Iter<'g, T> {
+    next: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T> Iter<'g, T> {
+    /// Creates an iterator starting at `next`, which must have been reached from a set while
+    /// `guard` was pinned.
+    pub(super) fn new(next: Shared<'g, Node<T>>, guard: &'g Guard) -> Self {
+        Iter { next, guard }
+    }
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<&'g T> {
+        // SAFETY: the guard keeps the thread pinned, so nodes reached from the list stay valid
+        while let Some(node) = unsafe { self.next.as_ref() } {
+            // a removed node's links are marked and never change again, so following them
+            // still leads forward through the list
+            let succ = node.next[0].load(SeqCst, self.guard);
+            self.next = succ.with_tag(0);
+            if succ.tag() == 0 {
+                return Some(&node.val);
+            }
+        }
+        None
+    }
+}
+
+impl<'g, T> FusedIterator for Iter<'g, T> {}
+
+impl<'g, T> fmt::Debug for Iter<'g, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for SkipSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SkipSet").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkipSet;
+
+    #[test]
+    fn insert_contains_remove() {
+        let s = SkipSet::new();
+        assert!(s.is_empty());
+        for i in (0..100).rev() {
+            assert!(s.insert(i));
+        }
+        assert!(!s.insert(50));
+        assert_eq!(s.len(), 100);
+        for i in 0..100 {
+            assert!(s.contains(&i));
+        }
+        assert!(!s.contains(&100));
+
+        for i in (0..100).step_by(2) {
+            assert!(s.remove(&i));
+            assert!(!s.remove(&i));
+        }
+        assert_eq!(s.len(), 50);
+        for i in 0..100 {
+            assert_eq!(s.contains(&i), i % 2 == 1);
+        }
+
+        let strings = SkipSet::new();
+        strings.insert(String::from("a"));
+        assert!(strings.contains("a"));
+        assert!(strings.remove("a"));
+    }
+
+    #[test]
+    fn iter() {
+        let s = SkipSet::new();
+        for i in [5, 1, 4, 2, 3] {
+            s.insert(i);
+        }
+        s.remove(&4);
+        let guard = &crossbeam_epoch::pin();
+        assert!(s.iter(guard).copied().eq([1, 2, 3, 5]));
+
+        let mut iter = s.iter(guard);
+        assert_eq!(iter.next(), Some(&1));
+        s.remove(&2);
+        s.insert(0);
+        s.insert(4);
+        // the removed element is skipped, and the new one before the iterator isn't seen
+        assert!(iter.copied().eq([3, 4, 5]));
+    }
+
+    #[test]
+    fn iter_during_writes() {
+        let s = SkipSet::new();
+        for i in (0..2_000u64).step_by(2) {
+            s.insert(i);
+        }
+        std::thread::scope(|scope| {
+            for t in 0..2u64 {
+                let s = &s;
+                scope.spawn(move || {
+                    for _ in 0..4_000 {
+                        let n = fastrand::u64(0..2_000) / 2 * 2 + 1;
+                        if t == 0 {
+                            s.insert(n);
+                        } else {
+                            s.remove(&n);
+                        }
+                    }
+                });
+            }
+            for _ in 0..20 {
+                let guard = &crossbeam_epoch::pin();
+                let seen: Vec<u64> = s.iter(guard).copied().collect();
+                // only odd elements change, so every even one is always seen, in order
+                assert!(seen.windows(2).all(|w| w[0] < w[1]));
+                assert!(seen.iter().copied().filter(|n| n % 2 == 0).eq((0..2_000).step_by(2)));
+            }
+        });
+    }
+
+    #[test]
+    fn many_threads() {
+        let s = SkipSet::new();
+        std::thread::scope(|scope| {
+            for t in 0..8u64 {
+                let s = &s;
+                scope.spawn(move || {
+                    for i in 0..2_000 {
+                        let n = i * 8 + t;
+                        assert!(s.insert(n));
+                        // every thread also races to remove numbers the others insert
+                        s.remove(&(fastrand::u64(0..16_000)));
+                        if n % 3 == 0 {
+                            s.remove(&n);
+                            assert!(!s.contains(&n));
+                        }
+                    }
+                });
+            }
+        });
+
+        let remaining = (0..16_000u64).filter(|n| s.contains(n)).count();
+        assert_eq!(remaining, s.len());
+
+        let removed: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let s = &s;
+                    scope.spawn(move || (0..16_000u64).filter(|n| s.remove(n)).count())
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(removed, remaining);
+        assert!(s.is_empty());
+        assert!((0..16_000u64).all(|n| !s.contains(&n)));
+    }
+}
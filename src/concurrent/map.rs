@@ -0,0 +1,386 @@
+//! A concurrent ordered map built on the lock-free [`SkipSet`].
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+use crossbeam_epoch::{self as epoch, Guard, Shared};
+
+use super::lock_free::{self, Node, SkipSet};
+
+/// A key and its value, ordered by the key alone.
+struct MapEntry<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<K: Ord, V> PartialEq for MapEntry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Ord, V> Eq for MapEntry<K, V> {}
+
+impl<K: Ord, V> PartialOrd for MapEntry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for MapEntry<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// An ordered map that any number of threads can read and modify at the same time through a
+/// shared reference, without taking locks.
+///
+/// Lookups hand out an [`Entry`], which keeps the current thread pinned so the entry's key and
+/// value stay valid even if another thread removes it in the meantime. Values can't be changed
+/// in place; inserting a key again replaces its entry with a new one.
+pub struct SkipMap<K, V> {
+    set: SkipSet<MapEntry<K, V>>,
+}
+
+impl<K, V> SkipMap<K, V> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        SkipMap { set: SkipSet::new() }
+    }
+
+    /// Returns the number of entries in the map. Other threads may change it at any moment.
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Returns true if the map contains no entries. Other threads may change it at any moment.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Returns an iterator over the entries sorted by key, which stay valid for as long as
+    /// `guard` keeps the current thread pinned. See [`SkipSet::iter`] for what it sees of
+    /// concurrent updates.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter { inner: self.set.iter(guard) }
+    }
+
+    /// Returns an iterator over the entries whose keys fall in `range`, sorted by key. Like
+    /// [`SkipMap::iter`], they stay valid for as long as `guard` keeps the thread pinned.
+    pub fn range<'g, Q, R>(&'g self, range: R, guard: &'g Guard) -> Range<'g, Q, R, K, V>
+    where
+        K: Borrow<Q>,
+        R: RangeBounds<Q>,
+        Q: Ord + ?Sized,
+    {
+        let start = self
+            .set
+            .search_by(
+                |entry| match range.start_bound() {
+                    Bound::Included(start) if entry.key.borrow() < start => Ordering::Less,
+                    Bound::Excluded(start) if entry.key.borrow() <= start => Ordering::Less,
+                    _ => Ordering::Greater,
+                },
+                guard,
+            )
+            .right[0];
+        Range { inner: Some(lock_free::Iter::new(start, guard)), range, _marker: PhantomData }
+    }
+
+    /// Wraps `node`, which must have been reached from this map while `guard` was pinned.
+    fn entry(&self, node: *const Node<MapEntry<K, V>>, guard: Guard) -> Entry<'_, K, V> {
+        Entry { map: self, node, guard }
+    }
+}
+
+impl<K: Ord + Send + 'static, V: Send + 'static> SkipMap<K, V> {
+    /// Returns the entry for `key`, if there is one.
+    pub fn get<Q>(&self, key: &Q) -> Option<Entry<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let node = self.set.search_by(|entry| entry.key.borrow().cmp(key), &guard).found?.as_raw();
+        Some(self.entry(node, guard))
+    }
+
+    /// Returns true if the map contains an entry for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts `value` for `key`, replacing the entry already there, and returns the new entry.
+    ///
+    /// The old entry is removed before the new one is linked, so other threads may briefly find
+    /// no entry for `key` at all.
+    pub fn insert(&self, key: K, value: V) -> Entry<'_, K, V> {
+        let guard = epoch::pin();
+        let Ok(node) = self.set.insert_node(MapEntry { key, value }, true, &guard).map(|node| node.as_raw()) else {
+            unreachable!("inserting with replace never fails");
+        };
+        self.entry(node, guard)
+    }
+
+    /// Returns the entry for `key`, inserting `value` if there is none. `value` is dropped if
+    /// the key was already present.
+    pub fn get_or_insert(&self, key: K, value: V) -> Entry<'_, K, V> {
+        self.get_or_insert_with(key, || value)
+    }
+
+    /// Returns the entry for `key`, inserting the result of `f` if there is none. `f` is only
+    /// called if the key is missing, but its result is dropped if another thread inserts the
+    /// key first.
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> Entry<'_, K, V> {
+        let guard = epoch::pin();
+        if let Some(node) = self.set.search_by(|entry| entry.key.cmp(&key), &guard).found.map(|node| node.as_raw()) {
+            return self.entry(node, guard);
+        }
+        let node = match self.set.insert_node(MapEntry { key, value: f() }, false, &guard) {
+            Ok(node) | Err((node, _)) => node.as_raw(),
+        };
+        self.entry(node, guard)
+    }
+
+    /// Removes the entry for `key` and returns it, or returns None if there is none. When several
+    /// threads remove the same key at once, exactly one of them gets the entry.
+    pub fn remove<Q>(&self, key: &Q) -> Option<Entry<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = epoch::pin();
+        let node = self.set.remove_by(|entry| entry.key.borrow().cmp(key), &guard)?.as_raw();
+        Some(self.entry(node, guard))
+    }
+}
+
+impl<K, V> Default for SkipMap<K, V> {
+    fn default() -> Self {
+        SkipMap::new()
+    }
+}
+
+impl<K, V> fmt::Debug for SkipMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SkipMap").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+/// A reference to an entry of a `SkipMap`.
+///
+/// The entry keeps the current thread pinned, so its key and value stay valid for as long as
+/// it's alive, even after another thread removes it from the map. Dropping entries promptly
+/// lets removed entries be freed. Returned by [`SkipMap::get`], [`SkipMap::insert`] and friends.
+pub struct Entry<'a, K, V> {
+    map: &'a SkipMap<K, V>,
+    // reached from map while guard was pinned, so it stays valid until guard is dropped
+    node: *const Node<MapEntry<K, V>>,
+    guard: Guard,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    fn node(&self) -> &Node<MapEntry<K, V>> {
+        // SAFETY: the guard has kept the thread pinned since the node was reached from the map
+        unsafe { &*self.node }
+    }
+
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        &self.node().val.key
+    }
+
+    /// Returns the value of this entry.
+    pub fn value(&self) -> &V {
+        &self.node().val.value
+    }
+
+    /// Returns true if the entry has been removed from the map, by this thread or another one.
+    pub fn is_removed(&self) -> bool {
+        self.node().is_removed(&self.guard)
+    }
+}
+
+impl<'a, K: Ord + Send + 'static, V: Send + 'static> Entry<'a, K, V> {
+    /// Removes this entry from the map, and returns true if this call was the one to remove it.
+    /// Its key and value stay readable afterwards.
+    pub fn remove(&self) -> bool {
+        self.map.set.remove_node(Shared::from(self.node), &self.guard)
+    }
+}
+
+impl<'a, K: fmt::Debug, V: fmt::Debug> fmt::Debug for Entry<'a, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entry").field("key", self.key()).field("value", self.value()).finish()
+    }
+}
+
+/// An iterator over the entries of a `SkipMap`, sorted by key.
+///
+/// Created by [`SkipMap::iter`].
+pub struct Iter<'g, K, V> {
+    inner: lock_free::Iter<'g, MapEntry<K, V>>,
+}
+
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<'g, K, V> FusedIterator for Iter<'g, K, V> {}
+
+impl<'g, K, V> fmt::Debug for Iter<'g, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the entries of a `SkipMap` whose keys fall in a range, sorted by key.
+///
+/// Created by [`SkipMap::range`].
+pub struct Range<'g, Q: ?Sized, R, K, V> {
+    // None once the iterator has passed the end of the range
+    inner: Option<lock_free::Iter<'g, MapEntry<K, V>>>,
+    range: R,
+    _marker: PhantomData<fn(&Q)>,
+}
+
+impl<'g, Q, R, K, V> Iterator for Range<'g, Q, R, K, V>
+where
+    K: Borrow<Q>,
+    R: RangeBounds<Q>,
+    Q: Ord + ?Sized,
+{
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.as_mut()?.next();
+        let in_range = entry.is_some_and(|entry| match self.range.end_bound() {
+            Bound::Included(end) => entry.key.borrow() <= end,
+            Bound::Excluded(end) => entry.key.borrow() < end,
+            Bound::Unbounded => true,
+        });
+        if !in_range {
+            self.inner = None;
+            return None;
+        }
+        entry.map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<'g, Q, R, K, V> FusedIterator for Range<'g, Q, R, K, V>
+where
+    K: Borrow<Q>,
+    R: RangeBounds<Q>,
+    Q: Ord + ?Sized,
+{
+}
+
+impl<'g, Q: ?Sized, R, K, V> fmt::Debug for Range<'g, Q, R, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Range").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkipMap;
+
+    #[test]
+    fn get_insert_remove() {
+        let m = SkipMap::new();
+        assert!(m.is_empty());
+        for i in (0..50).rev() {
+            assert_eq!(*m.insert(i, i * 10).value(), i * 10);
+        }
+        assert_eq!(m.len(), 50);
+        assert_eq!(m.get(&7).map(|e| *e.value()), Some(70));
+        assert!(m.get(&50).is_none());
+
+        assert_eq!(*m.insert(7, 0).value(), 0);
+        assert_eq!(m.len(), 50);
+        assert_eq!(*m.get(&7).unwrap().value(), 0);
+        assert_eq!(*m.get_or_insert(7, 1).value(), 0);
+        assert_eq!(*m.get_or_insert(50, 500).value(), 500);
+
+        let entry = m.remove(&3).unwrap();
+        assert_eq!((*entry.key(), *entry.value()), (3, 30));
+        assert!(entry.is_removed());
+        assert!(m.remove(&3).is_none());
+        assert!(!m.contains_key(&3));
+
+        let entry = m.get(&4).unwrap();
+        assert!(entry.remove());
+        assert!(!entry.remove());
+        assert_eq!(*entry.value(), 40);
+        assert_eq!(m.len(), 49);
+
+        let strings = SkipMap::new();
+        strings.insert(String::from("a"), 1);
+        assert!(strings.contains_key("a"));
+        assert_eq!(strings.remove("a").map(|e| *e.value()), Some(1));
+    }
+
+    #[test]
+    fn iter_and_range() {
+        let m = SkipMap::new();
+        for i in 0..20 {
+            m.insert(i, -i);
+        }
+        m.remove(&10);
+        let guard = &crossbeam_epoch::pin();
+        assert!(m.iter(guard).map(|(&k, &v)| (k, v)).eq((0..20).filter(|&i| i != 10).map(|i| (i, -i))));
+        assert!(m.range(5..12, guard).map(|(&k, _)| k).eq([5, 6, 7, 8, 9, 11]));
+        assert!(m.range(..=3, guard).map(|(&k, _)| k).eq(0..=3));
+        assert!(m.range((std::ops::Bound::Excluded(17), std::ops::Bound::Unbounded), guard).map(|(&k, _)| k).eq(18..20));
+        assert_eq!(m.range(30.., guard).count(), 0);
+    }
+
+    #[test]
+    fn entries_outlive_removal() {
+        let m = SkipMap::new();
+        std::thread::scope(|scope| {
+            for t in 0..4u64 {
+                let m = &m;
+                scope.spawn(move || {
+                    for i in 0..1_000 {
+                        let key = fastrand::u64(0..64);
+                        match i % 3 {
+                            0 => {
+                                let entry = m.insert(key, format!("{}-{}", key, t));
+                                assert_eq!(*entry.key(), key);
+                            }
+                            1 => {
+                                if let Some(entry) = m.remove(&key) {
+                                    assert!(entry.value().starts_with(&format!("{}-", key)));
+                                }
+                            }
+                            _ => {
+                                if let Some(entry) = m.get(&key) {
+                                    // other threads may remove it, but it stays readable
+                                    assert!(entry.value().starts_with(&format!("{}-", key)));
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let guard = &crossbeam_epoch::pin();
+        assert_eq!(m.iter(guard).count(), m.len());
+        assert!(m.iter(guard).all(|(k, v)| v.starts_with(&format!("{}-", k))));
+    }
+}
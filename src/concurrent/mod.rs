@@ -0,0 +1,35 @@
+//! Skip lists that many threads can read and modify at once through a shared reference.
+//!
+//! [`LazySkipSet`] takes a lock per node for updates and needs nothing beyond `std`. With the
+//! `concurrent` feature, [`SkipSet`] does without locks entirely, and frees removed nodes
+//! through `crossbeam-epoch` so a thread still looking at a node never sees it freed. Iterating
+//! over a `SkipSet` borrows a [`Guard`] from [`pin`] for as long as the elements are in use.
+//! [`SkipMap`] is the ordered map built on the same lists. With the `hazard` feature,
+//! [`HazardSkipSet`] is the same lock-free set reclaiming nodes with hazard pointers instead,
+//! which keeps the garbage bounded even when a thread stalls mid-operation.
+
+#[cfg(feature = "hazard")]
+mod hazard;
+mod lazy;
+#[cfg(feature = "concurrent")]
+mod lock_free;
+#[cfg(feature = "concurrent")]
+pub mod map;
+
+#[cfg(feature = "hazard")]
+pub use hazard::HazardSkipSet;
+pub use lazy::LazySkipSet;
+#[cfg(feature = "concurrent")]
+pub use lock_free::{Iter, SkipSet};
+#[cfg(feature = "concurrent")]
+pub use map::SkipMap;
+#[cfg(feature = "concurrent")]
+pub use crossbeam_epoch::{pin, Guard};
+
+const MAX_HEIGHT: usize = 32;
+
+/// Returns the number of levels a new node reaches, between 1 and `MAX_HEIGHT`.
+fn gen_height() -> usize {
+    let mask = (1 << (MAX_HEIGHT - 1)) - 1;
+    (fastrand::u64(..) & mask).trailing_ones() as usize + 1
+}
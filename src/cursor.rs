@@ -0,0 +1,472 @@
+use core::cmp::Ordering;
+use core::ptr::NonNull;
+
+use crate::{Allocator, Comparator, Geometric, Global, LevelGenerator, Link, NodeRef, OrdComparator, SkipList, SkipListNode};
+
+/// A read-only cursor over a `SkipList` that can move back and forth.
+///
+/// Like [`CursorMut`], the cursor is either on an element or on the "ghost" position between
+/// the last and the first element.
+///
+/// Created by [`SkipList::lower_bound`] and [`SkipList::upper_bound`].
+pub struct Cursor<'a, T, const NUM_LEVELS: usize, C = OrdComparator, G = Geometric, A: Allocator = Global> {
+    list: &'a SkipList<T, NUM_LEVELS, C, G, A>,
+    current: Option<NodeRef<'a, T, NUM_LEVELS>>,
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Cursor<'a, T, NUM_LEVELS, C, G, A> {
+    /// Creates a cursor on `current`, which must be None or a non-head node of `list`.
+    pub(crate) fn new(list: &'a SkipList<T, NUM_LEVELS, C, G, A>, current: Option<NodeRef<'a, T, NUM_LEVELS>>) -> Self {
+        Cursor { list, current }
+    }
+
+    /// Returns a reference to the element under the cursor, or None on the ghost.
+    pub fn current(&self) -> Option<&'a T> {
+        self.current.and_then(|node| node.val())
+    }
+
+    /// Returns a reference to the element after the cursor. On the ghost, that's the first element.
+    pub fn peek_next(&self) -> Option<&'a T> {
+        self.next_node().and_then(|node| node.val())
+    }
+
+    /// Returns a reference to the element before the cursor. On the ghost, that's the last element.
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        self.prev_node().and_then(|node| node.val())
+    }
+
+    /// Moves the cursor to the next element. Moving past the last element lands on the ghost,
+    /// and moving past the ghost wraps around to the first element.
+    pub fn move_next(&mut self) {
+        self.current = self.next_node();
+    }
+
+    /// Moves the cursor to the previous element. Moving before the first element lands on the
+    /// ghost, and moving before the ghost wraps around to the last element.
+    pub fn move_prev(&mut self) {
+        self.current = self.prev_node();
+    }
+
+    fn next_node(&self) -> Option<NodeRef<'a, T, NUM_LEVELS>> {
+        match self.current {
+            Some(node) => node.next(0),
+            None => self.list.head.next(0),
+        }
+    }
+
+    fn prev_node(&self) -> Option<NodeRef<'a, T, NUM_LEVELS>> {
+        match self.current {
+            Some(node) => node.prev().filter(|prev| !prev.is_head()),
+            // SAFETY: If a link is Some, it points to a SkipListNode
+            None => self.list.tail.map(|tail| unsafe { NodeRef::new(tail) }),
+        }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Clone for Cursor<'a, T, NUM_LEVELS, C, G, A> {
+    fn clone(&self) -> Self {
+        Cursor { list: self.list, current: self.current }
+    }
+}
+
+/// A cursor over a `SkipList` that can move back and forth and edit the list in place.
+///
+/// The cursor is either on an element or on a "ghost" position past the end of the list,
+/// between the last and the first element. It keeps the predecessors of its position at every
+/// level, so removing and inserting around it never has to search from the head again.
+///
+/// Created by [`SkipList::cursor_front_mut`], [`SkipList::cursor_back_mut`] and [`SkipList::cursor_at`].
+pub struct CursorMut<'a, T, const NUM_LEVELS: usize, C = OrdComparator, G = Geometric, A: Allocator = Global> {
+    list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>,
+    current: Link<T, NUM_LEVELS>,
+    // the last node before `current` at every level, or the last node of every level on the ghost
+    path: [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+}
+
+// SAFETY: current and path only point into the list the cursor borrows mutably, so it's as
+// thread-safe as that borrow
+unsafe impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Send for CursorMut<'a, T, NUM_LEVELS, C, G, A> where
+    SkipList<T, NUM_LEVELS, C, G, A>: Send
+{
+}
+unsafe impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Sync for CursorMut<'a, T, NUM_LEVELS, C, G, A> where
+    SkipList<T, NUM_LEVELS, C, G, A>: Sync
+{
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> CursorMut<'a, T, NUM_LEVELS, C, G, A> {
+    /// Creates a cursor on `current`.
+    ///
+    /// SAFETY: `path` must hold the last node before `current` at every level, or the last node of
+    /// every level if `current` is None.
+    pub(crate) unsafe fn new(
+        list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>,
+        current: Link<T, NUM_LEVELS>,
+        path: [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    ) -> Self {
+        CursorMut { list, current, path }
+    }
+
+    /// Returns a mutable reference to the element under the cursor, or None on the ghost.
+    ///
+    /// The element must not be modified in a way that changes its position in the list.
+    pub fn current(&mut self) -> Option<&mut T> {
+        // SAFETY: If a link is Some, it points to a SkipListNode, and the list is mutably borrowed
+        self.current.and_then(|mut node| unsafe { node.as_mut() }.val.as_mut())
+    }
+
+    /// Returns a reference to the element after the cursor. On the ghost, that's the first element.
+    pub fn peek_next(&self) -> Option<&T> {
+        let next = match self.current {
+            // SAFETY: If a link is Some, it points to a SkipListNode
+            Some(node) => unsafe { NodeRef::new(node) }.next(0),
+            None => self.list.head.next(0),
+        };
+        next.and_then(|node| node.val())
+    }
+
+    /// Returns a reference to the element before the cursor. On the ghost, that's the last element.
+    pub fn peek_prev(&self) -> Option<&T> {
+        // SAFETY: path[0] is the head or a node in the list
+        unsafe { self.path[0].as_ref() }.val()
+    }
+
+    /// Moves the cursor to the next element. Moving past the last element lands on the ghost,
+    /// and moving past the ghost wraps around to the first element.
+    pub fn move_next(&mut self) {
+        match self.current {
+            Some(node) => {
+                // SAFETY: If a link is Some, it points to a SkipListNode
+                let node_ref = unsafe { NodeRef::new(node) };
+                self.path[..=node_ref.level].fill(node);
+                self.current = node_ref.tower()[0].next;
+            }
+            None => {
+                self.path = [self.list.head.0; NUM_LEVELS];
+                self.current = self.list.head.tower()[0].next;
+            }
+        }
+    }
+
+    /// Moves the cursor to the previous element. Moving before the first element lands on the
+    /// ghost, and moving before the ghost wraps around to the last element.
+    pub fn move_prev(&mut self) {
+        // SAFETY: path[0] is the head or a node in the list
+        let prev = self.path[0];
+        if unsafe { prev.as_ref() }.is_head() {
+            self.current = None;
+            self.path = self.list.find_path_mut(|_| true);
+        } else {
+            self.current = Some(prev);
+            // SAFETY: prev is a node in the list
+            unsafe { self.retreat_path(prev) };
+        }
+    }
+
+    /// Updates `path` after moving back onto `node`, walking back along level 0 to find the
+    /// predecessors of `node` at the levels it occupies. Higher levels don't change.
+    ///
+    /// SAFETY: node must be a non-head node in the list, and `path` must be valid for the position right after it.
+    unsafe fn retreat_path(&mut self, node: NonNull<SkipListNode<T, NUM_LEVELS>>) {
+        let node = node.as_ref();
+        let mut prev = node.prev.unwrap();
+        for level in 0..=node.level {
+            while prev.as_ref().level < level && !prev.as_ref().is_head() {
+                prev = prev.as_ref().prev.unwrap();
+            }
+            self.path[level] = prev;
+        }
+    }
+
+    /// Removes the element under the cursor and returns it, moving the cursor to the next
+    /// element. Does nothing on the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.current?;
+        // SAFETY: If a link is Some, it points to a SkipListNode
+        self.current = unsafe { SkipListNode::tower(node) }[0].next;
+        // SAFETY: path holds the predecessors of node at every level
+        Some(unsafe { self.list.unlink(node, &self.path) })
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C: Comparator<T>, G: LevelGenerator, A: Allocator> CursorMut<'a, T, NUM_LEVELS, C, G, A> {
+    /// Inserts `item` right before the cursor (at the back of the list on the ghost), unless
+    /// that would break the list's order, in which case `item` is handed back.
+    pub fn insert_before(&mut self, item: T) -> Result<(), T> {
+        let next = self.current.and_then(|node| unsafe { node.as_ref() }.val());
+        let cmp = &self.list.cmp;
+        if self.peek_prev().is_some_and(|prev| cmp.compare(prev, &item) == Ordering::Greater)
+            || next.is_some_and(|next| cmp.compare(&item, next) == Ordering::Greater)
+        {
+            return Err(item);
+        }
+
+        let level = self.list.gen_level();
+        // SAFETY: path holds the last node before the cursor at every level, and the order was checked above
+        let new_node = unsafe { self.list.link(item, level, &self.path) };
+        self.path[..=level].fill(new_node);
+        Ok(())
+    }
+
+    /// Inserts `item` right after the cursor (at the front of the list on the ghost), unless
+    /// that would break the list's order, in which case `item` is handed back.
+    pub fn insert_after(&mut self, item: T) -> Result<(), T> {
+        let current = self.current.and_then(|node| unsafe { node.as_ref() }.val());
+        let cmp = &self.list.cmp;
+        if current.is_some_and(|current| cmp.compare(current, &item) == Ordering::Greater)
+            || self.peek_next().is_some_and(|next| cmp.compare(&item, next) == Ordering::Greater)
+        {
+            return Err(item);
+        }
+
+        let level = self.list.gen_level();
+        let head = self.list.head.0;
+        let mut preds = self.path;
+        match self.current {
+            // SAFETY: If a link is Some, it points to a SkipListNode
+            Some(node) => preds[..=unsafe { node.as_ref() }.level].fill(node),
+            None => preds = [head; NUM_LEVELS],
+        }
+
+        // SAFETY: preds holds the last node up to and including the cursor at every level, and the order was checked above
+        let new_node = unsafe { self.list.link(item, level, &preds) };
+        if self.current.is_none() {
+            // on the ghost, the new node is the last one of every level that was empty
+            for prev in self.path[..=level].iter_mut().filter(|prev| *prev == &head) {
+                *prev = new_node;
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts `item` right before the cursor like [`insert_before`](CursorMut::insert_before)
+    /// if that keeps the list in order, which takes no search, like C++'s `set::insert(hint, x)`.
+    /// Otherwise `item` goes where a search puts it, like [`SkipList::insert`]. Either way the
+    /// cursor stays where it is, and the list's duplicate policy doesn't apply.
+    ///
+    /// A cursor on the ghost is the right hint for items larger than every element, and one
+    /// moved along with the inserts is for items that come in order.
+    pub fn insert_with_hint(&mut self, item: T) {
+        let item = match self.insert_before(item) {
+            Ok(()) => return,
+            Err(item) => item,
+        };
+        // the item goes either after the cursor or before the element before it
+        let cmp = &self.list.cmp;
+        let before_cursor = self.peek_prev().is_some_and(|prev| cmp.compare(&item, prev) == Ordering::Less);
+
+        let path = self.list.find_path_by_mut(|cmp, v| cmp.compare(&item, v) != Ordering::Less);
+        let level = self.list.gen_level();
+        // SAFETY: path holds the last node <= item at every level
+        let new_node = unsafe { self.list.link(item, level, &path) };
+        if before_cursor {
+            // the new node is the cursor's predecessor wherever nothing was left between them
+            for (prev, new_prev) in self.path[..=level].iter_mut().zip(&path) {
+                if prev == new_prev {
+                    *prev = new_node;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SkipList;
+
+    fn list(nums: impl IntoIterator<Item = i32>) -> SkipList<i32, 8> {
+        let mut l = SkipList::new();
+        l.extend(nums);
+        l
+    }
+
+    #[test]
+    fn move_and_peek() {
+        let mut l = list(0..10);
+        let mut cursor = l.cursor_front_mut();
+        assert_eq!(cursor.current().copied(), Some(0));
+        assert_eq!(cursor.peek_prev(), None);
+        assert_eq!(cursor.peek_next(), Some(&1));
+
+        for i in 1..10 {
+            cursor.move_next();
+            assert_eq!(cursor.current().copied(), Some(i));
+        }
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_prev(), Some(&9));
+        assert_eq!(cursor.peek_next(), Some(&0));
+        cursor.move_next();
+        assert_eq!(cursor.current().copied(), Some(0));
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        for i in (0..10).rev() {
+            cursor.move_prev();
+            assert_eq!(cursor.current().copied(), Some(i));
+        }
+
+        let mut cursor = l.cursor_back_mut();
+        assert_eq!(cursor.current().copied(), Some(9));
+        let mut cursor = l.cursor_at(&4);
+        assert_eq!(cursor.current().copied(), Some(4));
+        assert!(l.cursor_at(&100).current().is_none());
+    }
+
+    #[test]
+    fn remove_current() {
+        let mut l = list(0..100);
+        let mut cursor = l.cursor_at(&10);
+        for i in 10..20 {
+            assert_eq!(cursor.remove_current(), Some(i));
+        }
+        assert_eq!(cursor.current().copied(), Some(20));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(8));
+        assert_eq!(cursor.current().copied(), Some(9));
+
+        let mut cursor = l.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(99));
+        assert_eq!(cursor.remove_current(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(98));
+
+        assert_eq!(l.len(), 100 - 13);
+        assert_eq!(l.last(), Some(&97));
+        assert!(l.iter().copied().eq((0..8).chain(9..10).chain(20..98)));
+        for i in 20..98 {
+            assert!(l.contains(&i));
+        }
+    }
+
+    #[test]
+    fn insert_around_cursor() {
+        let mut l = list((0..20).map(|i| i * 10));
+        let mut cursor = l.cursor_at(&50);
+        for i in 41..50 {
+            assert_eq!(cursor.insert_before(i), Ok(()));
+        }
+        for i in (51..60).rev() {
+            assert_eq!(cursor.insert_after(i), Ok(()));
+        }
+        assert_eq!(cursor.insert_before(60), Err(60));
+        assert_eq!(cursor.insert_after(40), Err(40));
+        assert_eq!(cursor.current().copied(), Some(50));
+        assert_eq!(cursor.peek_prev(), Some(&49));
+        assert_eq!(cursor.peek_next(), Some(&51));
+
+        // inserting on the ghost appends or prepends
+        let mut cursor = l.cursor_at(&1000);
+        assert_eq!(cursor.insert_before(1000), Ok(()));
+        assert_eq!(cursor.insert_before(1001), Ok(()));
+        assert_eq!(cursor.insert_after(-1), Ok(()));
+        assert_eq!(cursor.insert_after(-2), Ok(()));
+        assert_eq!(cursor.peek_prev(), Some(&1001));
+        assert_eq!(cursor.insert_before(0), Err(0));
+
+        let mut expected: Vec<i32> = (0..20).map(|i| i * 10).chain(41..50).chain(51..60).collect();
+        expected.extend([1000, 1001, -1, -2]);
+        expected.sort();
+        assert!(l.iter().eq(expected.iter()));
+        assert!(l.iter().rev().eq(expected.iter().rev()));
+        for i in expected.iter() {
+            assert!(l.contains(i));
+        }
+        assert_eq!(l.len(), expected.len());
+
+        let mut e = SkipList::<i32, 8>::new();
+        let mut cursor = e.cursor_front_mut();
+        assert_eq!(cursor.insert_after(2), Ok(()));
+        assert_eq!(cursor.insert_before(3), Ok(()));
+        assert_eq!(cursor.insert_after(1), Ok(()));
+        assert!(e.iter().copied().eq(1..4));
+        assert_eq!(e.pop_back(), Some(3));
+    }
+
+    #[test]
+    fn bounds() {
+        use std::ops::Bound::*;
+
+        let l = list((0..10).map(|i| i * 2));
+        assert_eq!(l.lower_bound(Included(&4)).current(), Some(&4));
+        assert_eq!(l.lower_bound(Excluded(&4)).current(), Some(&6));
+        assert_eq!(l.lower_bound(Included(&5)).current(), Some(&6));
+        assert_eq!(l.lower_bound(Unbounded).current(), Some(&0));
+        assert_eq!(l.lower_bound(Included(&19)).current(), None);
+
+        assert_eq!(l.upper_bound(Included(&4)).current(), Some(&4));
+        assert_eq!(l.upper_bound(Excluded(&4)).current(), Some(&2));
+        assert_eq!(l.upper_bound(Included(&5)).current(), Some(&4));
+        assert_eq!(l.upper_bound(Unbounded).current(), Some(&18));
+        assert_eq!(l.upper_bound(Excluded(&0)).current(), None);
+
+        // scan the neighborhood of a boundary in both directions
+        let mut cursor = l.lower_bound(Included(&7));
+        assert_eq!(cursor.peek_prev(), Some(&6));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&4));
+        let mut forward = cursor.clone();
+        forward.move_next();
+        assert_eq!(forward.current(), Some(&6));
+
+        let mut cursor = l.upper_bound(Excluded(&0));
+        assert_eq!(cursor.peek_next(), Some(&0));
+        assert_eq!(cursor.peek_prev(), Some(&18));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&18));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&0));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+    }
+
+    #[test]
+    fn partition_point() {
+        let l = list((0..1000).map(|i| i * 2));
+        assert_eq!(l.partition_point(|&x| x < 500).current(), Some(&500));
+        assert_eq!(l.partition_point(|&x| x * x <= 1000).current(), Some(&32));
+        assert_eq!(l.partition_point(|_| false).current(), Some(&0));
+        let cursor = l.partition_point(|_| true);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_prev(), Some(&1998));
+        assert_eq!(list(0..0).partition_point(|_| true).current(), None);
+    }
+
+    #[test]
+    fn insert_with_hint() {
+        let mut l = list((0..50).map(|i| i * 2));
+        let mut cursor = l.cursor_at(&40);
+        // right before the cursor, and anywhere else when the hint is wrong
+        cursor.insert_with_hint(39);
+        cursor.insert_with_hint(40);
+        cursor.insert_with_hint(3);
+        cursor.insert_with_hint(77);
+        assert_eq!(cursor.peek_prev(), Some(&40));
+        assert_eq!(cursor.current().copied(), Some(40));
+        // the cursor's path still holds its predecessors
+        assert_eq!(cursor.remove_current(), Some(40));
+        assert_eq!(cursor.remove_current(), Some(42));
+        assert!(cursor.insert_before(41).is_ok());
+
+        // items in order, with the cursor on the ghost
+        let mut cursor = l.cursor_back_mut();
+        cursor.move_next();
+        for i in 100..200 {
+            cursor.insert_with_hint(i);
+        }
+        cursor.insert_with_hint(-1);
+        assert!(cursor.insert_before(200).is_ok());
+
+        let mut expected: Vec<i32> = (0..50).map(|i| i * 2).filter(|&i| i != 42).collect();
+        expected.extend([-1, 3, 39, 41, 77]);
+        expected.extend(100..201);
+        expected.sort();
+        assert!(l.iter().eq(&expected));
+        for (i, v) in expected.iter().enumerate() {
+            assert_eq!(l.get(i), Some(v));
+        }
+    }
+}
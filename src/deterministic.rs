@@ -0,0 +1,491 @@
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use core::mem;
+use core::ptr::NonNull;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use alloc::vec;
+
+use crate::{Comparator, OrdComparator};
+
+// INVARIANT: if a link is Some, it must point to a DetNode
+type DetLink<T> = Option<NonNull<DetNode<T>>>;
+
+struct DetNode<T> {
+    val: Option<T>,
+    // one link per level the node reaches. Towers grow and shrink as the list rebalances,
+    // so a node's level is next.len() - 1
+    next: Vec<DetLink<T>>,
+}
+
+// SAFETY: a node's links only lead to the nodes after it in the same list, which are never
+// mutated through a shared reference
+unsafe impl<T: Send> Send for DetNode<T> {}
+unsafe impl<T: Sync> Sync for DetNode<T> {}
+
+impl<T> DetNode<T> {
+    fn next(&self, level: usize) -> Option<&Self> {
+        // SAFETY: If a link is Some, it points to a DetNode
+        unsafe { self.next[level].map(|p| p.as_ref()) }
+    }
+}
+
+/// A sorted list that keeps its balance structurally instead of with random levels, so every
+/// search, insertion and removal takes O(log n) steps in the worst case rather than on average.
+///
+/// This is the 1-2-3 skip list of Munro, Papadakis and Sedgewick: between two consecutive
+/// nodes reaching some level, there are always 1, 2 or 3 nodes that reach exactly one level
+/// lower. Insertions split gaps of 3 on the way down by raising their middle node, and removals
+/// widen gaps of 1 on the way down by merging with or borrowing from a neighbouring gap.
+pub struct DeterministicSkipList<T, C = OrdComparator> {
+    // the head's highest level is always empty, and the one below it is empty only if the list is
+    head: Box<DetNode<T>>,
+    len: usize,
+    cmp: C,
+}
+
+// SAFETY: the list owns all of its nodes and only reaches them through its head, and shared
+// access to it only hands out shared access to the elements and the comparator
+unsafe impl<T: Send, C: Send> Send for DeterministicSkipList<T, C> {}
+unsafe impl<T: Sync, C: Sync> Sync for DeterministicSkipList<T, C> {}
+
+impl<T> DeterministicSkipList<T> {
+    /// Creates an empty list.
+    pub fn new() -> Self {
+        DeterministicSkipList::with_comparator(OrdComparator)
+    }
+}
+
+impl<T, C> DeterministicSkipList<T, C> {
+    /// Creates an empty list ordered by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        let head = Box::new(DetNode { val: None, next: vec![None] });
+        DeterministicSkipList { head, len: 0, cmp }
+    }
+
+    /// Returns the number of elements in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of levels the list currently uses, which never exceeds
+    /// `log2(len + 1) + 1`.
+    pub fn height(&self) -> usize {
+        self.head.next.len()
+    }
+
+    /// Returns an iterator over the elements of the list in sorted order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { next: self.head.next(0), len: self.len }
+    }
+
+    /// Returns a reference to the smallest element in the list.
+    pub fn first(&self) -> Option<&T> {
+        self.head.next(0).and_then(|node| node.val.as_ref())
+    }
+
+    /// Removes the smallest element from the list and returns it.
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.remove_first(|_, _| false, |_, _| true)
+    }
+
+    /// Removes all elements from the list.
+    pub fn clear(&mut self) {
+        self.drop_nodes();
+        self.head.next = vec![None];
+        self.len = 0;
+    }
+
+    /// Starting from `node`, moves forward at `level` while `f` returns true for the next
+    /// element. Returns the node it stopped on, and the one before it if it moved at all.
+    ///
+    /// SAFETY: `node` must be the head or a node in this list that reaches `level`.
+    unsafe fn advance(
+        &self,
+        mut node: NonNull<DetNode<T>>,
+        level: usize,
+        f: &mut impl FnMut(&C, &T) -> bool,
+    ) -> (DetLink<T>, NonNull<DetNode<T>>) {
+        let mut prev = None;
+        while let Some(next) = node.as_ref().next[level] {
+            if !next.as_ref().val.as_ref().is_some_and(|v| f(&self.cmp, v)) {
+                break;
+            }
+            prev = Some(node);
+            node = next;
+        }
+        (prev, node)
+    }
+
+    /// Removes the first element for which `before` returns false, if `is_target` returns true
+    /// for it. Every gap the search drops into is widened to at least two nodes first, so taking
+    /// a node out of the bottom level never leaves a gap empty.
+    fn remove_first(
+        &mut self,
+        mut before: impl FnMut(&C, &T) -> bool,
+        is_target: impl FnOnce(&C, &T) -> bool,
+    ) -> Option<T> {
+        let mut node = NonNull::from(self.head.as_mut());
+        // SAFETY: node is always the head or a node in the list reaching the current level, and
+        // a link is Some iff it points to a valid DetNode
+        unsafe {
+            for level in (1..self.height()).rev() {
+                let (prev, curr) = self.advance(node, level, &mut before);
+                let next = curr.as_ref().next[level];
+                node = match Self::gap(curr, next, level) {
+                    1 => Self::widen(prev, curr, next, level),
+                    _ => curr,
+                };
+            }
+
+            let (prev, node) = self.advance(node, 0, &mut before);
+            let target = node.as_ref().next[0].filter(|target| {
+                target.as_ref().val.as_ref().is_some_and(|v| is_target(&self.cmp, v))
+            });
+            let Some(target) = target else {
+                self.shrink_height();
+                return None;
+            };
+
+            let removed = if target.as_ref().next.len() == 1 {
+                (&mut *node.as_ptr()).next[0] = target.as_ref().next[0];
+                target
+            } else {
+                // target is a tower, so node is the last of the two or more nodes in the gap
+                // before it, which only reach level 0. Move target's element down into node and
+                // take node out instead, which keeps every element in order
+                mem::swap(&mut (*node.as_ptr()).val, &mut (*target.as_ptr()).val);
+                (&mut *prev.unwrap().as_ptr()).next[0] = Some(target);
+                node
+            };
+            self.len -= 1;
+            self.shrink_height();
+            Box::from_raw(removed.as_ptr()).val
+        }
+    }
+
+    /// Returns the number of nodes between `from` and `to` at `level - 1`, where `to` is the next
+    /// node after `from` at `level`. These are exactly the nodes whose highest level is `level - 1`.
+    ///
+    /// SAFETY: `from` must be the head or a node in this list reaching `level`, and `to` must be
+    /// its successor at `level`.
+    unsafe fn gap(from: NonNull<DetNode<T>>, to: DetLink<T>, level: usize) -> usize {
+        let mut count = 0;
+        let mut node = from.as_ref().next[level - 1];
+        while node != to {
+            count += 1;
+            node = node.unwrap().as_ref().next[level - 1];
+        }
+        count
+    }
+
+    /// Links `node`, whose highest level is `level - 1`, into `level` right after `prev`.
+    ///
+    /// SAFETY: both must be in this list, and `prev` must be the last node before `node` at `level`.
+    unsafe fn raise(prev: NonNull<DetNode<T>>, node: NonNull<DetNode<T>>, level: usize) {
+        let prev = &mut *prev.as_ptr();
+        (&mut *node.as_ptr()).next.push(prev.next[level]);
+        prev.next[level] = Some(node);
+    }
+
+    /// Unlinks `node`, whose highest level is `level`, from `level`.
+    ///
+    /// SAFETY: both must be in this list, and `prev` must be the node before `node` at `level`.
+    unsafe fn lower(prev: NonNull<DetNode<T>>, node: NonNull<DetNode<T>>, level: usize) {
+        (&mut *prev.as_ptr()).next[level] = (&mut *node.as_ptr()).next.pop().unwrap();
+    }
+
+    /// Widens the gap below `node` at `level`, which holds a single node, by lowering one of the
+    /// nodes bounding it and raising a node of the neighbouring gap if that one can spare it.
+    /// Returns the node the widened gap starts after.
+    ///
+    /// SAFETY: `next` must be `node`'s successor at `level`, and `prev` its predecessor at `level`
+    /// if `node` was reached by moving forward at `level`.
+    unsafe fn widen(
+        prev: DetLink<T>,
+        node: NonNull<DetNode<T>>,
+        next: DetLink<T>,
+        level: usize,
+    ) -> NonNull<DetNode<T>> {
+        match (prev, next) {
+            // next separates this gap from the one after it
+            (_, Some(next)) if next.as_ref().next.len() == level + 1 => {
+                let after = next.as_ref().next[level];
+                if Self::gap(next, after, level) > 1 {
+                    Self::raise(next, next.as_ref().next[level - 1].unwrap(), level);
+                }
+                Self::lower(node, next, level);
+                node
+            }
+            // node separates this gap from the one before it
+            (Some(prev), _) if node.as_ref().next.len() == level + 1 => match Self::gap(prev, Some(node), level) {
+                1 => {
+                    Self::lower(prev, node, level);
+                    prev
+                }
+                gap => {
+                    let mut last = prev.as_ref().next[level - 1].unwrap();
+                    for _ in 1..gap {
+                        last = last.as_ref().next[level - 1].unwrap();
+                    }
+                    Self::raise(prev, last, level);
+                    Self::lower(last, node, level);
+                    last
+                }
+            },
+            // both bounds reach higher, so this is the topmost gap, which may hold a single node
+            _ => node,
+        }
+    }
+
+    /// Drops empty levels off the top of the head, keeping exactly one.
+    fn shrink_height(&mut self) {
+        while self.head.next.len() > 1 && self.head.next[self.head.next.len() - 2].is_none() {
+            self.head.next.pop();
+        }
+    }
+
+    /// Frees every node after the head, without touching the head's links.
+    fn drop_nodes(&mut self) {
+        let mut node = self.head.next[0];
+        while let Some(next) = node {
+            // SAFETY: a link is Some iff it points to a valid DetNode, and every node is owned by the list
+            let next = unsafe { Box::from_raw(next.as_ptr()) };
+            node = next.next[0];
+        }
+    }
+}
+
+impl<T, C: Comparator<T>> DeterministicSkipList<T, C> {
+    /// Inserts `item` after any elements equal to it.
+    pub fn insert(&mut self, item: T) {
+        let mut node = NonNull::from(self.head.as_mut());
+        let mut before = |cmp: &C, v: &T| cmp.compare(v, &item) != Ordering::Greater;
+        // SAFETY: node is always the head or a node in the list reaching the current level, and
+        // a link is Some iff it points to a valid DetNode
+        unsafe {
+            for level in (1..self.height()).rev() {
+                node = self.advance(node, level, &mut before).1;
+                let next = node.as_ref().next[level];
+                if Self::gap(node, next, level) == 3 {
+                    let middle = node.as_ref().next[level - 1].unwrap().as_ref().next[level - 1].unwrap();
+                    Self::raise(node, middle, level);
+                    if middle.as_ref().val.as_ref().is_some_and(|v| before(&self.cmp, v)) {
+                        node = middle;
+                    }
+                }
+            }
+
+            node = self.advance(node, 0, &mut before).1;
+            let next = node.as_ref().next[0];
+            // the list owns the node from now on, and frees it in remove_first or drop_nodes
+            let new_node = NonNull::from(Box::leak(Box::new(DetNode { val: Some(item), next: vec![next] })));
+            (&mut *node.as_ptr()).next[0] = Some(new_node);
+        }
+        self.len += 1;
+
+        let top = self.height() - 1;
+        if self.head.next[top].is_some() {
+            self.head.next.push(None);
+        }
+    }
+
+    /// Returns the first element equal to `item`.
+    pub fn find<Q: ?Sized>(&self, item: &Q) -> Option<&T>
+    where
+        C: Comparator<T, Q>,
+    {
+        let mut node = self.head.as_ref();
+        for level in (0..self.height()).rev() {
+            while let Some(next) = node.next(level) {
+                if next.val.as_ref().is_none_or(|v| self.cmp.compare(v, item) != Ordering::Less) {
+                    break;
+                }
+                node = next;
+            }
+        }
+        node.next(0)
+            .and_then(|node| node.val.as_ref())
+            .filter(|&v| self.cmp.compare(v, item) == Ordering::Equal)
+    }
+
+    /// Returns true if the list contains an element equal to `item`.
+    pub fn contains<Q: ?Sized>(&self, item: &Q) -> bool
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find(item).is_some()
+    }
+
+    /// Removes the first element equal to `item` from the list and returns it.
+    pub fn remove<Q: ?Sized>(&mut self, item: &Q) -> Option<T>
+    where
+        C: Comparator<T, Q>,
+    {
+        self.remove_first(
+            |cmp, v| cmp.compare(v, item) == Ordering::Less,
+            |cmp, v| cmp.compare(v, item) == Ordering::Equal,
+        )
+    }
+}
+
+impl<T, C> Drop for DeterministicSkipList<T, C> {
+    fn drop(&mut self) {
+        self.drop_nodes();
+    }
+}
+
+impl<T, C: Default> Default for DeterministicSkipList<T, C> {
+    fn default() -> Self {
+        DeterministicSkipList::with_comparator(C::default())
+    }
+}
+
+impl<T: Debug, C> Debug for DeterministicSkipList<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for DeterministicSkipList<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a DeterministicSkipList<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a `DeterministicSkipList` in sorted order.
+///
+/// Created by [`DeterministicSkipList::iter`].
+pub struct Iter<'a, T> {
+    next: Option<&'a DetNode<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.next?;
+        self.next = node.next(0);
+        self.len -= 1;
+        node.val.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+impl<'a, T> Clone for Iter<'a, T> {
+    fn clone(&self) -> Self {
+        Iter { next: self.next, len: self.len }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DetNode, DeterministicSkipList};
+
+    /// Checks that every gap holds 1, 2 or 3 nodes, and that the head is exactly one level
+    /// taller than the tallest node.
+    fn assert_balanced<T, C>(l: &DeterministicSkipList<T, C>) {
+        let height = l.height();
+        assert!(l.head.next[height - 1].is_none());
+        assert_eq!(height == 1, l.is_empty());
+
+        for level in 1..height {
+            let mut bound: Option<&DetNode<T>> = Some(&l.head);
+            while let Some(from) = bound {
+                let to = from.next(level);
+                let mut gap = 0;
+                let mut node = from.next(level - 1);
+                while node.map(|n| n as *const _) != to.map(|n| n as *const _) {
+                    assert_eq!(node.unwrap().next.len(), level);
+                    gap += 1;
+                    node = node.unwrap().next(level - 1);
+                }
+                assert!((1..=3).contains(&gap), "gap of {} at level {}", gap, level);
+                bound = to;
+            }
+        }
+        assert!(l.len() + 1 >= 1 << (height - 1));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn insert_find_remove() {
+        let mut l = DeterministicSkipList::new();
+        let mut nums: Vec<i32> = (0..1_000).map(|_| fastrand::i32(0..300)).collect();
+        for &n in &nums {
+            l.insert(n);
+            assert_balanced(&l);
+        }
+        nums.sort();
+        assert_eq!(l.len(), 1_000);
+        assert!(l.iter().eq(nums.iter()));
+        assert_eq!(l.first(), nums.first());
+
+        for i in 0..300 {
+            assert_eq!(l.contains(&i), nums.contains(&i));
+        }
+        for i in (0..300).step_by(2) {
+            while l.remove(&i).is_some() {
+                assert_balanced(&l);
+            }
+            assert!(!l.contains(&i));
+        }
+        nums.retain(|v| v % 2 == 1);
+        assert!(l.iter().eq(nums.iter()));
+        assert_eq!(l.iter().len(), nums.len());
+
+        while let Some(v) = l.pop_front() {
+            assert_eq!(v, nums.remove(0));
+            assert_balanced(&l);
+        }
+        assert!(nums.is_empty());
+        assert_eq!(l.height(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn sorted_insertions_stay_shallow() {
+        let mut l = DeterministicSkipList::with_comparator(|a: &u32, b: &u32| b.cmp(a));
+        l.extend(0..4_096);
+        assert_balanced(&l);
+        assert!(l.height() <= 13);
+        assert!(l.iter().copied().eq((0..4_096).rev()));
+
+        for i in 0..4_096 {
+            assert_eq!(l.remove(&i), Some(i));
+        }
+        assert_balanced(&l);
+        assert!(l.is_empty());
+
+        let mut s = DeterministicSkipList::<String>::default();
+        s.extend(["b", "a"].map(String::from));
+        assert_eq!(s.find("a").map(String::as_str), Some("a"));
+        s.clear();
+        assert_eq!(format!("{:?}", s), "[]");
+    }
+}
@@ -0,0 +1,328 @@
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use core::ptr::NonNull;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use alloc::vec;
+
+use crate::level::Seed;
+use crate::{Comparator, OrdComparator};
+
+// INVARIANT: if a link is Some, it must point to a DynNode
+type DynLink<T> = Option<NonNull<DynNode<T>>>;
+
+struct DynNode<T> {
+    val: Option<T>,
+    // one link per level the node participates in, so its length is the node's level + 1
+    next: Box<[DynLink<T>]>,
+}
+
+// SAFETY: a node's links only lead to the nodes after it in the same list, which are never
+// mutated through a shared reference
+unsafe impl<T: Send> Send for DynNode<T> {}
+unsafe impl<T: Sync> Sync for DynNode<T> {}
+
+impl<T> DynNode<T> {
+    fn new(val: Option<T>, levels: usize) -> Self {
+        DynNode { val, next: vec![None; levels].into_boxed_slice() }
+    }
+
+    fn next(&self, level: usize) -> Option<&Self> {
+        // SAFETY: If a link is Some, it points to a DynNode
+        unsafe { self.next[level].map(|p| p.as_ref()) }
+    }
+}
+
+/// A sorted list like `SkipList`, except that the number of levels is picked at runtime
+/// instead of being a const generic.
+///
+/// Each node only allocates links for the levels it participates in, so a list with many
+/// levels costs no more memory per node than one with few. The trade-off is an extra
+/// allocation per node and a search path that lives on the heap.
+pub struct DynSkipList<T, C = OrdComparator> {
+    head: Box<DynNode<T>>,
+    rng: Seed,
+    len: usize,
+    cmp: C,
+}
+
+// SAFETY: the list owns all of its nodes and only reaches them through its head, and shared
+// access to it only hands out shared access to the elements and the comparator
+unsafe impl<T: Send, C: Send> Send for DynSkipList<T, C> {}
+unsafe impl<T: Sync, C: Sync> Sync for DynSkipList<T, C> {}
+
+impl<T> DynSkipList<T> {
+    /// Creates an empty list with `max_levels` levels, which must be between 1 and 64.
+    pub fn new(max_levels: usize) -> Self {
+        DynSkipList::with_comparator(max_levels, OrdComparator)
+    }
+}
+
+impl<T, C> DynSkipList<T, C> {
+    /// Creates an empty list with `max_levels` levels, which must be between 1 and 64, ordered by `cmp`.
+    pub fn with_comparator(max_levels: usize, cmp: C) -> Self {
+        assert!((1..=64).contains(&max_levels), "DynSkipList: max_levels must be between 1 and 64");
+        let head = Box::new(DynNode::new(None, max_levels));
+        DynSkipList { head, rng: Seed::new(), len: 0, cmp }
+    }
+
+    /// Returns the number of levels the list was created with.
+    pub fn max_levels(&self) -> usize {
+        self.head.next.len()
+    }
+
+    /// Returns the number of elements in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the elements of the list in sorted order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { next: self.head.next(0), len: self.len }
+    }
+
+    /// Returns a reference to the smallest element in the list.
+    pub fn first(&self) -> Option<&T> {
+        self.head.next(0).and_then(|node| node.val.as_ref())
+    }
+
+    /// Removes the smallest element from the list and returns it.
+    pub fn pop_front(&mut self) -> Option<T> {
+        let first = self.head.next[0]?;
+        let path = vec![NonNull::from(self.head.as_mut()); self.max_levels()];
+        // SAFETY: first is the first node after the head, so the head is its predecessor at every level
+        Some(unsafe { self.unlink(first, &path) })
+    }
+
+    /// Removes all elements from the list.
+    pub fn clear(&mut self) {
+        self.drop_nodes();
+        self.head.next.fill(None);
+        self.len = 0;
+    }
+
+    fn gen_level(&mut self) -> usize {
+        let mask = (1u64 << (self.max_levels() - 1)) - 1;
+        (self.rng.u64() & mask).trailing_ones() as usize
+    }
+
+    /// Returns, for every level, the last node at that level such that `f` returns true for its
+    /// element and the elements of every node before it, or the head if there is none.
+    fn find_path_mut(&mut self, mut f: impl FnMut(&C, &T) -> bool) -> Vec<NonNull<DynNode<T>>> {
+        let cmp = &self.cmp;
+        let mut node = NonNull::from(self.head.as_mut());
+        let mut path = vec![node; self.head.next.len()];
+        for level in (0..path.len()).rev() {
+            // SAFETY: node is the head or a node reachable from it, and a link is Some iff it points to a valid DynNode
+            unsafe {
+                while let Some(next) = node.as_ref().next[level] {
+                    if !next.as_ref().val.as_ref().is_some_and(|v| f(cmp, v)) {
+                        break;
+                    }
+                    node = next;
+                }
+            }
+            path[level] = node;
+        }
+        path
+    }
+
+    /// Unlinks `node` from every level it participates in and returns its value.
+    ///
+    /// SAFETY: `node` must be a non-head node in this list, and `path[level]` must be its
+    /// predecessor at every level it participates in.
+    unsafe fn unlink(&mut self, node: NonNull<DynNode<T>>, path: &[NonNull<DynNode<T>>]) -> T {
+        let mut node = Box::from_raw(node.as_ptr());
+        for (level, next) in node.next.iter().enumerate() {
+            (*path[level].as_ptr()).next[level] = *next;
+        }
+        self.len -= 1;
+        node.val.take().unwrap()
+    }
+
+    /// Frees every node after the head, without touching the head's links.
+    fn drop_nodes(&mut self) {
+        let mut node = self.head.next[0];
+        while let Some(next) = node {
+            // SAFETY: a link is Some iff it points to a valid DynNode, and every node is owned by the list
+            let next = unsafe { Box::from_raw(next.as_ptr()) };
+            node = next.next[0];
+        }
+    }
+}
+
+impl<T, C: Comparator<T>> DynSkipList<T, C> {
+    /// Inserts `item` after any elements equal to it.
+    pub fn insert(&mut self, item: T) {
+        let path = self.find_path_mut(|cmp, v| cmp.compare(&item, v) != Ordering::Less);
+        let levels = self.gen_level() + 1;
+
+        // the list owns the node from now on, and frees it in unlink or drop_nodes
+        let mut new_node = NonNull::from(Box::leak(Box::new(DynNode::new(Some(item), levels))));
+        for (level, prev) in path.iter().enumerate().take(levels) {
+            // SAFETY: path only holds the head or nodes in this list, and new_node was just allocated
+            unsafe { new_node.as_mut().next[level] = (*prev.as_ptr()).next[level].replace(new_node) };
+        }
+        self.len += 1;
+    }
+
+    /// Returns the first element equal to `item`.
+    pub fn find<Q: ?Sized>(&self, item: &Q) -> Option<&T>
+    where
+        C: Comparator<T, Q>,
+    {
+        let mut node = self.head.as_ref();
+        for level in (0..self.max_levels()).rev() {
+            while let Some(next) = node.next(level) {
+                if next.val.as_ref().is_none_or(|v| self.cmp.compare(v, item) != Ordering::Less) {
+                    break;
+                }
+                node = next;
+            }
+        }
+        node.next(0)
+            .and_then(|node| node.val.as_ref())
+            .filter(|&v| self.cmp.compare(v, item) == Ordering::Equal)
+    }
+
+    /// Returns true if the list contains an element equal to `item`.
+    pub fn contains<Q: ?Sized>(&self, item: &Q) -> bool
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find(item).is_some()
+    }
+
+    /// Removes the first element equal to `item` from the list and returns it.
+    pub fn remove<Q: ?Sized>(&mut self, item: &Q) -> Option<T>
+    where
+        C: Comparator<T, Q>,
+    {
+        let path = self.find_path_mut(|cmp, v| cmp.compare(v, item) == Ordering::Less);
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid DynNode
+        let target = unsafe { path[0].as_ref().next[0]? };
+        if unsafe { target.as_ref() }.val.as_ref().is_none_or(|v| self.cmp.compare(v, item) != Ordering::Equal) {
+            return None;
+        }
+
+        // SAFETY: target is the first node >= item, so path[level] is its predecessor at every level it occupies
+        Some(unsafe { self.unlink(target, &path) })
+    }
+}
+
+impl<T, C> Drop for DynSkipList<T, C> {
+    fn drop(&mut self) {
+        self.drop_nodes();
+    }
+}
+
+impl<T: Debug, C> Debug for DynSkipList<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for DynSkipList<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a DynSkipList<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a `DynSkipList` in sorted order.
+///
+/// Created by [`DynSkipList::iter`].
+pub struct Iter<'a, T> {
+    next: Option<&'a DynNode<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.next?;
+        self.next = node.next(0);
+        self.len -= 1;
+        node.val.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+impl<'a, T> Clone for Iter<'a, T> {
+    fn clone(&self) -> Self {
+        Iter { next: self.next, len: self.len }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynSkipList;
+
+    #[test]
+    fn insert_find_remove() {
+        let mut l = DynSkipList::new(12);
+        assert_eq!(l.max_levels(), 12);
+        assert!(l.is_empty());
+
+        let mut nums: Vec<i32> = (0..500).map(|_| fastrand::i32(0..200)).collect();
+        l.extend(nums.iter().copied());
+        nums.sort();
+        assert_eq!(l.len(), 500);
+        assert!(l.iter().eq(nums.iter()));
+        assert_eq!(l.first(), nums.first());
+
+        for i in 0..200 {
+            assert_eq!(l.contains(&i), nums.contains(&i));
+        }
+        for i in (0..200).step_by(2) {
+            while l.remove(&i).is_some() {}
+            assert!(!l.contains(&i));
+        }
+        nums.retain(|v| v % 2 == 1);
+        assert!(l.iter().eq(nums.iter()));
+        assert_eq!(l.iter().len(), nums.len());
+
+        assert_eq!(l.pop_front(), nums.first().copied());
+        l.clear();
+        assert!(l.is_empty());
+        l.insert(3);
+        assert_eq!(format!("{:?}", l), "[3]");
+    }
+
+    #[test]
+    fn single_level_and_comparator() {
+        let mut l = DynSkipList::with_comparator(1, |a: &i32, b: &i32| b.cmp(a));
+        l.extend([1, 3, 2]);
+        assert!(l.iter().eq([3, 2, 1].iter()));
+        assert_eq!(l.remove(&2), Some(2));
+
+        let mut s = DynSkipList::<String>::new(64);
+        s.extend(["b", "a"].map(String::from));
+        assert_eq!(s.find("a").map(String::as_str), Some("a"));
+    }
+}
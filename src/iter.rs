@@ -0,0 +1,344 @@
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::{Allocator, Geometric, Global, Link, NodeRef, OrdComparator, SkipList, SkipListNode};
+
+/// An iterator over the elements of a `SkipList` in sorted order.
+///
+/// Created by [`SkipList::iter`].
+pub struct Iter<'a, T, const NUM_LEVELS: usize> {
+    front: Option<NodeRef<'a, T, NUM_LEVELS>>,
+    back: Option<NodeRef<'a, T, NUM_LEVELS>>,
+    len: usize,
+}
+
+impl<'a, T, const NUM_LEVELS: usize> Iter<'a, T, NUM_LEVELS> {
+    pub(crate) fn new<C, G, A: Allocator>(list: &'a SkipList<T, NUM_LEVELS, C, G, A>) -> Self {
+        // SAFETY: If a link is Some, it points to a SkipListNode
+        let back = list.tail.map(|p| unsafe { NodeRef::new(p) });
+        Iter { front: list.head.next(0), back, len: list.len }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> Iterator for Iter<'a, T, NUM_LEVELS> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.front?;
+        self.front = node.next(0);
+        self.len -= 1;
+        node.val()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> DoubleEndedIterator for Iter<'a, T, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.back?;
+        self.back = node.prev();
+        self.len -= 1;
+        node.val()
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> ExactSizeIterator for Iter<'a, T, NUM_LEVELS> {}
+
+impl<'a, T, const NUM_LEVELS: usize> FusedIterator for Iter<'a, T, NUM_LEVELS> {}
+
+impl<'a, T, const NUM_LEVELS: usize> Clone for Iter<'a, T, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        Iter { front: self.front, back: self.back, len: self.len }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> IntoIterator for &'a SkipList<T, NUM_LEVELS, C, G, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over a sub-range of the elements of a `SkipList` in sorted order.
+///
+/// Created by [`SkipList::range`].
+pub struct Range<'a, T, const NUM_LEVELS: usize> {
+    front: Option<NodeRef<'a, T, NUM_LEVELS>>,
+    back: Option<NodeRef<'a, T, NUM_LEVELS>>,
+}
+
+impl<'a, T, const NUM_LEVELS: usize> Range<'a, T, NUM_LEVELS> {
+    /// Creates an iterator yielding everything from `front` to `back` (inclusive). Both nodes
+    /// must either be None or non-head nodes of the same list with `front` not after `back`.
+    pub(crate) fn new(
+        front: Option<NodeRef<'a, T, NUM_LEVELS>>,
+        back: Option<NodeRef<'a, T, NUM_LEVELS>>,
+    ) -> Self {
+        Range { front, back }
+    }
+
+    fn finish_if_met(&mut self, node: NodeRef<'a, T, NUM_LEVELS>) -> bool {
+        let met = self.front.is_some_and(|front| front.ptr() == node.ptr()) && self.back.is_some_and(|back| back.ptr() == node.ptr());
+        if met {
+            self.front = None;
+            self.back = None;
+        }
+        met
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> Iterator for Range<'a, T, NUM_LEVELS> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.front?;
+        if !self.finish_if_met(node) {
+            self.front = node.next(0);
+        }
+        node.val()
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> DoubleEndedIterator for Range<'a, T, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let node = self.back?;
+        if !self.finish_if_met(node) {
+            self.back = node.prev();
+        }
+        node.val()
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> FusedIterator for Range<'a, T, NUM_LEVELS> {}
+
+impl<'a, T, const NUM_LEVELS: usize> Clone for Range<'a, T, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        Range { front: self.front, back: self.back }
+    }
+}
+
+/// A mutable iterator over the elements of a `SkipList` in sorted order.
+///
+/// Created by [`SkipList::iter_mut`].
+pub struct IterMut<'a, T, const NUM_LEVELS: usize> {
+    front: Link<T, NUM_LEVELS>,
+    back: Link<T, NUM_LEVELS>,
+    len: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: IterMut hands out &'a mut T to distinct elements, so it behaves like a &'a mut [T]
+unsafe impl<'a, T: Send, const NUM_LEVELS: usize> Send for IterMut<'a, T, NUM_LEVELS> {}
+unsafe impl<'a, T: Sync, const NUM_LEVELS: usize> Sync for IterMut<'a, T, NUM_LEVELS> {}
+
+impl<'a, T, const NUM_LEVELS: usize> IterMut<'a, T, NUM_LEVELS> {
+    pub(crate) fn new<C, G, A: Allocator>(list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>) -> Self {
+        IterMut { front: list.head.tower()[0].next, back: list.tail, len: list.len, _marker: PhantomData }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> Iterator for IterMut<'a, T, NUM_LEVELS> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.front?;
+        // SAFETY: If a link is Some, it points to a SkipListNode, the list is mutably borrowed for 'a,
+        // and every node is yielded at most once
+        unsafe {
+            self.front = SkipListNode::tower(node)[0].next;
+            self.len -= 1;
+            (*node.as_ptr()).val.as_mut()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> DoubleEndedIterator for IterMut<'a, T, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        // SAFETY: same as next()
+        let node = unsafe { self.back?.as_mut() };
+        self.back = node.prev;
+        self.len -= 1;
+        node.val.as_mut()
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize> ExactSizeIterator for IterMut<'a, T, NUM_LEVELS> {}
+
+impl<'a, T, const NUM_LEVELS: usize> FusedIterator for IterMut<'a, T, NUM_LEVELS> {}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> IntoIterator for &'a mut SkipList<T, NUM_LEVELS, C, G, A> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An owning iterator over the elements of a `SkipList` in sorted order.
+///
+/// Created by the `into_iter` method on `SkipList`. Nodes are freed as the iterator advances.
+pub struct IntoIter<T, const NUM_LEVELS: usize, C = OrdComparator, G = Geometric, A: Allocator = Global> {
+    list: SkipList<T, NUM_LEVELS, C, G, A>,
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> Iterator for IntoIter<T, NUM_LEVELS, C, G, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> DoubleEndedIterator for IntoIter<T, NUM_LEVELS, C, G, A> {
+    fn next_back(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> ExactSizeIterator for IntoIter<T, NUM_LEVELS, C, G, A> {}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> FusedIterator for IntoIter<T, NUM_LEVELS, C, G, A> {}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> IntoIterator for SkipList<T, NUM_LEVELS, C, G, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, NUM_LEVELS, C, G, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+/// A draining iterator over the elements of a `SkipList` in sorted order.
+///
+/// Created by [`SkipList::drain`]. The list is left empty when the iterator is dropped,
+/// even if it was not fully consumed.
+pub struct Drain<'a, T, const NUM_LEVELS: usize, C = OrdComparator, G = Geometric, A: Allocator = Global> {
+    list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>,
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Drain<'a, T, NUM_LEVELS, C, G, A> {
+    pub(crate) fn new(list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>) -> Self {
+        Drain { list }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Iterator for Drain<'a, T, NUM_LEVELS, C, G, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> DoubleEndedIterator for Drain<'a, T, NUM_LEVELS, C, G, A> {
+    fn next_back(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> ExactSizeIterator for Drain<'a, T, NUM_LEVELS, C, G, A> {}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> FusedIterator for Drain<'a, T, NUM_LEVELS, C, G, A> {}
+
+impl<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> Drop for Drain<'a, T, NUM_LEVELS, C, G, A> {
+    fn drop(&mut self) {
+        self.list.clear();
+    }
+}
+
+/// An iterator that removes and yields the elements of a `SkipList` matching a predicate.
+///
+/// Created by [`SkipList::extract_if`]. Elements are visited in sorted order; those not
+/// yielded before the iterator is dropped stay in the list.
+pub struct ExtractIf<'a, T, const NUM_LEVELS: usize, F: FnMut(&T) -> bool, C = OrdComparator, G = Geometric, A: Allocator = Global> {
+    list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>,
+    // the last node at each level that was kept, i.e. the predecessors of `next`
+    path: [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    next: Link<T, NUM_LEVELS>,
+    pred: F,
+}
+
+// SAFETY: path and next only point into the list the iterator borrows mutably, so it's as
+// thread-safe as that borrow and the predicate
+unsafe impl<'a, T, const NUM_LEVELS: usize, F, C, G, A: Allocator> Send for ExtractIf<'a, T, NUM_LEVELS, F, C, G, A>
+where
+    F: FnMut(&T) -> bool + Send,
+    SkipList<T, NUM_LEVELS, C, G, A>: Send,
+{
+}
+unsafe impl<'a, T, const NUM_LEVELS: usize, F, C, G, A: Allocator> Sync for ExtractIf<'a, T, NUM_LEVELS, F, C, G, A>
+where
+    F: FnMut(&T) -> bool + Sync,
+    SkipList<T, NUM_LEVELS, C, G, A>: Sync,
+{
+}
+
+impl<'a, T, const NUM_LEVELS: usize, F: FnMut(&T) -> bool, C, G, A: Allocator> ExtractIf<'a, T, NUM_LEVELS, F, C, G, A> {
+    pub(crate) fn new(list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>, pred: F) -> Self {
+        let next = list.head.tower()[0].next;
+        let path = [list.head.0; NUM_LEVELS];
+        ExtractIf { list, path, next, pred }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, F: FnMut(&T) -> bool, C, G, A: Allocator> Iterator for ExtractIf<'a, T, NUM_LEVELS, F, C, G, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while let Some(node) = self.next {
+            // SAFETY: If a link is Some, it points to a SkipListNode
+            let node_ref = unsafe { NodeRef::new(node) };
+            self.next = node_ref.tower()[0].next;
+
+            if node_ref.val().is_some_and(&mut self.pred) {
+                // SAFETY: path holds the last kept node at every level, which are node's predecessors
+                return Some(unsafe { self.list.unlink(node, &self.path) });
+            }
+
+            for level in 0..=node_ref.level {
+                self.path[level] = node;
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.list.len))
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, F: FnMut(&T) -> bool, C, G, A: Allocator> FusedIterator for ExtractIf<'a, T, NUM_LEVELS, F, C, G, A> {}
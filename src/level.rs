@@ -0,0 +1,233 @@
+use alloc::boxed::Box;
+/// Picks the level of every node a `SkipList` inserts.
+///
+/// Implemented by [`Geometric`], the default, which flips a biased coin per level, by
+/// [`Deterministic`], which needs no randomness at all, and by [`Biased`], which takes a
+/// separate promotion probability for every level. With the `rand_core` feature,
+/// `RngGeometric` flips the same coins with any `rand_core::RngCore`. The level only affects
+/// how fast the list is, never which elements it holds or their order.
+pub trait LevelGenerator {
+    /// Returns the level of a new node, between 0 and `max_level` inclusive. A node at level
+    /// `l` is linked into levels `0..=l`.
+    fn gen_level(&mut self, max_level: usize) -> usize;
+}
+
+/// Gives a node level `l` with probability `p^l * (1 - p)`, cut off at the list's highest
+/// level. This is the classic skip list and the default generator, with `p` = 1/2.
+#[derive(Clone, Debug)]
+pub struct Geometric {
+    rng: Seed,
+    // the probability that a node reaching some level also reaches the next one
+    p: f64,
+}
+
+impl Geometric {
+    /// Creates a generator with `p` = 1/2, seeded from the thread-local generator.
+    pub fn new() -> Self {
+        Geometric { rng: Seed::new(), p: 0.5 }
+    }
+
+    /// Creates a generator with `p` = 1/2 that always produces the same levels for a given `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Geometric { rng: Seed(seed), p: 0.5 }
+    }
+
+    /// Sets the probability `p` that a node reaching some level also reaches the next one,
+    /// which must be between 0 and 1.
+    pub fn with_probability(mut self, p: f64) -> Self {
+        assert!(p > 0.0 && p < 1.0, "promotion probability must be between 0 and 1");
+        self.p = p;
+        self
+    }
+
+    /// Returns the probability that a node reaching some level also reaches the next one.
+    pub fn probability(&self) -> f64 {
+        self.p
+    }
+}
+
+impl Default for Geometric {
+    fn default() -> Self {
+        Geometric::new()
+    }
+}
+
+impl LevelGenerator for Geometric {
+    fn gen_level(&mut self, max_level: usize) -> usize {
+        if self.p == 0.5 {
+            // every random bit is a fair coin flip
+            let mask = (1 << max_level) - 1;
+            let rand = self.rng.u64() as usize;
+            let jawn = rand & mask;
+            return jawn.trailing_ones() as usize;
+        }
+
+        let mut level = 0;
+        while level < max_level && self.rng.f64() < self.p {
+            level += 1;
+        }
+        level
+    }
+}
+
+/// Gives the `n`th node it's asked for the number of times 2 divides `n`, so levels go
+/// 0, 1, 0, 2, 0, 1, 0, 3, ...
+///
+/// Appending elements in sorted order builds a perfectly balanced list, and the same
+/// insertions always build the same towers. Other insertion orders get no balance guarantee,
+/// so prefer [`Geometric`] unless the input is known to arrive mostly sorted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deterministic {
+    count: u64,
+}
+
+impl Deterministic {
+    /// Creates a generator whose first node gets level 0.
+    pub fn new() -> Self {
+        Deterministic::default()
+    }
+}
+
+impl LevelGenerator for Deterministic {
+    fn gen_level(&mut self, max_level: usize) -> usize {
+        self.count = self.count.wrapping_add(1);
+        (self.count.trailing_zeros() as usize).min(max_level)
+    }
+}
+
+/// Promotes a node from level `l` to `l + 1` with its own probability for every level, and
+/// never past the last level given a probability.
+///
+/// Useful for keeping the upper levels sparse without thinning out the lower ones, or for
+/// capping tower heights below the list's number of levels.
+#[derive(Clone, Debug)]
+pub struct Biased {
+    rng: Seed,
+    probabilities: Box<[f64]>,
+}
+
+impl Biased {
+    /// Creates a generator that promotes a node from level `l` with probability
+    /// `probabilities[l]`, each of which must be between 0 and 1.
+    pub fn new(probabilities: impl Into<Box<[f64]>>) -> Self {
+        Biased::with_rng(probabilities.into(), Seed::new())
+    }
+
+    /// Like [`Biased::new`], but always produces the same levels for a given `seed`.
+    pub fn with_seed(probabilities: impl Into<Box<[f64]>>, seed: u64) -> Self {
+        Biased::with_rng(probabilities.into(), Seed(seed))
+    }
+
+    fn with_rng(probabilities: Box<[f64]>, rng: Seed) -> Self {
+        assert!(
+            probabilities.iter().all(|&p| (0.0..=1.0).contains(&p)),
+            "promotion probabilities must be between 0 and 1"
+        );
+        Biased { rng, probabilities }
+    }
+}
+
+impl LevelGenerator for Biased {
+    fn gen_level(&mut self, max_level: usize) -> usize {
+        let Biased { rng, probabilities } = self;
+        let mut level = 0;
+        while level < max_level && probabilities.get(level).is_some_and(|&p| rng.f64() < p) {
+            level += 1;
+        }
+        level
+    }
+}
+
+/// The state of a wyrand generator, the one `fastrand::Rng` runs, kept as a bare `u64`.
+///
+/// `fastrand::Rng` lives in a `Cell`, so holding one would make the generators `!Sync`, and its
+/// `Clone` advances the original through a shared reference. A plain seed is `Send + Sync`, and
+/// cloning it derives a fresh seed without touching the original, so a cloned list doesn't
+/// repeat the levels of the list it came from. Stepping the seed here also keeps `fastrand`,
+/// which needs `std`, down to picking the first seed.
+#[derive(Debug)]
+pub(crate) struct Seed(pub(crate) u64);
+
+impl Seed {
+    /// Returns a random seed. Without the `std` feature there's no source of entropy, so every
+    /// generator starts from the same seed; use `with_seed` to bring your own.
+    pub(crate) fn new() -> Self {
+        #[cfg(feature = "std")]
+        return Seed(fastrand::u64(..));
+        #[cfg(not(feature = "std"))]
+        return Seed(0x2545_F491_4F6C_DD1D);
+    }
+
+    pub(crate) fn u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0xA076_1D64_78BD_642F);
+        let t = u128::from(self.0) * u128::from(self.0 ^ 0xE703_7ED1_A0B4_28DB);
+        (t as u64) ^ (t >> 64) as u64
+    }
+
+    /// Returns a float in `0.0..1.0`.
+    pub(crate) fn f64(&mut self) -> f64 {
+        let mantissa = f64::MANTISSA_DIGITS - 1;
+        f64::from_bits((1 << 62) - (1 << mantissa) + (self.u64() >> (64 - mantissa))) - 1.0
+    }
+}
+
+impl Clone for Seed {
+    fn clone(&self) -> Self {
+        Seed(Seed(self.0).u64())
+    }
+}
+
+/// Like [`Geometric`], but draws its coin flips from any `rand_core::RngCore`, such as a
+/// seeded `StdRng`, a ChaCha generator, or a hardware RNG, instead of `fastrand`.
+#[cfg(feature = "rand_core")]
+#[derive(Clone, Debug)]
+pub struct RngGeometric<R> {
+    rng: R,
+    // the probability that a node reaching some level also reaches the next one
+    p: f64,
+}
+
+#[cfg(feature = "rand_core")]
+impl<R: rand_core::RngCore> RngGeometric<R> {
+    /// Creates a generator with `p` = 1/2 that draws from `rng`.
+    pub fn new(rng: R) -> Self {
+        RngGeometric { rng, p: 0.5 }
+    }
+
+    /// Sets the probability `p` that a node reaching some level also reaches the next one,
+    /// which must be between 0 and 1.
+    pub fn with_probability(mut self, p: f64) -> Self {
+        assert!(p > 0.0 && p < 1.0, "promotion probability must be between 0 and 1");
+        self.p = p;
+        self
+    }
+
+    /// Returns the probability that a node reaching some level also reaches the next one.
+    pub fn probability(&self) -> f64 {
+        self.p
+    }
+
+    /// Consumes the generator and returns the underlying random number generator.
+    pub fn into_inner(self) -> R {
+        self.rng
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl<R: rand_core::RngCore> LevelGenerator for RngGeometric<R> {
+    fn gen_level(&mut self, max_level: usize) -> usize {
+        if self.p == 0.5 {
+            // every random bit is a fair coin flip
+            let mask = (1 << max_level) - 1;
+            return (self.rng.next_u64() & mask).trailing_ones() as usize;
+        }
+
+        // the top 53 bits of a random u64 give a uniform f64 in [0, 1)
+        let scale = 1.0 / (1u64 << 53) as f64;
+        let mut level = 0;
+        while level < max_level && (self.rng.next_u64() >> 11) as f64 * scale < self.p {
+            level += 1;
+        }
+        level
+    }
+}
@@ -1,24 +1,263 @@
-#![feature(maybe_uninit_array_assume_init)]
-use std::ptr::{self, NonNull};
-use std::fmt::Debug;
-use std::cmp::{PartialOrd, Ordering};
-use std::fmt;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
-// NUM_LEVELS must be <= std::mem::size_of<usize>()
+extern crate alloc;
+
+use alloc::alloc::{handle_alloc_error, Layout};
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::fmt::Debug;
+use core::cmp::{PartialOrd, Ordering};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Bound, Deref, RangeBounds};
+use core::slice;
+use alloc::vec::Vec;
+use alloc::format;
+
+mod allocator;
+mod arena;
+mod compare;
+#[cfg(feature = "std")]
+pub mod concurrent;
+mod cursor;
+pub mod deterministic;
+pub mod dynamic;
+mod iter;
+mod level;
+pub mod map;
+mod memory;
+pub mod memtable;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(all(feature = "persistent", unix))]
+pub mod persistent;
+#[cfg(feature = "proptest")]
+pub mod proptest_impl;
+mod queue;
+mod render;
+#[cfg(feature = "rkyv")]
+mod rkyv_impl;
+mod set_ops;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "std")]
+mod snapshot;
+pub mod timers;
+mod top_k;
+pub mod unrolled;
+pub mod versioned;
+#[cfg(feature = "std")]
+pub mod wal;
+
+pub use allocator::{AllocError, Allocator, Global};
+pub use compare::{Comparator, KeyComparator, OrdComparator};
+pub use cursor::{Cursor, CursorMut};
+pub use deterministic::DeterministicSkipList;
+pub use dynamic::DynSkipList;
+pub use iter::{Drain, ExtractIf, IntoIter, Iter, IterMut, Range};
+pub use level::{Biased, Deterministic, Geometric, LevelGenerator};
+#[cfg(feature = "rand_core")]
+pub use level::RngGeometric;
+pub use map::SkipListMap;
+pub use memory::MemoryUsage;
+pub use memtable::{FrozenMemTable, MemTable};
+#[cfg(feature = "rayon")]
+pub use par::ParIter;
+pub use queue::SkipQueue;
+pub use set_ops::SymmetricDifference;
+pub use timers::Timers;
+pub use top_k::TopK;
+pub use unrolled::UnrolledSkipList;
+pub use versioned::VersionedMap;
+#[cfg(feature = "rkyv")]
+pub use rkyv_impl::{ArchivedSkipList, ArchivedSkipListMap};
+#[cfg(feature = "serde")]
+pub use serde_impl::Towers;
+
+use arena::Arena;
+use compare::{before_start, through_end};
+
+// NUM_LEVELS must be <= core::mem::size_of<usize>()
+
+// the size of a list's nodes from which its searches prefetch, about that of a core's L2 cache.
+// The search_prefetch bench measures the prefetch against none at different sizes
+const PREFETCH_MIN_BYTES: usize = 1 << 20;
 
 // INVARIANT: if a link is Some, it must point to a SkipListNode
 type Link<T, const NUM_LEVELS: usize> = Option<NonNull<SkipListNode<T, NUM_LEVELS>>>;
 
-pub struct SkipList<T: PartialEq + Debug, const NUM_LEVELS: usize> {
-    head: Box<SkipListNode<T, NUM_LEVELS>>,
-    rng: fastrand::Rng,
+/// What inserting an element equal to one already in a `SkipList` does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Insert it after the equal elements, like a multiset. This is the default.
+    #[default]
+    Allow,
+    /// Leave the list unchanged and hand the new element back, like a set.
+    Reject,
+    /// Put the new element in place of the last equal one and hand that back, so the last write wins.
+    Replace,
+}
+
+/// Which element a `SkipList` built with [`with_max_len`](SkipList::with_max_len) evicts when
+/// an insert takes it past its maximum length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvictPolicy {
+    /// Evict the smallest element, keeping the largest ones, like a top-N list.
+    Smallest,
+    /// Evict the largest element, keeping the smallest ones.
+    Largest,
+}
+
+/// The error returned by [`SkipList::try_insert`] when the new node couldn't be allocated. It
+/// holds the element that wasn't inserted.
+pub struct TryInsertError<T> {
+    item: T,
+    layout: Layout,
+}
+
+impl<T> TryInsertError<T> {
+    /// Returns the element that wasn't inserted.
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+}
+
+impl<T> fmt::Debug for TryInsertError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryInsertError").field("layout", &self.layout).finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for TryInsertError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "memory allocation of {} bytes failed", self.layout.size())
+    }
+}
+
+impl<T> core::error::Error for TryInsertError<T> {}
+
+/// A broken invariant of a `SkipList`, found by [`SkipList::validate`]. Indices count elements
+/// in sorted order from 0, and None stands for the head.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvariantError {
+    /// The element at `index` is less than the one before it on `level`.
+    Unsorted { level: usize, index: usize },
+    /// A node on `level` isn't a node of level 0 reaching `level`, or comes before the node
+    /// before it on `level` in level 0 order. Right after `index`, the chain of `level` isn't a
+    /// subsequence of level 0.
+    NotSubsequence { level: usize, index: Option<usize> },
+    /// The prev link of the node at `index` doesn't lead to the node before it on level 0.
+    BrokenPrev { index: usize },
+    /// The link of `level` out of the node at `index` spans a different number of level 0
+    /// steps than its width says.
+    Width { level: usize, index: Option<usize> },
+    /// The list's length is `expected`, but level 0 holds `found` nodes.
+    Len { expected: usize, found: usize },
+    /// The tail isn't the last node of level 0.
+    Tail,
+    /// A node reaches a level at or above the list's height, or the top level below the
+    /// height is empty.
+    Height,
+    /// The node kept as the last of `level`, to append without a search, isn't.
+    Rightmost { level: usize },
+    /// The path kept from the last search isn't the last node before some position at `level`.
+    Finger { level: usize },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = |index: Option<usize>| index.map_or_else(|| "the head".into(), |index| format!("element {}", index));
+        match *self {
+            InvariantError::Unsorted { level, index } => write!(f, "level {} is out of order at element {}", level, index),
+            InvariantError::NotSubsequence { level, index } => {
+                write!(f, "level {} isn't a subsequence of level 0 after {}", level, at(index))
+            }
+            InvariantError::BrokenPrev { index } => write!(f, "the prev link of element {} is wrong", index),
+            InvariantError::Width { level, index } => write!(f, "the width of the level {} link out of {} is wrong", level, at(index)),
+            InvariantError::Len { expected, found } => write!(f, "the length is {} but level 0 holds {} nodes", expected, found),
+            InvariantError::Tail => write!(f, "the tail isn't the last node"),
+            InvariantError::Height => write!(f, "the height doesn't match the levels in use"),
+            InvariantError::Rightmost { level } => write!(f, "the last node of level {} is wrong", level),
+            InvariantError::Finger { level } => write!(f, "the search finger is wrong at level {}", level),
+        }
+    }
+}
+
+impl core::error::Error for InvariantError {}
+
+/// A sorted list of `T`, ordered by the comparator `C`. By default that's `T`'s `Ord` impl.
+///
+/// `G` picks the level of every new node, which is a coin flip per level by default. `A` is the
+/// allocator the head and every boxed node are allocated from, which is the global one by default.
+pub struct SkipList<T, const NUM_LEVELS: usize, C = OrdComparator, G = Geometric, A: Allocator = Global> {
+    head: Head<T, NUM_LEVELS>,
+    tail: Link<T, NUM_LEVELS>,
+    gen: G,
     len: usize,
+    // the number of levels any node reaches, and at least 1. Searches start at the highest of
+    // them, since every level above only holds the head
+    height: usize,
+    cmp: C,
+    policy: DuplicatePolicy,
+    // the most elements inserts may leave in the list, and which one they evict past that
+    max_len: Option<(usize, EvictPolicy)>,
+    // the path the last insert, removal or search by `find_path_by_mut` ended on, which the next
+    // one starts from when its target isn't before it. Linking and unlinking a node leave it on
+    // the node's position, and anything else that rewrites links resets it
+    finger: Option<[NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS]>,
+    // the last node at every level, or the head, so items past the tail are linked in without
+    // a search
+    rightmost: [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    // where nodes are allocated from when the list was built with `with_arena`. Otherwise every
+    // node is boxed on its own
+    arena: Option<Arena<SkipListNode<T, NUM_LEVELS>>>,
+    // removed nodes kept for reuse, chained through their level 0 links, one chain per level
+    free: [Link<T, NUM_LEVELS>; NUM_LEVELS],
+    free_len: usize,
+    free_cap: usize,
+    alloc: A,
+}
+
+// SAFETY: the list owns all of its nodes the way a Box would, and only reaches them through the
+// head it owns. Shared access to the list only ever hands out shared access to the elements, the
+// comparator, the generator and the allocator, so the list can cross threads whenever they all can.
+unsafe impl<T: Send, const NUM_LEVELS: usize, C: Send, G: Send, A: Allocator + Send> Send for SkipList<T, NUM_LEVELS, C, G, A> {}
+unsafe impl<T: Sync, const NUM_LEVELS: usize, C: Sync, G: Sync, A: Allocator + Sync> Sync for SkipList<T, NUM_LEVELS, C, G, A> {}
+
+/// A `SkipList` ordered by a closure `F: Fn(&T, &T) -> Ordering`, for element types that
+/// aren't `Ord` or need a different order. Create one with [`SkipList::with_comparator`].
+pub type SkipListBy<T, const NUM_LEVELS: usize, F, G = Geometric> = SkipList<T, NUM_LEVELS, F, G>;
+
+/// A `SkipList` ordered by a key extracted from each element by `F: Fn(&T) -> K`, where
+/// `K: Ord`. Create one with [`SkipList::with_key`].
+pub type SkipListByKey<T, const NUM_LEVELS: usize, F, G = Geometric> = SkipList<T, NUM_LEVELS, KeyComparator<F>, G>;
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> Drop for SkipList<T, NUM_LEVELS, C, G, A> {
+    fn drop(&mut self) {
+        // frees what's left even if dropping an element panics
+        struct Guard<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator>(&'a mut SkipList<T, NUM_LEVELS, C, G, A>);
+
+        impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> Drop for Guard<'_, T, NUM_LEVELS, C, G, A> {
+            fn drop(&mut self) {
+                self.0.release_free_nodes();
+                let layout = SkipListNode::<T, NUM_LEVELS>::layout(NUM_LEVELS - 1);
+                // SAFETY: the head was allocated from the list's allocator with this layout
+                unsafe { self.0.alloc.deallocate(self.0.head.0.cast(), layout) };
+            }
+        }
+
+        self.free_cap = 0;
+        Guard(self).0.drop_nodes();
+    }
 }
 
-impl<T: PartialOrd + PartialEq + Debug, const NUM_LEVELS: usize> Debug for SkipList<T, NUM_LEVELS> {
+impl<T: Debug, const NUM_LEVELS: usize, C, G, A: Allocator> Debug for SkipList<T, NUM_LEVELS, C, G, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut args = Vec::new();
-        let mut node = self.head.as_ref();
+        let mut node = self.head.node();
         loop {
             args.push(format!("{:?}", node));
             match node.next(0) {
@@ -30,107 +269,163 @@ impl<T: PartialOrd + PartialEq + Debug, const NUM_LEVELS: usize> Debug for SkipL
     }
 }
 
-#[derive(Debug)]
-pub struct SkipListNode<T: PartialEq + Debug, const NUM_LEVELS: usize> {
-    level: usize,
+// Searches read a node's value, then its level to find its tower, then a link in the tower,
+// which is most often one of the lowest. So those come first and in that order, with the cold
+// prev link last, right before the tower. Node allocations aren't aligned to cache lines: that
+// pads most nodes to a full line, so fewer of them fit in cache, and the node_layout bench has
+// searches of 100000 elements and more at least 10% slower with it, for no gain below that
+#[repr(C)]
+pub struct SkipListNode<T, const NUM_LEVELS: usize> {
     val: Option<T>,
+    level: usize,
     prev: Link<T, NUM_LEVELS>,
-    next: [Link<T, NUM_LEVELS>; NUM_LEVELS],
+    // followed in the same allocation by the node's tower, with one `Level` for each of the
+    // levels 0..=level the node is linked into. The head's tower reaches every level
 }
 
-impl<T: PartialEq + Debug, const NUM_LEVELS: usize> Drop for SkipListNode<T, NUM_LEVELS> {
-    fn drop(&mut self) {
-        let mut node = self.next[0].take();
-        while let Some(next) = node {
-            let mut next = unsafe { Box::from_raw(next.as_ptr()) };
-            node = next.next[0].take();
-        }
+/// One level of a node's tower.
+struct Level<T, const NUM_LEVELS: usize> {
+    next: Link<T, NUM_LEVELS>,
+    // the number of level 0 steps the link spans. A link that is None spans up to the position
+    // right after the last node, so the widths along any level always add up to len + 1.
+    width: usize,
+}
+
+impl<T, const NUM_LEVELS: usize> Clone for Level<T, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        *self
     }
 }
 
-impl<T: PartialEq + Debug, const NUM_LEVELS: usize> SkipListNode<T, NUM_LEVELS> {
-    fn val(&self) -> Option<&T> {
-        self.val.as_ref()
+impl<T, const NUM_LEVELS: usize> Copy for Level<T, NUM_LEVELS> {}
+
+impl<T: Debug, const NUM_LEVELS: usize> Debug for SkipListNode<T, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the tower lies past what a reference to the node covers, so only a NodeRef prints it
+        f.debug_struct("SkipListNode")
+            .field("level", &self.level)
+            .field("val", &self.val)
+            .field("prev", &self.prev)
+            .finish_non_exhaustive()
     }
+}
 
-    fn next(&self, level: usize) -> Option<&Self> {
-        assert!(level < NUM_LEVELS);
+/// A shared reference to a node, tower included.
+///
+/// A `&SkipListNode` only covers the fields before the tower, so the tower can't be reached
+/// from one. This keeps the pointer the node was allocated through instead, which covers the
+/// whole allocation, for as long as the list it belongs to is borrowed.
+struct NodeRef<'a, T, const NUM_LEVELS: usize> {
+    ptr: NonNull<SkipListNode<T, NUM_LEVELS>>,
+    _marker: PhantomData<&'a SkipListNode<T, NUM_LEVELS>>,
+}
 
-        // SAFETY: If a link is some, it points to a SkipListNode
-        unsafe { self.next[level].map(|p| p.as_ref()) }
+impl<T, const NUM_LEVELS: usize> Clone for NodeRef<'_, T, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        *self
     }
+}
 
-    fn next_mut(&mut self, level: usize) -> Option<&mut Self> {
-        assert!(level < NUM_LEVELS);
+impl<T, const NUM_LEVELS: usize> Copy for NodeRef<'_, T, NUM_LEVELS> {}
+
+// SAFETY: a NodeRef is a shared reference to its node and the nodes after it
+unsafe impl<T: Sync, const NUM_LEVELS: usize> Send for NodeRef<'_, T, NUM_LEVELS> {}
+unsafe impl<T: Sync, const NUM_LEVELS: usize> Sync for NodeRef<'_, T, NUM_LEVELS> {}
 
-        // SAFETY: If a link is Some, it points to SkipListNode
-        unsafe { self.next[level].as_mut().map(|p| p.as_mut()) }
+impl<T, const NUM_LEVELS: usize> Deref for NodeRef<'_, T, NUM_LEVELS> {
+    type Target = SkipListNode<T, NUM_LEVELS>;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the node is valid and left unchanged for as long as its list is borrowed
+        unsafe { self.ptr.as_ref() }
     }
+}
 
-    fn next_if(&self, level: usize, f: impl FnOnce(&Self, &Self) -> bool) -> Result<&Self, &Self> {
-        assert!(level < NUM_LEVELS);
+impl<T: Debug, const NUM_LEVELS: usize> Debug for NodeRef<'_, T, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SkipListNode")
+            .field("level", &self.level)
+            .field("val", &self.val)
+            .field("prev", &self.prev)
+            .field("next", &self.tower().iter().map(|level| level.next).collect::<Vec<_>>())
+            .field("width", &self.tower().iter().map(|level| level.width).collect::<Vec<_>>())
+            .finish()
+    }
+}
 
-        // SAFETY: If a link is Some, it points to SkipListNode
-        let next = unsafe { self.next[level].map(|p| p.as_ref()) };
-        match next {
-            Some(next) if f(self, next) => Ok(next),
-            _ => Err(self),
-        }
+impl<'a, T, const NUM_LEVELS: usize> NodeRef<'a, T, NUM_LEVELS> {
+    /// SAFETY: `ptr` must be the head or a node of a list that stays borrowed, and unchanged,
+    /// for `'a`, and it must come from the list's own links, which cover the whole allocation.
+    unsafe fn new(ptr: NonNull<SkipListNode<T, NUM_LEVELS>>) -> Self {
+        NodeRef { ptr, _marker: PhantomData }
     }
 
-    fn prev(&self) -> Option<&Self> {
-        // SAFETY: If a link is Some, it points to a SkipListNode
-        unsafe { self.prev.map(|p| p.as_ref()) }
+    fn ptr(self) -> NonNull<SkipListNode<T, NUM_LEVELS>> {
+        self.ptr
     }
 
-    fn prev_mut(&mut self) -> Option<&mut Self> {
-        // SAFETY: If a link is Some, it points to a SkipList
-        unsafe { self.prev.as_mut().map(|p| p.as_mut()) }
+    /// Returns the node without its tower, for as long as the list is borrowed.
+    fn get(self) -> &'a SkipListNode<T, NUM_LEVELS> {
+        // SAFETY: as in deref
+        unsafe { self.ptr.as_ref() }
     }
 
-    fn next_if_mut(
-        &mut self,
-        level: usize,
-        f: impl FnOnce(&Self, &Self) -> bool,
-    ) -> Result<&mut Self, &mut Self> {
-        assert!(level < NUM_LEVELS);
+    fn val(self) -> Option<&'a T> {
+        self.get().val.as_ref()
+    }
 
-        // SAFETY: If a link is some, it points to SkipListNode
-        let next = unsafe { self.next[level].as_mut().map(|p| p.as_mut()) };
-        match next {
-            Some(next) if f(self, next) => Ok(next),
-            _ => Err(self),
+    /// Returns the levels this node is linked into, from the bottom up.
+    fn tower(self) -> &'a [Level<T, NUM_LEVELS>] {
+        // SAFETY: ptr points to a node, whose tower is left unchanged for 'a
+        unsafe { SkipListNode::tower(self.ptr) }
+    }
+
+    /// Hints the CPU to start loading the next node at `level`, which a search reads right after
+    /// comparing against this one. Searches of large lists are bound by cache misses, and this
+    /// overlaps the next one with the comparison. Searches only do it once their list outgrows
+    /// the cache, see `SkipList::prefetches`. Does nothing off x86_64.
+    #[inline(always)]
+    fn prefetch_next(self, level: usize) {
+        #[cfg(all(target_arch = "x86_64", not(skiplist_no_prefetch)))]
+        if let Some(next) = self.tower()[level].next {
+            use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            // SAFETY: x86_64 always has SSE, and a prefetch never faults, whatever the address
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(next.as_ptr().cast()) };
         }
+        #[cfg(any(not(target_arch = "x86_64"), skiplist_no_prefetch))]
+        let _ = level;
     }
 
-    fn proceed_at_level_while(
-        &self,
-        level: usize,
-        mut f: impl FnMut(&Self, &Self) -> bool,
-    ) -> &Self {
+    fn next(self, level: usize) -> Option<Self> {
         assert!(level < NUM_LEVELS);
 
-        let mut curr = self;
-        loop {
-            match curr.next_if(level, &mut f) {
-                Ok(next) => {
-                    curr = next;
+        // SAFETY: If a link is some, it points to a SkipListNode of the same list
+        self.tower()[level].next.map(|p| unsafe { NodeRef::new(p) })
+    }
+
+    fn next_if(self, level: usize, prefetch: bool, f: impl FnOnce(Self, Self) -> bool) -> Result<Self, Self> {
+        match self.next(level) {
+            Some(next) => {
+                if prefetch {
+                    next.prefetch_next(level);
                 }
-                Err(curr) => return curr,
+                if f(self, next) { Ok(next) } else { Err(self) }
             }
+            None => Err(self),
         }
     }
 
-    fn proceed_at_level_while_mut(
-        &mut self,
-        level: usize,
-        mut f: impl FnMut(&Self, &Self) -> bool,
-    ) -> &mut Self {
+    fn prev(self) -> Option<Self> {
+        // SAFETY: If a link is Some, it points to a SkipListNode of the same list
+        self.prev.map(|p| unsafe { NodeRef::new(p) })
+    }
+
+    fn proceed_at_level_while(self, level: usize, prefetch: bool, mut f: impl FnMut(Self, Self) -> bool) -> Self {
         assert!(level < NUM_LEVELS);
 
         let mut curr = self;
         loop {
-            match curr.next_if_mut(level, &mut f) {
+            match curr.next_if(level, prefetch, &mut f) {
                 Ok(next) => {
                     curr = next;
                 }
@@ -140,22 +435,66 @@ impl<T: PartialEq + Debug, const NUM_LEVELS: usize> SkipListNode<T, NUM_LEVELS>
     }
 }
 
-impl<T: PartialEq + Debug, const NUM_LEVELS: usize> SkipListNode<T, NUM_LEVELS> {
-    fn new_head() -> SkipListNode<T, NUM_LEVELS> {
-        SkipListNode {
-            level: NUM_LEVELS - 1,
-            val: None,
-            prev: None,
-            next: [None; NUM_LEVELS],
-        }
+/// The head of a `SkipList`, which reaches every level and never holds an element. The list
+/// allocates and frees it.
+struct Head<T, const NUM_LEVELS: usize>(NonNull<SkipListNode<T, NUM_LEVELS>>);
+
+impl<T, const NUM_LEVELS: usize> Head<T, NUM_LEVELS> {
+    /// Returns the head, for a search to start from.
+    fn node(&self) -> NodeRef<'_, T, NUM_LEVELS> {
+        // SAFETY: the head lives as long as its list, which only hands it out through itself
+        unsafe { NodeRef::new(self.0) }
     }
-    fn new(val: T, level: usize, prev: Link<T, NUM_LEVELS>) -> SkipListNode<T, NUM_LEVELS> {
-        SkipListNode {
-            level,
-            val: Some(val),
-            prev: prev,
-            next: [None; NUM_LEVELS],
+
+    fn next(&self, level: usize) -> Option<NodeRef<'_, T, NUM_LEVELS>> {
+        self.node().next(level)
+    }
+
+    fn tower(&self) -> &[Level<T, NUM_LEVELS>] {
+        self.node().tower()
+    }
+
+    fn tower_mut(&mut self) -> &mut [Level<T, NUM_LEVELS>] {
+        // SAFETY: as in node, and the list is borrowed mutably
+        unsafe { SkipListNode::tower_mut(self.0) }
+    }
+
+    /// Returns, for every level, the last node at that level such that `f` returns true for it
+    /// and every node before it, starting from the head. Only the lowest `height` levels are
+    /// searched, and the path stays on the head above them. The path is made from the list's own
+    /// pointer to the head, so it stays valid when the head is borrowed again.
+    fn path_while_mut(
+        &mut self,
+        height: usize,
+        prefetch: bool,
+        mut f: impl FnMut(NodeRef<'_, T, NUM_LEVELS>) -> bool,
+    ) -> [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS] {
+        let mut node = self.node();
+        let mut path = [node.ptr(); NUM_LEVELS];
+        for level in (0..height).rev() {
+            while let Some(next) = node.next(level) {
+                if prefetch {
+                    next.prefetch_next(level);
+                }
+                if !f(next) {
+                    break;
+                }
+                node = next;
+            }
+            path[level] = node.ptr();
         }
+        path
+    }
+}
+
+// SAFETY: a node's links only lead to the nodes after it in the same list, which are never
+// mutated through a shared reference, so a node is as thread-safe as the elements it leads to.
+unsafe impl<T: Send, const NUM_LEVELS: usize> Send for SkipListNode<T, NUM_LEVELS> {}
+unsafe impl<T: Sync, const NUM_LEVELS: usize> Sync for SkipListNode<T, NUM_LEVELS> {}
+
+impl<T, const NUM_LEVELS: usize> SkipListNode<T, NUM_LEVELS> {
+    fn val(&self) -> Option<&T> {
+        self.val.as_ref()
     }
 
     fn is_head(&self) -> bool {
@@ -163,159 +502,3334 @@ impl<T: PartialEq + Debug, const NUM_LEVELS: usize> SkipListNode<T, NUM_LEVELS>
     }
 }
 
-impl<T: PartialEq + Debug, const NUM_LEVELS: usize> SkipList<T, NUM_LEVELS> {
+impl<T, const NUM_LEVELS: usize> SkipListNode<T, NUM_LEVELS> {
+    /// Returns the layout of a node reaching `level`, tower included.
+    fn layout(level: usize) -> Layout {
+        let tower = Layout::array::<Level<T, NUM_LEVELS>>(level + 1).unwrap();
+        // the tower starts right at the end of the node, since the node is at least as aligned
+        Layout::new::<Self>().extend(tower).unwrap().0.pad_to_align()
+    }
+
+    /// Writes a node reaching `level` to `ptr`, with every link None and every width `width`.
+    ///
+    /// SAFETY: `ptr` must be valid for writes of `layout(level)`, and aligned for it.
+    unsafe fn init(ptr: NonNull<u8>, val: Option<T>, level: usize, prev: Link<T, NUM_LEVELS>, width: usize) -> NonNull<Self> {
+        let node = ptr.cast::<Self>();
+        node.as_ptr().write(SkipListNode { val, level, prev });
+        let tower = node.as_ptr().add(1).cast::<Level<T, NUM_LEVELS>>();
+        for level in 0..=level {
+            tower.add(level).write(Level { next: None, width });
+        }
+        node
+    }
+
+    /// Returns the levels `node` is linked into, from the bottom up. It's reached through `node`
+    /// itself, since a reference to the node doesn't cover the tower right after it.
+    ///
+    /// SAFETY: `node` must point to a node, through a pointer covering its whole allocation, and
+    /// its tower must not be written to for `'a`.
+    unsafe fn tower<'a>(node: NonNull<Self>) -> &'a [Level<T, NUM_LEVELS>] {
+        let tower = node.as_ptr().add(1).cast::<Level<T, NUM_LEVELS>>();
+        slice::from_raw_parts(tower, (*node.as_ptr()).level + 1)
+    }
+
+    /// SAFETY: as for tower, and the tower must not be accessed in any other way for `'a`.
+    unsafe fn tower_mut<'a>(node: NonNull<Self>) -> &'a mut [Level<T, NUM_LEVELS>] {
+        let tower = node.as_ptr().add(1).cast::<Level<T, NUM_LEVELS>>();
+        slice::from_raw_parts_mut(tower, (*node.as_ptr()).level + 1)
+    }
+}
+
+impl<T, const NUM_LEVELS: usize> SkipList<T, NUM_LEVELS> {
     pub fn new() -> Self {
-        let head = Box::new(SkipListNode::<T, NUM_LEVELS>::new_head());
-        SkipList { head, rng: fastrand::Rng::new(), len: 0 }
+        SkipList::with_comparator(OrdComparator)
+    }
+
+    /// Creates an empty list whose levels are drawn from a random generator seeded with `seed`,
+    /// so inserting the same elements in the same order always builds the same towers.
+    pub fn with_seed(seed: u64) -> Self {
+        SkipList::with_level_generator(OrdComparator, Geometric::with_seed(seed))
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, A: Allocator> SkipList<T, NUM_LEVELS, OrdComparator, Geometric, A> {
+    /// Creates an empty list whose head and nodes are allocated from `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        SkipList::with_level_generator_in(OrdComparator, Geometric::new(), alloc)
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, K: Ord, F: Fn(&T) -> K> SkipList<T, NUM_LEVELS, KeyComparator<F>> {
+    /// Creates an empty list ordered by the key `f` extracts from each element, like
+    /// `SkipList::with_key(|user: &User| user.id)`.
+    pub fn with_key(f: F) -> Self {
+        SkipList::with_comparator(KeyComparator(f))
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C> SkipList<T, NUM_LEVELS, C> {
+    /// Creates an empty list ordered by `cmp`. Closures need their argument types spelled out,
+    /// as in `SkipList::with_comparator(|a: &f64, b: &f64| a.total_cmp(b))`.
+    pub fn with_comparator(cmp: C) -> Self {
+        SkipList::with_level_generator(cmp, Geometric::new())
+    }
+
+    /// Sets the probability `p` that a node reaching some level also reaches the next one,
+    /// which must be between 0 and 1. The default is 1/2.
+    ///
+    /// Nodes have `1 / (1 - p)` links on average, and searches take about `log(n) / (p * log(1/p))`
+    /// steps. Lowering `p` to 1/4 or 1/e saves memory and pointer chasing on large lists.
+    pub fn with_promotion_probability(mut self, p: f64) -> Self {
+        let gen = mem::take(&mut self.gen);
+        self.gen = gen.with_probability(p);
+        self
+    }
+
+    /// Returns the probability that a node reaching some level also reaches the next one.
+    pub fn promotion_probability(&self) -> f64 {
+        self.gen.probability()
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C, G> SkipList<T, NUM_LEVELS, C, G> {
+    /// Creates an empty list ordered by `cmp` whose node levels are picked by `gen`, like
+    /// `SkipList::with_level_generator(OrdComparator, Deterministic::new())`.
+    pub fn with_level_generator(cmp: C, gen: G) -> Self {
+        SkipList::with_level_generator_in(cmp, gen, Global)
+    }
+
+    /// Allocates the list's nodes from an arena instead of boxing each one, so nodes inserted
+    /// together sit next to each other in memory and the allocator is only called once per
+    /// chunk of nodes. The list must be empty.
+    ///
+    /// The memory of removed nodes is only given back by [`clear`](SkipList::clear) or when the
+    /// list is dropped, so this suits lists that mostly grow, or are rebuilt from scratch, more
+    /// than lists with heavy churn. In exchange, clearing or dropping a list whose elements don't
+    /// need dropping frees whole chunks without visiting any node.
+    pub fn with_arena(mut self) -> Self {
+        assert!(self.is_empty(), "with_arena: the list must be empty");
+        self.arena = Some(Arena::new());
+        self
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Like [`with_level_generator`](SkipList::with_level_generator), but allocates the head and
+    /// every node from `alloc`.
+    pub fn with_level_generator_in(cmp: C, gen: G, alloc: A) -> Self {
+        let layout = SkipListNode::<T, NUM_LEVELS>::layout(NUM_LEVELS - 1);
+        let head = alloc.allocate(layout).unwrap_or_else(|_| handle_alloc_error(layout));
+        // SAFETY: the memory was just allocated with the head's layout
+        let head = Head(unsafe { SkipListNode::init(head.cast(), None, NUM_LEVELS - 1, None, 1) });
+        SkipList {
+            rightmost: [head.0; NUM_LEVELS],
+            head,
+            tail: None,
+            gen,
+            len: 0,
+            height: 1,
+            cmp,
+            policy: DuplicatePolicy::Allow,
+            max_len: None,
+            finger: None,
+            arena: None,
+            free: [None; NUM_LEVELS],
+            free_len: 0,
+            free_cap: 0,
+            alloc,
+        }
+    }
+
+    /// Returns the allocator the list's head and nodes are allocated from.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Keeps up to `capacity` removed nodes around and reuses them for new elements of the same
+    /// level, so workloads that keep inserting and removing stop going through the allocator.
+    ///
+    /// Cached nodes hold no element. Their memory is given back by
+    /// [`release_free_nodes`](SkipList::release_free_nodes) or when the list is dropped.
+    pub fn with_free_list(mut self, capacity: usize) -> Self {
+        self.free_cap = capacity;
+        self
+    }
+
+    /// Gives the memory of every node cached for reuse back to the allocator.
+    pub fn release_free_nodes(&mut self) {
+        for level in 0..NUM_LEVELS {
+            let mut node = self.free[level].take();
+            while let Some(curr) = node {
+                // SAFETY: cached nodes are owned by the list and chained through their level 0
+                // links. Each one is freed after its link is read
+                unsafe {
+                    node = SkipListNode::tower(curr)[0].next;
+                    self.dealloc_node(curr);
+                }
+            }
+        }
+        self.free_len = 0;
+    }
+
+    /// Sets the policy [`insert`](SkipList::insert) and `extend` follow when an equal element
+    /// is already in the list.
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the policy [`insert`](SkipList::insert) and `extend` follow when an equal element
+    /// is already in the list.
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
+    /// Caps the list at `max_len` elements. An insert that takes the list past it evicts the
+    /// smallest or the largest element, as `policy` says, and returns it; `extend` drops the
+    /// elements it evicts. Elements already in the list beyond `max_len` are dropped right away.
+    ///
+    /// Only [`insert`](SkipList::insert), its variants, and `extend` evict. Inserting through a
+    /// cursor or an entry, or appending another list, can still take the list past `max_len`,
+    /// until the next insert evicts the surplus.
+    pub fn with_max_len(mut self, max_len: usize, policy: EvictPolicy) -> Self {
+        assert!(max_len > 0, "max_len must be at least 1");
+        self.max_len = Some((max_len, policy));
+        while self.evict().is_some() {}
+        self
+    }
+
+    /// Returns the maximum length set by [`with_max_len`](SkipList::with_max_len).
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len.map(|(max_len, _)| max_len)
+    }
+
+    /// Removes and returns one element if the list is longer than its maximum length.
+    fn evict(&mut self) -> Option<T> {
+        match self.max_len {
+            Some((max_len, EvictPolicy::Smallest)) if self.len > max_len => self.pop_front(),
+            Some((max_len, EvictPolicy::Largest)) if self.len > max_len => self.pop_back(),
+            _ => None,
+        }
     }
 
-    pub fn gen_level(&self) -> usize {
-        let max_level = NUM_LEVELS - 1;
-        let mask = (1 << max_level) - 1;
-        let rand = self.rng.usize(..);
-        let jawn = rand & mask;
-        jawn.trailing_ones() as usize
+    /// Returns the generator that picks the level of every new node.
+    pub fn level_generator(&self) -> &G {
+        &self.gen
     }
 
+    pub fn gen_level(&mut self) -> usize
+    where
+        G: LevelGenerator,
+    {
+        self.gen.gen_level(NUM_LEVELS - 1)
+    }
+
+    /// Creates an empty list with the same comparator, allocator and settings as this one.
+    fn empty_like(&self, gen: G) -> Self
+    where
+        C: Clone,
+        A: Clone,
+    {
+        let mut list = SkipList::with_level_generator_in(self.cmp.clone(), gen, self.allocator().clone());
+        list.policy = self.policy;
+        list.max_len = self.max_len;
+        list.arena = self.arena.as_ref().map(|_| Arena::new());
+        list.free_cap = self.free_cap;
+        list
+    }
+
+    /// Returns an iterator over the elements of the list in sorted order.
+    pub fn iter(&self) -> Iter<'_, T, NUM_LEVELS> {
+        Iter::new(self)
+    }
+
+    /// Returns an iterator over mutable references to the elements of the list in sorted order.
+    ///
+    /// Keeping the list sorted is the caller's responsibility: elements must not be modified
+    /// in a way that changes their order relative to each other. Doing so won't cause undefined
+    /// behavior, but searches may miss elements afterwards.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, NUM_LEVELS> {
+        IterMut::new(self)
+    }
+
+    /// Returns a cursor on the first element of the list, or on the ghost if the list is empty.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T, NUM_LEVELS, C, G, A> {
+        let first = self.head.tower()[0].next;
+        let path = [self.head.0; NUM_LEVELS];
+        // SAFETY: the head is the last node before the first node at every level
+        unsafe { CursorMut::new(self, first, path) }
+    }
+
+    /// Returns a cursor on the last element of the list, or on the ghost if the list is empty.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T, NUM_LEVELS, C, G, A> {
+        let last = self.tail;
+        let path = self.find_path_mut(|next| last.is_some_and(|last| next.ptr() != last));
+        // SAFETY: the search stops right before the tail at every level, or goes nowhere if there's no tail
+        unsafe { CursorMut::new(self, last, path) }
+    }
+
+    /// Removes all elements from the list, returning them in sorted order as an iterator.
+    ///
+    /// The list is empty once the iterator is dropped, whether or not it was fully consumed.
+    pub fn drain(&mut self) -> Drain<'_, T, NUM_LEVELS, C, G, A> {
+        Drain::new(self)
+    }
+
+    /// Returns an iterator that removes and yields, in sorted order, every element for which
+    /// `pred` returns true.
+    ///
+    /// The whole list is traversed in a single pass over level 0. Elements that haven't been
+    /// visited when the iterator is dropped are left in the list.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, NUM_LEVELS, F, C, G, A> {
+        ExtractIf::new(self, pred)
+    }
+
+    /// Retains only the elements for which `f` returns true, in a single pass over level 0.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.extract_if(|v| !f(v)).for_each(drop);
+    }
+
+    /// Removes all elements from the list, keeping its head and random state.
+    pub fn clear(&mut self) {
+        self.drop_nodes();
+    }
+
+    /// Returns the number of elements in the list.
     pub fn len(&self) -> usize {
         self.len
     }
 
-    pub fn find(&self, mut f: impl FnMut(&T) -> Ordering) -> Option<&T> {
-        self.find_node(|v| f(v) != Ordering::Greater).val()
+    /// Returns true if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn find_node(&self, f: impl FnMut(&T) -> bool) -> &SkipListNode<T, NUM_LEVELS> {
+        self.find_node_ref(f).get()
+    }
+
+    pub fn find_node_mut(&mut self, f: impl FnMut(&T) -> bool) -> &mut SkipListNode<T, NUM_LEVELS> {
+        let node = self.find_node_ref(f).ptr();
+        // SAFETY: node is the head or a node in the list, which is borrowed mutably
+        unsafe { &mut *node.as_ptr() }
     }
 
-    pub fn find_node(&self, mut f: impl FnMut(&T) -> bool) -> &SkipListNode<T, NUM_LEVELS> {
-        let mut node = self.head.as_ref();
-        for level in (0..NUM_LEVELS).rev() {
-            node = node.proceed_at_level_while(level, |_, next| {
-                next.val().map_or(false, |v2| f(v2))
+    /// Returns the last node such that `f` returns true for it and every node before it, or the
+    /// head if there is none.
+    fn find_node_ref(&self, mut f: impl FnMut(&T) -> bool) -> NodeRef<'_, T, NUM_LEVELS> {
+        let mut node = self.head.node();
+        for level in (0..self.height).rev() {
+            node = node.proceed_at_level_while(level, self.prefetches(), |_, next| {
+                next.val().is_some_and(&mut f)
             });
         }
         node
     }
 
-    pub fn find_node_mut(&mut self, mut f: impl FnMut(&T) -> bool) -> &mut SkipListNode<T, NUM_LEVELS> {
-        let mut node = self.head.as_mut();
-        for level in (0..NUM_LEVELS).rev() {
-            node = node.proceed_at_level_while_mut(level, |_, next| {
-                next.val().map_or(false, |v2| f(v2))
-            })
-        }
-        node
+    /// Returns the first element for which `f` returns `Ordering::Equal`, in O(log n).
+    ///
+    /// `f` tells how an element compares to the one being looked for, like the closure of
+    /// `slice::binary_search_by`, so the list can be searched by a projection of its elements,
+    /// such as a key field, without building a whole element to compare against. It must agree
+    /// with the list's order.
+    pub fn find_by(&self, mut f: impl FnMut(&T) -> Ordering) -> Option<&T> {
+        self.find_node_ref(|v| f(v) == Ordering::Less)
+            .next(0)
+            .and_then(|node| node.val())
+            .filter(|&v| f(v) == Ordering::Equal)
     }
 
-    pub fn contains(&self, mut f: impl FnMut(&T) -> Ordering) -> bool {
-        self.find(|v| f(v)).map_or(false, |v| f(v) == Ordering::Equal)
+    /// Returns true if the list contains an element for which `f` returns `Ordering::Equal`,
+    /// searching like [`find_by`](SkipList::find_by).
+    pub fn contains_by(&self, f: impl FnMut(&T) -> Ordering) -> bool {
+        self.find_by(f).is_some()
     }
 
-    pub fn insert(&mut self, item: T, mut cmp: impl FnMut(&T, &T) -> Ordering) {
-        let new_node_level = self.gen_level();
-        
-        let new_node = Box::new(SkipListNode::<T, NUM_LEVELS>::new(
-            item,
-            new_node_level,
-            None
-        ));
+    /// Returns a reference to the element at `index` in sorted order, in O(log n).
+    ///
+    /// The search skips ahead by the width of each link instead of comparing elements.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+
+        // the number of level 0 steps left from node to the target
+        let mut steps = index + 1;
+        let mut node = self.head.node();
+        for level in (0..self.height).rev() {
+            node = node.proceed_at_level_while(level, self.prefetches(), |curr, _| {
+                let fits = curr.tower()[level].width <= steps;
+                if fits {
+                    steps -= curr.tower()[level].width;
+                }
+                fits
+            });
+        }
+        node.val()
+    }
 
+    /// Removes the element at `index` in sorted order and returns it, in O(log n).
+    pub fn remove_at(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
 
-        // SAFETY: box never null, so NonNull::new_unchecked is ok
-        let mut new_node = unsafe { NonNull::new_unchecked(Box::into_raw(new_node)) };
-        let item = unsafe { new_node.as_ref().val().unwrap() };
+        let path = self.find_path_at_mut(index);
+        // SAFETY: path[0] is the head or a node in the list, and index is in bounds so it has a successor
+        let target = unsafe { SkipListNode::tower(path[0])[0].next.unwrap() };
+        // SAFETY: path holds the last node before index at every level
+        Some(unsafe { self.unlink(target, &path) })
+    }
 
-        let mut node = self.head.as_mut();
-        let mut level = NUM_LEVELS;
-        let old_next = loop {
-            level -= 1;
+    /// Returns the k-th smallest element, counting from 0, in O(log n). This is the inverse
+    /// of [`rank`](SkipList::rank), and the same as [`get`](SkipList::get).
+    pub fn select(&self, k: usize) -> Option<&T> {
+        self.get(k)
+    }
 
-            node = node.proceed_at_level_while_mut(level, |_, next| {
-                next.val().map_or(false, |v2| cmp(item, v2) != Ordering::Less)
-            });
+    /// Returns the median element, in O(log n). For an even number of elements, that's the
+    /// lower of the two middle ones.
+    pub fn median(&self) -> Option<&T> {
+        self.get(self.len.checked_sub(1)? / 2)
+    }
 
-            
-            if level <= new_node_level {
-                let old_next = node.next[level].replace(new_node);
+    /// Removes the smallest element from the list and returns it.
+    pub fn pop_front(&mut self) -> Option<T> {
+        let first = self.head.tower()[0].next?;
+        let path = [self.head.0; NUM_LEVELS];
 
-                // SAFETY: new_node hasn't been deleted yet since we're still inserting it
-                unsafe { new_node.as_mut().next[level] = old_next };
-                
-                if level == 0 {
-                    break old_next;
-                }
-            }
-        };
+        // SAFETY: first is the first node after the head, so the head is its predecessor at every level
+        Some(unsafe { self.unlink(first, &path) })
+    }
 
-        self.len += 1;
+    /// Returns a reference to the smallest element in the list.
+    pub fn first(&self) -> Option<&T> {
+        self.head.next(0).and_then(|node| node.val())
+    }
 
-        unsafe { new_node.as_mut().prev = Some(node.into())}
+    /// Returns a mutable reference to the smallest element in the list.
+    ///
+    /// The element must not be modified in a way that changes its position in the list.
+    pub fn first_mut(&mut self) -> Option<&mut T> {
+        let first = self.head.tower()[0].next?;
+        // SAFETY: If a link is Some, it points to a SkipListNode, and we have unique access to the list
+        unsafe { (*first.as_ptr()).val.as_mut() }
+    }
 
-        match old_next {
-            // SAFETY: old_next.as_mut() ok because a link is Some iff it points to a valid SkipListNode
-            Some(mut old_next) => unsafe {
-                old_next.as_mut().prev = Some(new_node);
-            }
-            None => {}
-        }
+    /// Returns a reference to the largest element in the list.
+    pub fn last(&self) -> Option<&T> {
+        // SAFETY: If a link is Some, it points to a SkipListNode
+        self.tail.and_then(|p| unsafe { p.as_ref() }.val())
     }
 
-    // fn remove(&mut self, mut cmp: impl FnMut(&T) -> Ordering) -> T {
-    //     let mut node = self.head.as_mut();
-    //     let mut level = NUM_LEVELS;
-    //     let old_next = loop {
-    //         level -= 1;
+    /// Returns a mutable reference to the largest element in the list.
+    ///
+    /// The element must not be modified in a way that changes its position in the list.
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: If a link is Some, it points to a SkipListNode, and we have unique access to the list
+        self.tail.and_then(|mut p| unsafe { p.as_mut() }.val.as_mut())
+    }
 
-    //         node = node.proceed_at_level_while_mut(level, |_, next| {
-    //             next.val().map_or(false, |v2| cmp(v2) != Ordering::Less)
-    //         });
+    /// Removes the largest element from the list and returns it.
+    pub fn pop_back(&mut self) -> Option<T> {
+        let last = self.tail?;
+        let path = self.find_path_mut(|next| next.ptr() != last);
 
-            
-    //         if level <= new_node_level {
-    //             let old_next = node.next[level].replace(new_node);
+        // SAFETY: the search stops right before the tail at every level, so path[level] is its predecessor
+        Some(unsafe { self.unlink(last, &path) })
+    }
 
-    //             // SAFETY: new_node hasn't been deleted yet since we're still inserting it
-    //             unsafe { new_node.as_mut().next[level] = old_next };
-                
-    //             if level == 0 {
-    //                 break old_next;
-    //             }
-    //         }
-    //     };
-    // }
-}
+    /// Returns true if searches should prefetch the nodes they are about to compare against,
+    /// which only pays off once the list's nodes outgrow the cache. Below `PREFETCH_MIN_BYTES`
+    /// they stay in cache, and the prefetch instructions are pure overhead.
+    fn prefetches(&self) -> bool {
+        let node_bytes = mem::size_of::<SkipListNode<T, NUM_LEVELS>>() + mem::size_of::<Level<T, NUM_LEVELS>>();
+        self.len * node_bytes >= PREFETCH_MIN_BYTES
+    }
 
+    /// Returns an iterator over every node after the head, in level 0 order.
+    fn iter_nodes(&self) -> impl Iterator<Item = NodeRef<'_, T, NUM_LEVELS>> {
+        core::iter::successors(self.head.next(0), |node| node.next(0))
+    }
 
+    /// Returns, for every level, the last node at that level such that `f` returns true for it
+    /// and every node before it, or the head if there is none.
+    fn find_path_mut(
+        &mut self,
+        f: impl FnMut(NodeRef<'_, T, NUM_LEVELS>) -> bool,
+    ) -> [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS] {
+        self.head.path_while_mut(self.height, self.prefetches(), f)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::SkipList;
+    /// Returns, for every level, the last node before the one at `index` at that level, by
+    /// skipping ahead by link widths.
+    fn find_path_at_mut(&mut self, index: usize) -> [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS] {
+        // the number of level 0 steps left from node to the target's predecessor
+        let mut steps = index;
+        let mut node = self.head.0;
+        let mut path = [node; NUM_LEVELS];
+        for level in (0..self.height).rev() {
+            // SAFETY: node is the head or a node reachable from it, and a link is Some iff it points to a valid SkipListNode
+            unsafe {
+                while let Some(next) = SkipListNode::tower(node)[level].next {
+                    let width = SkipListNode::tower(node)[level].width;
+                    if width > steps {
+                        break;
+                    }
+                    steps -= width;
+                    node = next;
+                }
+            }
+            path[level] = node;
+        }
+        path
+    }
 
-    #[test]
-    fn insert_and_lookup_same_order() {
-        let mut l = SkipList::<usize, 8>::new();
-        for i in 0..10 {
-            l.insert(i, |curr, next| curr.cmp(next));
+    /// Returns, for every level below `levels`, the number of level 0 steps from `path[level]`
+    /// to `path[0]`.
+    ///
+    /// SAFETY: `path[level]` must be the last node before some position at every level, so that
+    /// `path[level - 1]` can be reached from `path[level]` at level `level - 1`.
+    unsafe fn path_offsets(
+        path: &[NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+        levels: usize,
+    ) -> [usize; NUM_LEVELS] {
+        let mut offsets = [0; NUM_LEVELS];
+        for level in 1..levels {
+            let mut node = path[level];
+            offsets[level] = offsets[level - 1];
+            while node != path[level - 1] {
+                offsets[level] += SkipListNode::tower(node)[level - 1].width;
+                node = SkipListNode::tower(node)[level - 1].next.unwrap();
+            }
         }
+        offsets
+    }
 
-        for i in 0..10 {
-            assert!(l.contains(|v| v.cmp(&i)));
+    /// Allocates a node holding `val` with the given level and links it in right after
+    /// `path[level]` at every level it participates in. Links that span the new node at higher
+    /// levels get one step wider.
+    ///
+    /// SAFETY: `path[level]` must be the last node before the new node's position at every level.
+    unsafe fn link(
+        &mut self,
+        val: T,
+        new_node_level: usize,
+        path: &[NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    ) -> NonNull<SkipListNode<T, NUM_LEVELS>> {
+        let new_node = self.alloc_node(val, new_node_level, Some(path[0]));
+        self.link_node(new_node, path)
+    }
+
+    /// Links the unlinked `new_node`, made by `alloc_node` with `path[0]` as its prev, in right
+    /// after `path[level]` at every level it participates in.
+    ///
+    /// SAFETY: as for `link`.
+    unsafe fn link_node(
+        &mut self,
+        new_node: NonNull<SkipListNode<T, NUM_LEVELS>>,
+        path: &[NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    ) -> NonNull<SkipListNode<T, NUM_LEVELS>> {
+        let new_node_level = new_node.as_ref().level;
+        let offsets = Self::path_offsets(path, new_node_level + 1);
+        for (level, &prev) in path.iter().enumerate() {
+            let prev = SkipListNode::tower_mut(prev);
+            if level <= new_node_level {
+                let new = &mut SkipListNode::tower_mut(new_node)[level];
+                new.next = prev[level].next.replace(new_node);
+                new.width = prev[level].width - offsets[level];
+                prev[level].width = offsets[level] + 1;
+                if new.next.is_none() {
+                    self.rightmost[level] = new_node;
+                }
+            } else {
+                prev[level].width += 1;
+            }
+        }
+
+        match SkipListNode::tower(new_node)[0].next {
+            Some(mut next) => next.as_mut().prev = Some(new_node),
+            None => self.tail = Some(new_node),
         }
+        self.len += 1;
+        self.height = self.height.max(new_node_level + 1);
+        let mut finger = *path;
+        finger[..=new_node_level].fill(new_node);
+        self.finger = Some(finger);
+
+        new_node
     }
 
-    #[test]
-    fn insert_and_lookup_different_order() {
-        let mut l = SkipList::<i32, 9>::new();
-        let mut nums = Vec::new();
-        for _ in 0..200 {
-            let i = fastrand::i32(..);
-            l.insert(i, |curr, next| curr.cmp(next));
-            nums.push(i);
+    /// Rebuilds every link of the list from the nodes returned by `next_node`, which is handed
+    /// the list's comparator and must return all of the list's nodes in sorted order, then None.
+    /// Every node keeps its level, and gets linked after the ones before it.
+    ///
+    /// SAFETY: `next_node` must return valid, distinct nodes owned by this list, and must read a
+    /// node's links before returning it since they are overwritten right after.
+    unsafe fn relink(&mut self, mut next_node: impl FnMut(&C) -> Link<T, NUM_LEVELS>) {
+        for level in self.head.tower_mut() {
+            level.next = None;
         }
+        let mut path = [self.head.0; NUM_LEVELS];
+        // the position of path[level] in level 0 order, counting the head as 0
+        let mut ranks = [0; NUM_LEVELS];
+        let mut len = 0;
+        let mut height = 1;
 
-        fastrand::shuffle(nums.as_mut());
+        while let Some(mut node) = next_node(&self.cmp) {
+            len += 1;
+            let node_level = node.as_ref().level;
+            height = height.max(node_level + 1);
+            for level in SkipListNode::tower_mut(node) {
+                level.next = None;
+            }
+            node.as_mut().prev = Some(path[0]);
+            for level in 0..=node_level {
+                SkipListNode::tower_mut(path[level])[level].next = Some(node);
+                SkipListNode::tower_mut(path[level])[level].width = len - ranks[level];
+                path[level] = node;
+                ranks[level] = len;
+            }
+        }
 
-        for i in nums.into_iter() {
-            assert!(l.contains(|v| v.cmp(&i)));
+        for (level, &last) in path.iter().enumerate() {
+            SkipListNode::tower_mut(last)[level].width = len + 1 - ranks[level];
         }
+
+        self.tail = Some(path[0]).filter(|last| !last.as_ref().is_head());
+        self.len = len;
+        self.height = height;
+        self.finger = None;
+        self.rightmost = path;
     }
 
-}
\ No newline at end of file
+    /// Links a node holding `val` with the given level after the current tail, and updates `path`
+    /// so that it keeps pointing at the last node of every level.
+    ///
+    /// SAFETY: `path[level]` must be the last node at every level, and `val` must not be less than
+    /// any element in the list.
+    unsafe fn push_back_with_level(
+        &mut self,
+        val: T,
+        level: usize,
+        path: &mut [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    ) {
+        let new_node = self.link(val, level, path);
+        path[..=level].fill(new_node);
+    }
+
+    /// Unlinks `node` from every level it participates in and returns its value. Links that
+    /// span the node at higher levels get one step narrower.
+    ///
+    /// SAFETY: `node` must be a non-head node in this list, and `path[level]` must be the last
+    /// node before it at every level.
+    unsafe fn unlink(
+        &mut self,
+        node: NonNull<SkipListNode<T, NUM_LEVELS>>,
+        path: &[NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    ) -> T {
+        let node_level = node.as_ref().level;
+        let tower = SkipListNode::tower(node);
+        for (level, &prev) in path.iter().enumerate() {
+            let prev = SkipListNode::tower_mut(prev);
+            if level <= node_level {
+                prev[level].next = tower[level].next;
+                prev[level].width += tower[level].width - 1;
+                if self.rightmost[level] == node {
+                    self.rightmost[level] = path[level];
+                }
+            } else {
+                prev[level].width -= 1;
+            }
+        }
+
+        let prev = node.as_ref().prev;
+        match tower[0].next {
+            Some(mut next) => next.as_mut().prev = prev,
+            None => self.tail = prev.filter(|prev| !prev.as_ref().is_head()),
+        }
+
+        self.len -= 1;
+        self.shrink_height();
+        self.finger = Some(*path);
+
+        self.free_node(node).unwrap()
+    }
+
+    /// Creates an unlinked node holding `val` in a cached node of the same level, or else in a
+    /// new allocation, from the arena if the list has one.
+    fn alloc_node(&mut self, val: T, level: usize, prev: Link<T, NUM_LEVELS>) -> NonNull<SkipListNode<T, NUM_LEVELS>> {
+        self.try_alloc_node(val, level, prev).unwrap_or_else(|err| handle_alloc_error(err.layout))
+    }
+
+    /// Like `alloc_node`, but hands `val` back if the allocation fails.
+    fn try_alloc_node(
+        &mut self,
+        val: T,
+        level: usize,
+        prev: Link<T, NUM_LEVELS>,
+    ) -> Result<NonNull<SkipListNode<T, NUM_LEVELS>>, TryInsertError<T>> {
+        let layout = SkipListNode::<T, NUM_LEVELS>::layout(level);
+        let ptr = match (self.free[level], &mut self.arena) {
+            (Some(free), _) => {
+                // SAFETY: cached nodes are owned by the list and hold no element
+                self.free[level] = unsafe { SkipListNode::tower(free) }[0].next;
+                self.free_len -= 1;
+                Some(free.cast())
+            }
+            (None, Some(arena)) => arena.alloc(layout),
+            (None, None) => self.alloc.allocate(layout).ok().map(NonNull::cast),
+        };
+        match ptr {
+            // SAFETY: ptr is fit for a node of this level, and nothing else refers to it
+            Some(ptr) => Ok(unsafe { SkipListNode::init(ptr, Some(val), level, prev, 0) }),
+            None => Err(TryInsertError { item: val, layout }),
+        }
+    }
+
+    /// Frees `node` and returns its value.
+    ///
+    /// SAFETY: `node` must have been allocated by `alloc_node` on this list, or on a list whose
+    /// nodes this one took over, and must not be reachable from the list anymore.
+    unsafe fn free_node(&mut self, node: NonNull<SkipListNode<T, NUM_LEVELS>>) -> Option<T> {
+        let val = (*node.as_ptr()).val.take();
+        let level = node.as_ref().level;
+        if self.free_len < self.free_cap {
+            SkipListNode::tower_mut(node)[0].next = self.free[level];
+            self.free[level] = Some(node);
+            self.free_len += 1;
+        } else {
+            self.dealloc_node(node);
+        }
+        val
+    }
+
+    /// Frees `node` and every node after it on level 0. If dropping an element panics, the
+    /// rest of the chain is still freed while the panic unwinds.
+    ///
+    /// SAFETY: every node of the chain must be fit for `free_node`.
+    unsafe fn free_chain(&mut self, mut node: Link<T, NUM_LEVELS>) {
+        struct Guard<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator> {
+            list: &'a mut SkipList<T, NUM_LEVELS, C, G, A>,
+            rest: Link<T, NUM_LEVELS>,
+        }
+
+        impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> Drop for Guard<'_, T, NUM_LEVELS, C, G, A> {
+            fn drop(&mut self) {
+                // SAFETY: the rest of the chain is as fit for free_node as the whole of it
+                unsafe { self.list.free_chain(self.rest) };
+            }
+        }
+
+        while let Some(curr) = node {
+            // each node is freed after its link is read, and before its element is dropped
+            node = SkipListNode::tower(curr)[0].next;
+            let guard = Guard { list: self, rest: node };
+            drop(guard.list.free_node(curr));
+            mem::forget(guard);
+        }
+    }
+
+    /// Moves the elements of `chain`, a level 0 chain of nodes owned by `other`, into new nodes
+    /// allocated by this list, and returns the new chain. Each node keeps its level, and is
+    /// freed by `other` once its element is moved out.
+    ///
+    /// SAFETY: every node of the chain must be fit for `free_node` on `other`.
+    unsafe fn adopt_chain(&mut self, other: &mut Self, mut chain: Link<T, NUM_LEVELS>) -> Link<T, NUM_LEVELS> {
+        let mut first = None;
+        let mut last: Link<T, NUM_LEVELS> = None;
+        while let Some(node) = chain {
+            chain = SkipListNode::tower(node)[0].next;
+            let level = node.as_ref().level;
+            let new_node = self.alloc_node(other.free_node(node).unwrap(), level, None);
+            match last {
+                Some(last) => SkipListNode::tower_mut(last)[0].next = Some(new_node),
+                None => first = Some(new_node),
+            }
+            last = Some(new_node);
+        }
+        first
+    }
+
+    /// Gives the memory of `node` back, under the same conditions as `free_node`.
+    unsafe fn dealloc_node(&mut self, node: NonNull<SkipListNode<T, NUM_LEVELS>>) {
+        // arena memory stays taken until the arena is dropped
+        if self.arena.is_none() {
+            let layout = SkipListNode::<T, NUM_LEVELS>::layout(node.as_ref().level);
+            ptr::drop_in_place(node.as_ptr());
+            self.alloc.deallocate(node.cast(), layout);
+        }
+    }
+
+    /// Returns the level of the node at position `i` in a list whose towers are evenly spaced:
+    /// every 2nd node reaches level 1, every 4th level 2, and so on.
+    fn balanced_level(i: usize) -> usize {
+        ((i + 1).trailing_zeros() as usize).min(NUM_LEVELS - 1)
+    }
+
+    /// Rebuilds the list bottom-up with evenly spaced towers, like
+    /// [`from_sorted_iter`](SkipList::from_sorted_iter) would, in O(n).
+    ///
+    /// Long runs of inserts and removes can leave the towers unevenly spread and the nodes
+    /// scattered across memory. Every element is moved into a new node allocated in order,
+    /// from a fresh arena if the list uses one, and nodes kept for reuse are freed.
+    pub fn compact(&mut self) {
+        self.realloc_nodes(self.arena.is_some(), |i, _| Self::balanced_level(i));
+    }
+
+    /// Moves every element into a new node, allocated from a fresh arena if `arena` is set or
+    /// boxed otherwise. `new_level` gets the position and level of each node and returns its
+    /// new level.
+    fn realloc_nodes(&mut self, arena: bool, mut new_level: impl FnMut(usize, usize) -> usize) {
+        let mut nodes = self.head.tower()[0].next;
+        let mut new_nodes = Vec::with_capacity(self.len);
+        while let Some(node) = nodes {
+            // SAFETY: a link is Some iff it points to a valid SkipListNode, and the list owns
+            // every node after the head. Each one is freed after its link is read
+            unsafe {
+                let level = node.as_ref().level;
+                nodes = SkipListNode::tower(node)[0].next;
+                new_nodes.push((self.free_node(node).unwrap(), level));
+            }
+        }
+        self.release_free_nodes();
+
+        self.arena = if arena { Some(Arena::new()) } else { None };
+        let new_nodes: Vec<_> = new_nodes
+            .into_iter()
+            .enumerate()
+            .map(|(i, (val, level))| self.alloc_node(val, new_level(i, level), None))
+            .collect();
+        let mut new_nodes = new_nodes.into_iter();
+        // SAFETY: the nodes keep their order, and the old ones were all freed above
+        unsafe { self.relink(|_| new_nodes.next()) };
+    }
+
+    /// Empties the list and frees its nodes, one at a time along level 0, so no list is too
+    /// long to drop. The list is emptied first, so it stays valid if an element's drop panics.
+    fn drop_nodes(&mut self) {
+        let first = self.head.tower()[0].next;
+        self.head.tower_mut().fill(Level { next: None, width: 1 });
+        self.tail = None;
+        self.len = 0;
+        self.height = 1;
+        self.finger = None;
+        self.rightmost = [self.head.0; NUM_LEVELS];
+        if self.arena.is_none() || mem::needs_drop::<T>() {
+            // SAFETY: the list owned every node after the head, and none is reachable from it anymore
+            unsafe { self.free_chain(first) };
+        }
+        if let Some(arena) = &mut self.arena {
+            // the cached nodes go away with the chunks
+            *arena = Arena::new();
+            self.free = [None; NUM_LEVELS];
+            self.free_len = 0;
+        }
+    }
+
+    /// Lowers the height past every level that only holds the head.
+    fn shrink_height(&mut self) {
+        while self.height > 1 && self.head.tower()[self.height - 1].next.is_none() {
+            self.height -= 1;
+        }
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C: Comparator<T>, G: LevelGenerator, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Inserts `item`, following the list's [`DuplicatePolicy`] if an equal element is already
+    /// in the list. Returns the element that was left out of the list, if any, which is the one
+    /// evicted if the list was built [`with_max_len`](SkipList::with_max_len) and was full.
+    pub fn insert(&mut self, item: T) -> Option<T> {
+        self.insert_with_policy(item, self.policy)
+    }
+
+    /// Inserts `item`, following `policy` instead of the list's own policy if an equal element
+    /// is already in the list. Returns the element that was left out of the list, if any.
+    pub fn insert_with_policy(&mut self, item: T, policy: DuplicatePolicy) -> Option<T> {
+        self.try_insert_with_policy(item, policy).unwrap_or_else(|err| handle_alloc_error(err.layout))
+    }
+
+    /// Like [`insert`](SkipList::insert), but hands `item` back in the error instead of aborting
+    /// if the new node can't be allocated. The list is left unchanged in that case.
+    pub fn try_insert(&mut self, item: T) -> Result<Option<T>, TryInsertError<T>> {
+        self.try_insert_with_policy(item, self.policy)
+    }
+
+    /// Like [`insert_with_policy`](SkipList::insert_with_policy), but hands `item` back in the
+    /// error instead of aborting if the new node can't be allocated.
+    pub fn try_insert_with_policy(&mut self, item: T, policy: DuplicatePolicy) -> Result<Option<T>, TryInsertError<T>> {
+        // an item no less than the tail, like a timestamp or a sequence number, goes right after
+        // the last node of every level
+        let (path, duplicate) = match self.last().map(|last| self.cmp.compare(&item, last)) {
+            Some(Ordering::Less) => {
+                let path = self.find_path_by_mut(|cmp, v| cmp.compare(&item, v) != Ordering::Less);
+                // SAFETY: path[0] is the head or a node in the list
+                let duplicate = unsafe { path[0].as_ref() }.val().is_some_and(|v| self.cmp.compare(v, &item) == Ordering::Equal);
+                (path, duplicate)
+            }
+            ord => (self.rightmost, ord == Some(Ordering::Equal)),
+        };
+
+        // SAFETY: path[0] is the head or a node in the list, and it's the last node <= item
+        let last = unsafe { &mut *path[0].as_ptr() };
+        if duplicate {
+            match policy {
+                DuplicatePolicy::Allow => {}
+                DuplicatePolicy::Reject => return Ok(Some(item)),
+                DuplicatePolicy::Replace => return Ok(last.val.replace(item)),
+            }
+        }
+
+        // in a full list, an item that would be evicted right away doesn't need a node
+        let evicted_right_away = match self.max_len {
+            Some((max_len, policy)) if self.len >= max_len => match policy {
+                EvictPolicy::Smallest => self.first().is_some_and(|first| self.cmp.compare(&item, first) == Ordering::Less),
+                EvictPolicy::Largest => self.last().is_some_and(|last| self.cmp.compare(&item, last) != Ordering::Less),
+            },
+            _ => false,
+        };
+        if evicted_right_away {
+            return Ok(Some(item));
+        }
+
+        let new_node_level = self.gen_level();
+        let new_node = self.try_alloc_node(item, new_node_level, Some(path[0]))?;
+        // SAFETY: path holds the last node <= item at every level
+        unsafe { self.link_node(new_node, &path) };
+        Ok(self.evict())
+    }
+
+    /// Inserts `item`, or puts it in place of an equal element already in the list and returns
+    /// that one, like `BTreeSet::replace`. The list's [`DuplicatePolicy`] doesn't apply.
+    pub fn replace(&mut self, item: T) -> Option<T> {
+        self.insert_with_policy(item, DuplicatePolicy::Replace)
+    }
+
+    /// Returns the first element equal to `item`.
+    pub fn find<Q: ?Sized>(&self, item: &Q) -> Option<&T>
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find_node_ref(|v| self.cmp.compare(v, item) == Ordering::Less)
+            .next(0)
+            .and_then(|node| node.val())
+            .filter(|&v| self.cmp.compare(v, item) == Ordering::Equal)
+    }
+
+    /// Returns true if the list contains an element equal to `item`.
+    pub fn contains<Q: ?Sized>(&self, item: &Q) -> bool
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find(item).is_some()
+    }
+
+    /// Returns the smallest element greater than `item`, in O(log n).
+    pub fn next_after<Q: ?Sized>(&self, item: &Q) -> Option<&T>
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find_node_ref(|v| self.cmp.compare(v, item) != Ordering::Greater).next(0).and_then(|node| node.val())
+    }
+
+    /// Returns the largest element less than `item`, in O(log n).
+    pub fn prev_before<Q: ?Sized>(&self, item: &Q) -> Option<&T>
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find_node_ref(|v| self.cmp.compare(v, item) == Ordering::Less).val()
+    }
+
+    /// Removes the first element equal to `item` from the list and returns it.
+    pub fn remove<Q: ?Sized>(&mut self, item: &Q) -> Option<T>
+    where
+        C: Comparator<T, Q>,
+    {
+        let path = self.find_path_by_mut(|cmp, v| cmp.compare(v, item) == Ordering::Less);
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        let target = unsafe { SkipListNode::tower(path[0])[0].next? };
+        if unsafe { target.as_ref().val() }.is_none_or(|v| self.cmp.compare(v, item) != Ordering::Equal) {
+            return None;
+        }
+
+        // SAFETY: target is the first node >= item, so path[level] is its predecessor at every level it occupies
+        Some(unsafe { self.unlink(target, &path) })
+    }
+
+    /// Removes the first element equal to `item` from the list and returns it, like
+    /// `HashSet::take`. This is the same as [`remove`](SkipList::remove): the stored element
+    /// is handed back, not the probe.
+    pub fn take<Q: ?Sized>(&mut self, item: &Q) -> Option<T>
+    where
+        C: Comparator<T, Q>,
+    {
+        self.remove(item)
+    }
+
+    /// Builds a list from an iterator yielding its items in sorted order, in O(n).
+    ///
+    /// Instead of random levels, the list is built bottom-up with evenly spaced towers: every
+    /// 2nd node reaches level 1, every 4th level 2, and so on, giving an ideally balanced list.
+    /// If the items aren't sorted, the list won't be either.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self
+    where
+        C: Default,
+        G: Default,
+        A: Default,
+    {
+        let mut list = SkipList::with_level_generator_in(C::default(), G::default(), A::default());
+        let mut path = [list.head.0; NUM_LEVELS];
+        for (i, item) in iter.into_iter().enumerate() {
+            debug_assert!(
+                list.last().is_none_or(|last| list.cmp.compare(last, &item) != Ordering::Greater),
+                "from_sorted_iter: items are not sorted"
+            );
+
+            let level = Self::balanced_level(i);
+            // SAFETY: path holds the last node at every level, and items come in sorted order
+            unsafe { list.push_back_with_level(item, level, &mut path) };
+        }
+        list
+    }
+
+    /// Splits the list in two at `key`. Everything greater than or equal to `key` is moved
+    /// into the returned list, and everything less than `key` stays in this one.
+    ///
+    /// Nodes aren't moved or reallocated: the links at every level are cut at the split point
+    /// and handed over to the new list's head.
+    pub fn split_off(&mut self, key: &T) -> Self
+    where
+        C: Clone,
+        G: Clone,
+        A: Clone,
+    {
+        let path = self.find_path_by_mut(|cmp, v| cmp.compare(v, key) == Ordering::Less);
+
+        // SAFETY: path holds the last node before the split point at every level
+        let offsets = unsafe { Self::path_offsets(&path, NUM_LEVELS) };
+
+        let mut other = self.empty_like(self.gen.clone());
+        // the nodes moved to other may live in any of this list's chunks
+        other.arena = self.arena.as_ref().map(Arena::share);
+        for (level, &prev) in path.iter().enumerate() {
+            // SAFETY: path only holds the head or nodes in this list
+            let prev = unsafe { SkipListNode::tower_mut(prev) };
+            other.head.tower_mut()[level].next = prev[level].next.take();
+            other.head.tower_mut()[level].width = prev[level].width - offsets[level];
+            prev[level].width = offsets[level] + 1;
+            if other.head.tower()[level].next.is_some() {
+                other.rightmost[level] = self.rightmost[level];
+            }
+        }
+        self.rightmost = path;
+
+        if let Some(mut first) = other.head.tower()[0].next {
+            // SAFETY: first is a valid node that now belongs to other
+            unsafe { first.as_mut().prev = Some(other.head.0) };
+            other.tail = self.tail;
+            // SAFETY: path[0] is the head or a node in this list
+            self.tail = Some(path[0]).filter(|prev| !unsafe { prev.as_ref() }.is_head());
+
+            // the elements kept are the steps from the head to path[0]: along the top level to
+            // path[top], which is most often the head itself, then offsets[top] more
+            let top = NUM_LEVELS - 1;
+            let mut rank = offsets[top];
+            let mut node = self.head.0;
+            while node != path[top] {
+                // SAFETY: path[top] is the head or reachable from it on the top level
+                let level = unsafe { SkipListNode::tower(node) }[top];
+                rank += level.width;
+                node = level.next.unwrap();
+            }
+            other.len = self.len - rank;
+            self.len = rank;
+            other.height = self.height;
+            other.shrink_height();
+            self.shrink_height();
+        }
+
+        other
+    }
+
+    /// Moves every element of `other` into this list.
+    ///
+    /// The level 0 chains of both lists are zipped together in a single O(n + m) merge and the
+    /// towers are rebuilt along the way. Nodes keep their levels and are relinked as they are,
+    /// without cloning or reallocating elements, unless only one of the lists uses an arena.
+    /// Equal elements from this list come before the ones from `other`. This makes it the way
+    /// to combine lists built separately, say one per thread, into one.
+    ///
+    /// Boxed nodes are only relinked as they are if `A` is zero-sized, like [`Global`]: such an
+    /// allocator has no state telling its instances apart, so `other`'s nodes can be freed
+    /// through this list's instance. With any other allocator, `other` may have allocated its
+    /// nodes from a different instance, so its elements are moved into nodes allocated by this
+    /// list, keeping their levels.
+    #[doc(alias = "merge_from")]
+    pub fn append(&mut self, mut other: Self) {
+        other.release_free_nodes();
+        if let Some(arena) = &mut self.arena {
+            if other.arena.is_none() {
+                other.realloc_nodes(true, |_, level| level);
+            }
+            arena.adopt(other.arena.take().unwrap());
+        }
+
+        let mut left = self.head.tower_mut()[0].next.take();
+        let mut right = other.head.tower_mut()[0].next.take();
+        for level in other.head.tower_mut() {
+            level.next = None;
+        }
+        other.tail = None;
+        other.len = 0;
+        if self.arena.is_none() && (other.arena.is_some() || mem::size_of::<A>() != 0) {
+            // SAFETY: right is other's whole chain, which other no longer links to
+            right = unsafe { self.adopt_chain(&mut other, right) };
+        }
+
+        // SAFETY: every node of both chains is yielded exactly once, after reading its level 0 link,
+        // and every link that is Some points to a valid SkipListNode
+        let merged = |cmp: &C| unsafe {
+            let take_left = match (left, right) {
+                (Some(l), Some(r)) => {
+                    cmp.compare(r.as_ref().val()?, l.as_ref().val()?) != Ordering::Less
+                }
+                (l, _) => l.is_some(),
+            };
+            let cursor = if take_left { &mut left } else { &mut right };
+            let node = (*cursor)?;
+            *cursor = SkipListNode::tower(node)[0].next;
+            Some(node)
+        };
+
+        // SAFETY: merged yields every node of both lists in sorted order, and other no longer owns any of them
+        unsafe { self.relink(merged) };
+    }
+
+    /// Returns the number of elements equal to `item`, in O(log n + k) for k matches.
+    pub fn count(&self, item: &T) -> usize {
+        let node = self.find_node_ref(|v| self.cmp.compare(v, item) == Ordering::Less);
+        core::iter::successors(node.next(0), |node| node.next(0))
+            .take_while(|node| node.val().is_some_and(|v| self.cmp.compare(v, item) == Ordering::Equal))
+            .count()
+    }
+
+    /// Removes every element equal to `item` and returns how many were removed.
+    ///
+    /// The equal run is found with a single search and unlinked front to back, since the
+    /// predecessors of each removed node are also the predecessors of the next one.
+    pub fn remove_all(&mut self, item: &T) -> usize {
+        let path = self.find_path_by_mut(|cmp, v| cmp.compare(v, item) == Ordering::Less);
+
+        let mut removed = 0;
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        while let Some(next) = unsafe { SkipListNode::tower(path[0])[0].next } {
+            if unsafe { next.as_ref().val() }.is_none_or(|v| self.cmp.compare(v, item) != Ordering::Equal) {
+                break;
+            }
+
+            // SAFETY: next is the first node >= item, so path[level] is its predecessor at every level it occupies
+            unsafe { self.unlink(next, &path) };
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Removes every element within `range` and returns how many were removed.
+    ///
+    /// Both ends of the range are found with one search each, and the links at every level are
+    /// spliced over the removed run, which is then freed as a single chain.
+    pub fn remove_range<R: RangeBounds<T>>(&mut self, range: R) -> usize {
+        let start = self.find_path_by_mut(|cmp, v| before_start(cmp, v, range.start_bound()));
+        // SAFETY: start[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        let first = match unsafe { SkipListNode::tower(start[0])[0].next } {
+            Some(first) if unsafe { first.as_ref() }.val().is_some_and(|v| through_end(&self.cmp, v, range.end_bound())) => first,
+            _ => return 0,
+        };
+        let end = self.find_path_by_mut(|cmp, v| through_end(cmp, v, range.end_bound()));
+
+        // SAFETY: both paths hold the last node before some position at every level, and the
+        // range isn't empty so end[level] is start[level] or comes after it
+        unsafe {
+            let start_offsets = Self::path_offsets(&start, NUM_LEVELS);
+            let end_offsets = Self::path_offsets(&end, NUM_LEVELS);
+
+            let mut removed = 1;
+            let mut node = first;
+            while node != end[0] {
+                node = SkipListNode::tower(node)[0].next.unwrap();
+                removed += 1;
+            }
+
+            for level in 0..NUM_LEVELS {
+                let prev = SkipListNode::tower_mut(start[level]);
+                if start[level] == end[level] {
+                    prev[level].width -= removed;
+                } else {
+                    let last = SkipListNode::tower(end[level]);
+                    prev[level].next = last[level].next;
+                    prev[level].width = start_offsets[level] + last[level].width - end_offsets[level];
+                    if self.rightmost[level] == end[level] {
+                        self.rightmost[level] = start[level];
+                    }
+                }
+            }
+
+            match SkipListNode::tower(end[0])[0].next {
+                Some(mut next) => next.as_mut().prev = Some(start[0]),
+                None => self.tail = Some(start[0]).filter(|prev| !prev.as_ref().is_head()),
+            }
+            self.len -= removed;
+            self.shrink_height();
+            // the search for the end left the finger on the run being freed
+            self.finger = Some(start);
+
+            let mut node = Some(first);
+            while let Some(curr) = node {
+                node = SkipListNode::tower(curr)[0].next.filter(|_| curr != end[0]);
+                drop(self.free_node(curr));
+            }
+
+            removed
+        }
+    }
+
+    /// Inserts `item` unless an equal element is already in the list, in which case `item` is
+    /// handed back. Duplicates are detected during the same search that finds the insert position.
+    pub fn insert_unique(&mut self, item: T) -> Result<(), T> {
+        let path = self.find_path_by_mut(|cmp, v| cmp.compare(v, &item) == Ordering::Less);
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        let next = unsafe { SkipListNode::tower(path[0])[0].next };
+        if next.and_then(|next| unsafe { next.as_ref() }.val()).is_some_and(|v| self.cmp.compare(v, &item) == Ordering::Equal) {
+            return Err(item);
+        }
+
+        let level = self.gen_level();
+        // SAFETY: path holds the last node < item at every level, and there's no node equal to item
+        unsafe { self.link(item, level, &path) };
+        Ok(())
+    }
+
+    /// Returns the first element equal to `probe`, or inserts the element built by `f` if there
+    /// is none and returns that. Both cases take a single search: the new element is linked in
+    /// at the position the lookup already found.
+    ///
+    /// The element built by `f` must be equal to `probe`.
+    pub fn get_or_insert_with(&mut self, probe: &T, f: impl FnOnce() -> T) -> &T {
+        let path = self.find_path_by_mut(|cmp, v| cmp.compare(v, probe) == Ordering::Less);
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        let next = unsafe { SkipListNode::tower(path[0])[0].next };
+        let found = next.filter(|next| {
+            unsafe { next.as_ref() }.val().is_some_and(|v| self.cmp.compare(v, probe) == Ordering::Equal)
+        });
+
+        let node = match found {
+            Some(node) => node,
+            None => {
+                let item = f();
+                debug_assert!(
+                    self.cmp.compare(&item, probe) == Ordering::Equal,
+                    "get_or_insert_with: the new element isn't equal to the probe"
+                );
+                let level = self.gen_level();
+                // SAFETY: path holds the last node < probe at every level, and item is equal to probe
+                unsafe { self.link(item, level, &path) }
+            }
+        };
+        // SAFETY: node is a non-head node in the list, so it holds a value
+        unsafe { node.as_ref() }.val().unwrap()
+    }
+
+    /// Returns the number of elements strictly less than `item`, in O(log n). This is the
+    /// index `item` has, or would have if it were inserted before any equal elements.
+    pub fn rank(&self, item: &T) -> usize {
+        self.rank_while(|v| self.cmp.compare(v, item) == Ordering::Less)
+    }
+
+    /// Returns the number of elements within `range`, in O(log n) no matter how many there are.
+    pub fn range_count<R: RangeBounds<T>>(&self, range: R) -> usize {
+        let before = self.rank_while(|v| before_start(&self.cmp, v, range.start_bound()));
+        let through = self.rank_while(|v| through_end(&self.cmp, v, range.end_bound()));
+        through.saturating_sub(before)
+    }
+
+    /// Returns the number of leading elements for which `f` returns true, summing the widths
+    /// of the links taken by the search.
+    fn rank_while(&self, mut f: impl FnMut(&T) -> bool) -> usize {
+        let mut rank = 0;
+        let mut node = self.head.node();
+        for level in (0..self.height).rev() {
+            node = node.proceed_at_level_while(level, self.prefetches(), |curr, next| {
+                let taken = next.val().is_some_and(&mut f);
+                if taken {
+                    rank += curr.tower()[level].width;
+                }
+                taken
+            });
+        }
+        rank
+    }
+
+    /// Returns a cursor on the first element above `bound`: the first element `>= x` for
+    /// `Included(x)`, `> x` for `Excluded(x)`, or the first element for `Unbounded`.
+    /// The cursor is on the ghost if there is no such element.
+    pub fn lower_bound(&self, bound: Bound<&T>) -> Cursor<'_, T, NUM_LEVELS, C, G, A> {
+        let node = self.find_node_ref(|v| before_start(&self.cmp, v, bound));
+        Cursor::new(self, node.next(0))
+    }
+
+    /// Returns a cursor on the last element below `bound`: the last element `<= x` for
+    /// `Included(x)`, `< x` for `Excluded(x)`, or the last element for `Unbounded`.
+    /// The cursor is on the ghost if there is no such element.
+    pub fn upper_bound(&self, bound: Bound<&T>) -> Cursor<'_, T, NUM_LEVELS, C, G, A> {
+        let node = self.find_node_ref(|v| through_end(&self.cmp, v, bound));
+        Cursor::new(self, Some(node).filter(|node| !node.is_head()))
+    }
+
+    /// Returns a cursor on the first element for which `pred` returns false, or on the ghost if
+    /// it returns true for every element, like `slice::partition_point` does for sorted data.
+    ///
+    /// `pred` must hold for a prefix of the list and fail for the rest, as `|x| x < y` does for
+    /// any `y`. The flip is found by a descent along the towers in O(log n), so the list can be
+    /// split on a condition that isn't written as a comparison against an element.
+    pub fn partition_point(&self, pred: impl FnMut(&T) -> bool) -> Cursor<'_, T, NUM_LEVELS, C, G, A> {
+        Cursor::new(self, self.find_node_ref(pred).next(0))
+    }
+
+    /// Returns a cursor on the first element that is not less than `key`, or on the ghost if there is none.
+    pub fn cursor_at(&mut self, key: &T) -> CursorMut<'_, T, NUM_LEVELS, C, G, A> {
+        let path = self.find_path_by_mut(|cmp, v| cmp.compare(v, key) == Ordering::Less);
+        // SAFETY: path[0] is the head or a node in the list
+        let current = unsafe { SkipListNode::tower(path[0]) }[0].next;
+        // SAFETY: path holds the last node < key at every level, so it also holds current's predecessors
+        unsafe { CursorMut::new(self, current, path) }
+    }
+
+    /// Returns an iterator over the elements of the list that fall within `range`, in sorted order.
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T, NUM_LEVELS> {
+        let front = self.find_node_ref(|v| before_start(&self.cmp, v, range.start_bound())).next(0);
+        let back = self.find_node_ref(|v| through_end(&self.cmp, v, range.end_bound()));
+
+        match front {
+            Some(front) if front.val().is_some_and(|v| through_end(&self.cmp, v, range.end_bound())) => {
+                Range::new(Some(front), Some(back))
+            }
+            _ => Range::new(None, None),
+        }
+    }
+
+    /// Checks every invariant the list's links rely on, and returns the first one that's broken.
+    ///
+    /// Every level has to be sorted, and be a subsequence of level 0 made of the nodes reaching
+    /// it. Every prev link has to lead back to the node before, every width has to match the
+    /// steps its link spans, and the length, tail and height have to match level 0. This is
+    /// for tests and debugging, of the list's own unsafe code or code extending it: it takes
+    /// O(n log n) and allocates. A list only changed through its safe API always passes.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        use alloc::collections::BTreeMap;
+
+        type NodePtr<T, const NUM_LEVELS: usize> = *const SkipListNode<T, NUM_LEVELS>;
+        let head: NodePtr<T, NUM_LEVELS> = self.head.0.as_ptr();
+        // the position of every node reachable on level 0, counting the head as 0
+        let mut ranks = BTreeMap::new();
+        ranks.insert(head, 0);
+
+        let mut prev = self.head.node();
+        let mut found: usize = 0;
+        while let Some(node) = prev.next(0) {
+            let index = found;
+            found += 1;
+            if ranks.insert(node.ptr().as_ptr() as NodePtr<T, NUM_LEVELS>, found).is_some() {
+                return Err(InvariantError::NotSubsequence { level: 0, index: index.checked_sub(1) });
+            }
+            if node.prev != Some(prev.ptr()) {
+                return Err(InvariantError::BrokenPrev { index });
+            }
+            if let (Some(a), Some(b)) = (prev.val(), node.val()) {
+                if self.cmp.compare(a, b) == Ordering::Greater {
+                    return Err(InvariantError::Unsorted { level: 0, index });
+                }
+            }
+            if node.level >= self.height {
+                return Err(InvariantError::Height);
+            }
+            prev = node;
+        }
+        if found != self.len {
+            return Err(InvariantError::Len { expected: self.len, found });
+        }
+        if !ptr::eq(self.tail.map_or(head, |tail| tail.as_ptr()), prev.ptr().as_ptr()) {
+            return Err(InvariantError::Tail);
+        }
+        if self.height > 1 && self.head.tower()[self.height - 1].next.is_none() {
+            return Err(InvariantError::Height);
+        }
+
+        // the index of the element at a rank, or None for the head
+        let index = |rank: usize| rank.checked_sub(1);
+        for level in 0..NUM_LEVELS {
+            let mut prev = self.head.node();
+            let mut prev_rank = 0;
+            loop {
+                let width = prev.tower()[level].width;
+                let Some(next) = prev.tower()[level].next else {
+                    if prev_rank + width != self.len + 1 {
+                        return Err(InvariantError::Width { level, index: index(prev_rank) });
+                    }
+                    break;
+                };
+                // only dereference nodes known to be in the list, and read towers within bounds
+                let rank = match ranks.get(&(next.as_ptr() as NodePtr<T, NUM_LEVELS>)) {
+                    // SAFETY: next is a node reachable on level 0
+                    Some(&rank) if rank > prev_rank && unsafe { next.as_ref() }.level >= level => rank,
+                    _ => return Err(InvariantError::NotSubsequence { level, index: index(prev_rank) }),
+                };
+                // SAFETY: as above
+                let next = unsafe { NodeRef::new(next) };
+                if prev_rank + width != rank {
+                    return Err(InvariantError::Width { level, index: index(prev_rank) });
+                }
+                if let (Some(a), Some(b)) = (prev.val(), next.val()) {
+                    if self.cmp.compare(a, b) == Ordering::Greater {
+                        return Err(InvariantError::Unsorted { level, index: rank - 1 });
+                    }
+                }
+                prev = next;
+                prev_rank = rank;
+            }
+            if self.rightmost[level] != prev.ptr() {
+                return Err(InvariantError::Rightmost { level });
+            }
+        }
+
+        if let Some(finger) = self.finger {
+            let mut lower_rank = None;
+            for (level, node) in finger.iter().enumerate() {
+                // every node of the finger is on its level, and no later than its level 0 node
+                let rank = match ranks.get(&(node.as_ptr() as NodePtr<T, NUM_LEVELS>)) {
+                    // SAFETY: the node is the head or reachable on level 0
+                    Some(&rank) if unsafe { node.as_ref() }.level >= level && lower_rank.is_none_or(|lower| rank <= lower) => rank,
+                    _ => return Err(InvariantError::Finger { level }),
+                };
+                lower_rank.get_or_insert(rank);
+                // and the next node of its level comes after the position
+                // SAFETY: as above
+                let next = unsafe { SkipListNode::tower(*node) }[level].next;
+                if next.is_some_and(|next| ranks[&(next.as_ptr() as NodePtr<T, NUM_LEVELS>)] <= lower_rank.unwrap()) {
+                    return Err(InvariantError::Finger { level });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `find_path_mut`, but `f` is handed each element along with the list's comparator.
+    ///
+    /// The search starts from the finger, the path the last one ended on, unless `f` is false
+    /// for the finger's node, and from the head otherwise. From the finger it climbs to the
+    /// lowest level whose next node `f` is false for, and descends from there, so a target d
+    /// elements past the finger takes O(log d) steps on average instead of O(log n).
+    fn find_path_by_mut(&mut self, mut f: impl FnMut(&C, &T) -> bool) -> [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS] {
+        let head = self.head.0;
+        let prefetch = self.prefetches();
+        let cmp = &self.cmp;
+        let mut passes = |node: NonNull<SkipListNode<T, NUM_LEVELS>>| {
+            // SAFETY: the finger and every link only point to the head or valid nodes of this list
+            unsafe { node.as_ref() }.val().is_some_and(|v| f(cmp, v))
+        };
+        let mut path = match self.finger {
+            // SAFETY: the finger only holds the head or nodes of this list
+            Some(finger) if unsafe { finger[0].as_ref() }.is_head() || passes(finger[0]) => finger,
+            _ => [head; NUM_LEVELS],
+        };
+
+        // every node at a level above `top` after its path node is past the target too, since
+        // it's at level `top` as well and comes no earlier than the next node there
+        let mut top = 0;
+        // SAFETY: path only holds the head or nodes of this list
+        while top + 1 < self.height && unsafe { SkipListNode::tower(path[top]) }[top].next.is_some_and(&mut passes) {
+            top += 1;
+        }
+
+        // like in `extend`, a level's path node only stays valid as a start until the search
+        // moves at a level above it, which takes it past the old target
+        let mut node = path[top];
+        let mut moved = false;
+        for level in (0..=top).rev() {
+            if !moved {
+                node = path[level];
+            }
+            // SAFETY: node is the head or a node of this list, and a link is Some iff it points to a valid SkipListNode
+            while let Some(next) = unsafe { SkipListNode::tower(node) }[level].next {
+                if prefetch {
+                    // SAFETY: as above
+                    unsafe { NodeRef::new(next) }.prefetch_next(level);
+                }
+                if !passes(next) {
+                    break;
+                }
+                node = next;
+                moved = true;
+            }
+            path[level] = node;
+        }
+        self.finger = Some(path);
+        path
+    }
+}
+
+impl<T: Clone, const NUM_LEVELS: usize, C: Clone, G: Clone, A: Allocator + Clone> Clone for SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Clones the list node by node, giving every node of the clone the same level as the
+    /// original so both lists have identical search characteristics.
+    fn clone(&self) -> Self {
+        let mut list = self.empty_like(self.gen.clone());
+
+        let mut path = [list.head.0; NUM_LEVELS];
+        let mut node = self.head.next(0);
+        while let Some(curr) = node {
+            if let Some(val) = curr.val() {
+                // SAFETY: path holds the last node at every level, and nodes are copied in sorted order
+                unsafe { list.push_back_with_level(val.clone(), curr.level, &mut path) };
+            }
+            node = curr.next(0);
+        }
+        list
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C: Default, G: Default, A: Allocator + Default> Default for SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Creates an empty list. The head is heap-allocated since nodes keep pointers to it, so
+    /// this can't be a `const fn`; use a `OnceLock` or `LazyLock` to put a list in a static.
+    fn default() -> Self {
+        SkipList::with_level_generator_in(C::default(), G::default(), A::default())
+    }
+}
+
+impl<T: PartialEq, const NUM_LEVELS: usize, C, G, A: Allocator> PartialEq for SkipList<T, NUM_LEVELS, C, G, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, const NUM_LEVELS: usize, C, G, A: Allocator> Eq for SkipList<T, NUM_LEVELS, C, G, A> {}
+
+impl<T: PartialOrd, const NUM_LEVELS: usize, C, G, A: Allocator> PartialOrd for SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Compares the elements of both lists lexicographically, in sorted order.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord, const NUM_LEVELS: usize, C, G, A: Allocator> Ord for SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Compares the elements of both lists lexicographically, in sorted order.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Hash, const NUM_LEVELS: usize, C, G, A: Allocator> Hash for SkipList<T, NUM_LEVELS, C, G, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for item in self.iter() {
+            item.hash(state);
+        }
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C: Comparator<T>, G: LevelGenerator, A: Allocator> Extend<T> for SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Inserts every item of `iter`.
+    ///
+    /// The items are sorted first and then merged into the list in a single forward pass,
+    /// so each search resumes from where the previous one stopped instead of the head.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut items: Vec<T> = iter.into_iter().collect();
+        items.sort_by(|a, b| self.cmp.compare(a, b));
+
+        let mut path = [self.head.0; NUM_LEVELS];
+        for item in items {
+            // once the search moves forward at some level, it's past the previous path at every level below
+            let mut moved = false;
+            let mut node = path[self.height - 1];
+            for level in (0..self.height).rev() {
+                if !moved {
+                    node = path[level];
+                }
+
+                // SAFETY: node is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+                unsafe {
+                    while let Some(next) = SkipListNode::tower(node)[level].next {
+                        if next.as_ref().val().is_some_and(|v| self.cmp.compare(&item, v) == Ordering::Less) {
+                            break;
+                        }
+                        node = next;
+                        moved = true;
+                    }
+                }
+                path[level] = node;
+            }
+
+            // SAFETY: path[0] is the head or a node in the list, and it's the last node <= item
+            let last = unsafe { &mut *path[0].as_ptr() };
+            if last.val().is_some_and(|v| self.cmp.compare(v, &item) == Ordering::Equal) {
+                match self.policy {
+                    DuplicatePolicy::Allow => {}
+                    DuplicatePolicy::Reject => continue,
+                    DuplicatePolicy::Replace => {
+                        last.val = Some(item);
+                        continue;
+                    }
+                }
+            }
+
+            let level = self.gen_level();
+            // SAFETY: path holds the last node <= item at every level
+            let new_node = unsafe { self.link(item, level, &path) };
+            path[..=level].fill(new_node);
+        }
+        while self.evict().is_some() {}
+    }
+}
+
+impl<'a, T: Copy + 'a, const NUM_LEVELS: usize, C: Comparator<T>, G: LevelGenerator, A: Allocator> Extend<&'a T> for SkipList<T, NUM_LEVELS, C, G, A> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Biased, Deterministic, LevelGenerator, OrdComparator, Range, SkipList, SkipListBy, SkipListByKey, SkipListNode};
+    use std::ops::Bound;
+
+    #[test]
+    fn insert_and_lookup_same_order() {
+        let mut l = SkipList::<usize, 8>::new();
+        for i in 0..10 {
+            l.insert(i);
+        }
+
+        for i in 0..10 {
+            assert!(l.contains(&i));
+        }
+    }
+
+    #[test]
+    fn insert_and_lookup_different_order() {
+        let mut l = SkipList::<i32, 9>::new();
+        let mut nums = Vec::new();
+        for _ in 0..200 {
+            let i = fastrand::i32(..);
+            l.insert(i);
+            nums.push(i);
+        }
+
+        fastrand::shuffle(nums.as_mut());
+
+        for i in nums.into_iter() {
+            assert!(l.contains(&i));
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let mut l = SkipList::<usize, 8>::new();
+        let mut nums: Vec<usize> = (0..100).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+
+        for i in (0..100).step_by(2) {
+            assert_eq!(l.remove(&i), Some(i));
+        }
+        assert_eq!(l.remove(&0), None);
+        assert_eq!(l.remove(&100), None);
+        assert_eq!(l.len(), 50);
+
+        for i in 0..100 {
+            assert_eq!(l.contains(&i), i % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn find_by() {
+        #[derive(Debug, PartialEq)]
+        struct User {
+            id: u32,
+            name: String,
+        }
+
+        let mut l = SkipList::<User, 8, _>::with_key(|u: &User| u.id);
+        for id in (0..100).rev().step_by(3) {
+            l.insert(User { id, name: format!("user {}", id) });
+        }
+        assert_eq!(l.find_by(|u| u.id.cmp(&42)).map(|u| u.name.as_str()), Some("user 42"));
+        assert_eq!(l.find_by(|u| u.id.cmp(&43)), None);
+        assert!(l.contains_by(|u| u.id.cmp(&0)));
+        assert!(!l.contains_by(|u| u.id.cmp(&100)));
+        assert!(!SkipList::<User, 8, _>::with_key(|u: &User| u.id).contains_by(|u| u.id.cmp(&0)));
+    }
+
+    #[test]
+    fn next_after_and_prev_before() {
+        let l = SkipList::<u32, 8>::from_sorted_iter([1, 3, 3, 5, 9].iter().copied());
+        assert_eq!(l.next_after(&0), Some(&1));
+        assert_eq!(l.next_after(&1), Some(&3));
+        assert_eq!(l.next_after(&3), Some(&5));
+        assert_eq!(l.next_after(&4), Some(&5));
+        assert_eq!(l.next_after(&9), None);
+
+        assert_eq!(l.prev_before(&9), Some(&5));
+        assert_eq!(l.prev_before(&5), Some(&3));
+        assert_eq!(l.prev_before(&4), Some(&3));
+        assert_eq!(l.prev_before(&1), None);
+        assert_eq!(l.prev_before(&100), Some(&9));
+        assert_eq!(SkipList::<u32, 8>::new().next_after(&0), None);
+    }
+
+    #[test]
+    fn remove_duplicates() {
+        let mut l = SkipList::<i32, 4>::new();
+        for i in [3, 1, 3, 2, 3] {
+            l.insert(i);
+        }
+
+        for _ in 0..3 {
+            assert_eq!(l.remove(&3), Some(3));
+        }
+        assert_eq!(l.remove(&3), None);
+        assert_eq!(l.len(), 2);
+        assert!(l.contains(&1));
+        assert!(l.contains(&2));
+    }
+
+    #[test]
+    fn pop_front() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..100).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+
+        for i in 0..100 {
+            assert_eq!(l.pop_front(), Some(i));
+            assert!(!l.contains(&i));
+        }
+        assert_eq!(l.pop_front(), None);
+        assert_eq!(l.len(), 0);
+
+        l.insert(7);
+        assert_eq!(l.pop_front(), Some(7));
+    }
+
+    #[test]
+    fn last_and_pop_back() {
+        let mut l = SkipList::<i32, 8>::new();
+        assert_eq!(l.last(), None);
+        let mut nums: Vec<i32> = (0..100).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+        assert_eq!(l.last(), Some(&99));
+
+        assert_eq!(l.remove(&99), Some(99));
+        assert_eq!(l.last(), Some(&98));
+
+        for i in (50..99).rev() {
+            assert_eq!(l.pop_back(), Some(i));
+            assert_eq!(l.last(), Some(&(i - 1)));
+        }
+        assert_eq!(l.pop_front(), Some(0));
+        for i in (1..50).rev() {
+            assert_eq!(l.pop_back(), Some(i));
+        }
+        assert_eq!(l.last(), None);
+        assert_eq!(l.pop_back(), None);
+
+        l.insert(3);
+        l.insert(1);
+        assert_eq!(l.last(), Some(&3));
+    }
+
+    #[test]
+    fn len_tracks_mutations() {
+        let mut l = SkipList::<i32, 8>::new();
+        assert!(l.is_empty());
+        for i in 0..10 {
+            l.insert(i);
+            assert_eq!(l.len(), i as usize + 1);
+        }
+        assert!(!l.is_empty());
+
+        assert_eq!(l.remove(&42), None);
+        assert_eq!(l.len(), 10);
+        l.remove(&5);
+        l.pop_front();
+        l.pop_back();
+        assert_eq!(l.len(), 7);
+
+        while l.pop_front().is_some() {}
+        assert_eq!(l.len(), 0);
+        assert!(l.is_empty());
+    }
+
+    #[test]
+    fn clear() {
+        let mut l = SkipList::<String, 8>::new();
+        for i in 0..50 {
+            l.insert(i.to_string());
+        }
+
+        l.clear();
+        assert!(l.is_empty());
+        assert_eq!(l.last(), None);
+        assert_eq!(l.pop_front(), None);
+        assert!(!l.contains("7"));
+
+        l.insert("b".to_string());
+        l.insert("a".to_string());
+        assert_eq!(l.len(), 2);
+        assert_eq!(l.pop_front().as_deref(), Some("a"));
+        assert_eq!(l.last().map(String::as_str), Some("b"));
+    }
+
+    #[test]
+    fn first_and_last_mut() {
+        let mut l = SkipList::<(i32, i32), 8>::new();
+        assert_eq!(l.first(), None);
+        assert_eq!(l.first_mut(), None);
+        assert_eq!(l.last_mut(), None);
+
+        for i in [5, 2, 8, 1] {
+            l.insert((i, 0));
+        }
+        assert_eq!(l.first(), Some(&(1, 0)));
+
+        l.first_mut().unwrap().1 = 10;
+        l.last_mut().unwrap().1 = 80;
+        assert_eq!(l.pop_front(), Some((1, 10)));
+        assert_eq!(l.pop_back(), Some((8, 80)));
+        assert_eq!(l.first(), Some(&(2, 0)));
+    }
+
+    #[test]
+    fn iter() {
+        let mut l = SkipList::<i32, 8>::new();
+        assert_eq!(l.iter().next(), None);
+
+        let mut nums: Vec<i32> = (0..100).map(|_| fastrand::i32(-50..50)).collect();
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+        nums.sort();
+
+        let mut iter = l.iter();
+        assert_eq!(iter.len(), 100);
+        iter.next();
+        assert_eq!(iter.size_hint(), (99, Some(99)));
+
+        assert_eq!(l.iter().copied().collect::<Vec<_>>(), nums);
+        assert_eq!((&l).into_iter().count(), 100);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut l = SkipList::<String, 8>::new();
+        let mut nums: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        fastrand::shuffle(nums.as_mut());
+        for i in nums.iter() {
+            l.insert(i.clone());
+        }
+        nums.sort();
+
+        let mut iter = l.into_iter();
+        assert_eq!(iter.len(), 100);
+        assert_eq!(iter.next(), Some(nums[0].clone()));
+        assert_eq!(iter.collect::<Vec<_>>(), nums[1..]);
+
+        // dropping a partially consumed iterator frees the remaining nodes
+        let mut l = SkipList::<String, 8>::new();
+        for i in nums.into_iter() {
+            l.insert(i);
+        }
+        let mut iter = l.into_iter();
+        iter.next();
+        drop(iter);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut l = SkipList::<(i32, usize), 8>::new();
+        for i in [4, 1, 3, 0, 2] {
+            l.insert((i, 0));
+        }
+
+        let mut iter = l.iter_mut();
+        assert_eq!(iter.len(), 5);
+        for (i, (_, payload)) in iter.by_ref().take(2).enumerate() {
+            *payload = i + 10;
+        }
+        assert_eq!(iter.len(), 3);
+        for (_, payload) in &mut l {
+            *payload += 1;
+        }
+
+        let items: Vec<_> = l.iter().copied().collect();
+        assert_eq!(items, [(0, 11), (1, 12), (2, 1), (3, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn double_ended() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..100).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+
+        assert_eq!(l.iter().rev().copied().collect::<Vec<_>>(), (0..100).rev().collect::<Vec<_>>());
+
+        let mut iter = l.iter();
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.next_back(), Some(&99));
+        assert_eq!(iter.len(), 98);
+        assert_eq!(iter.by_ref().rev().take(97).count(), 97);
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        for v in l.iter_mut().rev().take(10) {
+            *v += 100;
+        }
+        assert_eq!(l.last(), Some(&199));
+
+        let mut iter = l.into_iter();
+        assert_eq!(iter.next_back(), Some(199));
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.rev().collect::<Vec<_>>().len(), 98);
+    }
+
+    #[test]
+    fn range() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..50).map(|i| i * 2).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+
+        let collect = |iter: Range<'_, i32, 8>| iter.copied().collect::<Vec<_>>();
+        assert_eq!(collect(l.range(10..20)), [10, 12, 14, 16, 18]);
+        assert_eq!(collect(l.range(9..=20)), [10, 12, 14, 16, 18, 20]);
+        assert_eq!(collect(l.range((Bound::Excluded(10), Bound::Excluded(16)))), [12, 14]);
+        assert_eq!(collect(l.range(..5)), [0, 2, 4]);
+        assert_eq!(collect(l.range(95..)), [96, 98]);
+        assert_eq!(collect(l.range(..)).len(), 50);
+        assert_eq!(collect(l.range(7..8)), Vec::<i32>::new());
+        assert_eq!(collect(l.range(200..)), Vec::<i32>::new());
+        assert_eq!(collect(l.range(..-1)), Vec::<i32>::new());
+        assert_eq!(collect(l.range((Bound::Included(20), Bound::Excluded(10)))), Vec::<i32>::new());
+        assert_eq!(collect(l.range(4..=4)), [4]);
+
+        assert_eq!(l.range(10..20).rev().copied().collect::<Vec<_>>(), [18, 16, 14, 12, 10]);
+        let mut iter = l.range(10..16);
+        assert_eq!(iter.next(), Some(&10));
+        assert_eq!(iter.next_back(), Some(&14));
+        assert_eq!(iter.next(), Some(&12));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn drain() {
+        let mut l = SkipList::<String, 8>::new();
+        for i in (0..20).rev() {
+            l.insert(format!("{:02}", i));
+        }
+
+        let drained: Vec<String> = l.drain().collect();
+        assert_eq!(drained, (0..20).map(|i| format!("{:02}", i)).collect::<Vec<_>>());
+        assert!(l.is_empty());
+        assert_eq!(l.first(), None);
+
+        for s in drained {
+            l.insert(s);
+        }
+        let mut drain = l.drain();
+        assert_eq!(drain.len(), 20);
+        assert_eq!(drain.next().as_deref(), Some("00"));
+        assert_eq!(drain.next_back().as_deref(), Some("19"));
+        drop(drain);
+        assert!(l.is_empty());
+        assert_eq!(l.last(), None);
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..200).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+
+        let extracted: Vec<i32> = l.extract_if(|v| v % 3 == 0).collect();
+        assert_eq!(extracted, (0..200).filter(|v| v % 3 == 0).collect::<Vec<_>>());
+        assert_eq!(l.len(), 200 - extracted.len());
+        for i in 0..200 {
+            assert_eq!(l.contains(&i), i % 3 != 0);
+        }
+        assert_eq!(l.first(), Some(&1));
+        assert_eq!(l.last(), Some(&199));
+
+        // removing the tail fixes up the tail link, and partially consumed iterators keep the rest
+        assert_eq!(l.extract_if(|v| *v > 190).next(), Some(191));
+        assert_eq!(l.last(), Some(&199));
+        assert_eq!(l.extract_if(|v| *v > 190).count(), 5);
+        assert_eq!(l.last(), Some(&190));
+        assert_eq!(l.pop_back(), Some(190));
+        assert_eq!(l.iter().next_back(), Some(&188));
+    }
+
+    #[test]
+    fn retain() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..200).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            l.insert(i);
+        }
+
+        l.retain(|v| v % 2 == 0 && *v != 0);
+        assert_eq!(l.len(), 99);
+        assert_eq!(l.iter().copied().collect::<Vec<_>>(), (2..200).step_by(2).collect::<Vec<_>>());
+        assert!(!l.contains(&0));
+        assert!(l.contains(&100));
+        assert_eq!(l.remove(&198), Some(198));
+        assert_eq!(l.last(), Some(&196));
+
+        l.retain(|_| false);
+        assert!(l.is_empty());
+        assert_eq!(l.last(), None);
+    }
+
+    #[test]
+    fn extend() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..100).map(|_| fastrand::i32(0..50)).collect();
+        for &i in nums[..30].iter() {
+            l.insert(i);
+        }
+        l.extend(nums[30..].iter().copied());
+        nums.sort();
+
+        assert_eq!(l.len(), 100);
+        assert_eq!(l.iter().copied().collect::<Vec<_>>(), nums);
+        assert_eq!(l.iter().rev().copied().collect::<Vec<_>>(), nums.iter().rev().copied().collect::<Vec<_>>());
+        for i in nums.iter() {
+            assert!(l.contains(i));
+        }
+
+        l.extend(&[-1, 1000]);
+        assert_eq!(l.first(), Some(&-1));
+        assert_eq!(l.last(), Some(&1000));
+        assert_eq!(l.remove(&1000), Some(1000));
+        assert_eq!(l.last(), nums.last());
+    }
+
+    #[test]
+    fn clone() {
+        let mut l = SkipList::<String, 8>::new();
+        for i in 0..100 {
+            l.insert(i.to_string());
+        }
+
+        let mut c = l.clone();
+        assert_eq!(c.len(), 100);
+        assert!(c.iter().eq(l.iter()));
+
+        // towers are preserved node for node
+        let levels = |l: &SkipList<String, 8>| {
+            let mut levels = Vec::new();
+            let mut node = l.head.next(0);
+            while let Some(curr) = node {
+                levels.push(curr.level);
+                node = curr.next(0);
+            }
+            levels
+        };
+        assert_eq!(levels(&c), levels(&l));
+
+        assert_eq!(c.remove(&"50".to_string()), Some("50".to_string()));
+        assert!(l.contains("50"));
+        assert!(!c.contains("50"));
+        assert_eq!(c.pop_back(), l.last().cloned());
+        assert!(SkipList::<String, 8>::new().clone().is_empty());
+    }
+
+    #[test]
+    fn eq_and_ord() {
+        let mut a = SkipList::<i32, 8>::new();
+        let mut b = SkipList::<i32, 8>::new();
+        assert_eq!(a, b);
+
+        a.extend([3, 1, 2]);
+        b.extend([2, 3, 1]);
+        assert_eq!(a, b);
+        assert_eq!(a.cmp(&b), std::cmp::Ordering::Equal);
+
+        b.insert(4);
+        assert_ne!(a, b);
+        assert!(a < b);
+
+        a.remove(&1);
+        assert!(a > b);
+
+        let mut lists = vec![a.clone(), b.clone(), a];
+        lists.sort();
+        lists.dedup();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0], b);
+
+        let mut f = SkipList::<f64, 8, _>::with_comparator(|a: &f64, b: &f64| a.total_cmp(b));
+        f.extend([1.0, f64::NAN]);
+        assert_eq!(f.partial_cmp(&f.clone()), None);
+    }
+
+    #[test]
+    fn with_comparator() {
+        let mut l: SkipListBy<f64, 8, _> = SkipList::with_comparator(|a: &f64, b: &f64| a.total_cmp(b));
+        l.extend([2.5, f64::NAN, -1.0, 0.0]);
+        l.insert(1.0);
+        assert_eq!(l.first(), Some(&-1.0));
+        assert!(l.last().unwrap().is_nan());
+        assert_eq!(l.remove(&0.0), Some(0.0));
+        assert_eq!(l.rank(&2.5), 2);
+
+        let mut rev = SkipList::<i32, 8, _>::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        rev.extend([3, 1, 4, 1, 5]);
+        assert!(rev.iter().eq([5, 4, 3, 1, 1].iter()));
+        assert_eq!(rev.count(&1), 2);
+        assert!(rev.range((Bound::Included(4), Bound::Included(1))).eq([4, 3, 1, 1].iter()));
+
+        let other = rev.split_off(&3);
+        assert!(rev.iter().eq([5, 4].iter()));
+        rev.append(other);
+        assert_eq!(rev.insert_unique(4), Err(4));
+        assert!(rev.clone().into_iter().eq([5, 4, 3, 1, 1]));
+    }
+
+    #[test]
+    fn with_key() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct User {
+            id: u32,
+            name: &'static str,
+        }
+
+        let mut l: SkipListByKey<User, 8, _> = SkipList::with_key(|user: &User| user.id);
+        l.extend([User { id: 3, name: "c" }, User { id: 1, name: "a" }, User { id: 2, name: "b" }]);
+        assert!(l.iter().map(|user| user.name).eq(["a", "b", "c"]));
+
+        let probe = User { id: 2, name: "" };
+        assert_eq!(l.rank(&probe), 1);
+        assert_eq!(l.remove(&probe).map(|user| user.name), Some("b"));
+        assert_eq!(l.insert_unique(User { id: 1, name: "dup" }).map_err(|user| user.name), Err("dup"));
+        assert_eq!(l.clone().len(), 2);
+    }
+
+    #[test]
+    fn duplicate_policy() {
+        use super::DuplicatePolicy;
+
+        let mut multiset = SkipList::<(i32, char), 8, _>::with_key(|&(k, _): &(i32, char)| k);
+        assert_eq!(multiset.duplicate_policy(), DuplicatePolicy::Allow);
+        assert_eq!(multiset.insert((1, 'a')), None);
+        assert_eq!(multiset.insert((1, 'b')), None);
+        assert_eq!(multiset.insert_with_policy((1, 'c'), DuplicatePolicy::Reject), Some((1, 'c')));
+        assert_eq!(multiset.insert_with_policy((1, 'd'), DuplicatePolicy::Replace), Some((1, 'b')));
+        assert!(multiset.iter().eq([(1, 'a'), (1, 'd')].iter()));
+
+        let mut set = SkipList::<i32, 8>::new().with_duplicate_policy(DuplicatePolicy::Reject);
+        assert_eq!(set.insert(2), None);
+        assert_eq!(set.insert(2), Some(2));
+        set.extend([3, 2, 1, 3]);
+        assert!(set.iter().eq([1, 2, 3].iter()));
+
+        let mut lww = SkipList::<(i32, char), 8, _>::with_key(|&(k, _): &(i32, char)| k)
+            .with_duplicate_policy(DuplicatePolicy::Replace);
+        assert_eq!(lww.insert((1, 'a')), None);
+        assert_eq!(lww.insert((1, 'b')), Some((1, 'a')));
+        lww.extend([(2, 'x'), (1, 'c'), (2, 'y')]);
+        assert!(lww.iter().eq([(1, 'c'), (2, 'y')].iter()));
+        assert_eq!(lww.len(), 2);
+        assert_eq!(lww.clone().duplicate_policy(), DuplicatePolicy::Replace);
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let mut l = SkipList::<(i32, char), 8, _>::with_key(|&(k, _): &(i32, char)| k);
+        l.extend([(1, 'a'), (3, 'c')]);
+
+        let mut calls = 0;
+        assert_eq!(l.get_or_insert_with(&(1, '?'), || { calls += 1; (1, 'x') }), &(1, 'a'));
+        assert_eq!(l.get_or_insert_with(&(2, '?'), || { calls += 1; (2, 'b') }), &(2, 'b'));
+        assert_eq!(l.get_or_insert_with(&(2, '?'), || { calls += 1; (2, 'y') }), &(2, 'b'));
+        assert_eq!(calls, 1);
+        assert!(l.iter().eq([(1, 'a'), (2, 'b'), (3, 'c')].iter()));
+        assert_eq!(l.get(1), Some(&(2, 'b')));
+    }
+
+    #[test]
+    fn replace() {
+        let mut l = SkipList::<(i32, char), 8, _>::with_key(|&(k, _): &(i32, char)| k);
+        assert_eq!(l.replace((1, 'a')), None);
+        assert_eq!(l.replace((2, 'b')), None);
+        assert_eq!(l.replace((1, 'c')), Some((1, 'a')));
+        assert!(l.iter().eq([(1, 'c'), (2, 'b')].iter()));
+        assert_eq!(l.len(), 2);
+    }
+
+    #[test]
+    fn take() {
+        let mut l = SkipList::<(i32, &str), 8, _>::with_key(|&(k, _): &(i32, &str)| k);
+        l.extend([(1, "one"), (2, "two")]);
+        assert_eq!(l.take(&(2, "")), Some((2, "two")));
+        assert_eq!(l.take(&(2, "")), None);
+        assert_eq!(l.len(), 1);
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut l = SkipList::<String, 8>::new();
+        l.extend(["b", "a", "c"].map(String::from));
+
+        assert!(l.contains("a"));
+        assert!(!l.contains("d"));
+        assert_eq!(l.find("b").map(String::as_str), Some("b"));
+        assert_eq!(l.find("bb"), None);
+        assert_eq!(l.remove("c").as_deref(), Some("c"));
+        assert_eq!(l.take("a").as_deref(), Some("a"));
+        assert!(l.iter().eq(["b"].iter()));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn promotion_probability() {
+        let promoted = |l: &SkipList<i32, 16>| l.iter_nodes().filter(|node| node.level > 0).count();
+
+        let mut half = SkipList::<i32, 16>::new();
+        half.extend(0..10_000);
+        assert_eq!(half.promotion_probability(), 0.5);
+        assert!((4_000..6_000).contains(&promoted(&half)));
+
+        let mut quarter = SkipList::<i32, 16>::new().with_promotion_probability(0.25);
+        quarter.extend(0..10_000);
+        assert!((2_000..3_000).contains(&promoted(&quarter)));
+        assert!(quarter.iter().copied().eq(0..10_000));
+        assert_eq!(quarter.clone().promotion_probability(), 0.25);
+    }
+
+    #[test]
+    fn with_seed() {
+        let levels = |l: &SkipList<i32, 16>| l.iter_nodes().map(|node| node.level).collect::<Vec<_>>();
+
+        let mut a = SkipList::<i32, 16>::with_seed(42);
+        let mut b = SkipList::<i32, 16>::with_seed(42);
+        for i in [5, 3, 9, 1, 7, 2, 8] {
+            a.insert(i);
+            b.insert(i);
+        }
+        assert_eq!(levels(&a), levels(&b));
+
+        let mut c = SkipList::<i32, 16>::with_seed(43);
+        c.extend(0..100);
+        let mut d = SkipList::<i32, 16>::with_seed(44);
+        d.extend(0..100);
+        assert_ne!(levels(&c), levels(&d));
+    }
+
+    #[test]
+    fn level_generator() {
+        let mut ruler = SkipList::<i32, 4, _, _>::with_level_generator(OrdComparator, Deterministic::new());
+        ruler.extend(0..8);
+        let levels: Vec<_> = ruler.iter_nodes().map(|node| node.level).collect();
+        assert_eq!(levels, [0, 1, 0, 2, 0, 1, 0, 3]);
+
+        let mut capped = SkipList::<i32, 16, _, _>::with_level_generator(OrdComparator, Biased::with_seed([1.0, 0.5], 7));
+        capped.extend(0..1_000);
+        assert!(capped.iter_nodes().all(|node| (1..=2).contains(&node.level)));
+        assert!(capped.iter().copied().eq(0..1_000));
+
+        #[derive(Clone)]
+        struct Flat;
+        impl LevelGenerator for Flat {
+            fn gen_level(&mut self, _max_level: usize) -> usize {
+                0
+            }
+        }
+        let mut flat = SkipList::<i32, 8, _, _>::with_level_generator(|a: &i32, b: &i32| b.cmp(a), Flat);
+        flat.extend([2, 3, 1]);
+        assert!(flat.iter_nodes().all(|node| node.level == 0));
+        assert!(flat.iter().eq([3, 2, 1].iter()));
+        assert_eq!(flat.remove(&2), Some(2));
+        assert!(flat.split_off(&3).iter().eq([3, 1].iter()));
+        assert!(flat.is_empty());
+    }
+
+    #[test]
+    fn height_follows_tallest_node() {
+        let tallest = |l: &SkipList<i32, 32>| l.iter_nodes().map(|node| node.level + 1).max().unwrap_or(1);
+
+        let mut l = SkipList::<i32, 32>::with_seed(3);
+        l.extend(0..100);
+        assert_eq!(l.height, tallest(&l));
+        assert!(l.height < 16);
+
+        l.retain(|v| v % 10 == 0);
+        assert_eq!(l.height, tallest(&l));
+        assert!(l.iter().copied().eq((0..100).step_by(10)));
+        assert_eq!(l.get(3), Some(&30));
+        assert_eq!(l.rank(&55), 6);
+
+        let mut tail = l.split_off(&50);
+        assert_eq!((l.height, tail.height), (tallest(&l), tallest(&tail)));
+        tail.remove_range(..);
+        assert_eq!(tail.height, 1);
+        l.append(tail);
+        assert_eq!(l.height, tallest(&l));
+        l.clear();
+        assert_eq!(l.height, 1);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn rng_core_levels() {
+        use super::RngGeometric;
+
+        #[derive(Clone)]
+        struct XorShift(u64);
+        impl rand_core::RngCore for XorShift {
+            fn next_u32(&mut self) -> u32 {
+                self.next_u64() as u32
+            }
+            fn next_u64(&mut self) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0
+            }
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                rand_core::impls::fill_bytes_via_next(self, dest)
+            }
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+                self.fill_bytes(dest);
+                Ok(())
+            }
+        }
+
+        let levels = |l: &SkipList<i32, 16, OrdComparator, RngGeometric<XorShift>>| {
+            l.iter_nodes().map(|node| node.level).collect::<Vec<_>>()
+        };
+        let mut a = SkipList::with_level_generator(OrdComparator, RngGeometric::new(XorShift(1)));
+        let mut b = SkipList::with_level_generator(OrdComparator, RngGeometric::new(XorShift(1)));
+        a.extend(0..1_000);
+        b.extend(0..1_000);
+        assert_eq!(levels(&a), levels(&b));
+        assert!((400..600).contains(&a.iter_nodes().filter(|node| node.level > 0).count()));
+
+        let mut quarter = SkipList::<i32, 16, _, _>::with_level_generator(
+            OrdComparator,
+            RngGeometric::new(XorShift(2)).with_probability(0.25),
+        );
+        quarter.extend(0..1_000);
+        assert!((150..350).contains(&quarter.iter_nodes().filter(|node| node.level > 0).count()));
+        assert!(quarter.iter().copied().eq(0..1_000));
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |l: &SkipList<String, 8>| {
+            let mut hasher = DefaultHasher::new();
+            l.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut a = SkipList::<String, 8>::new();
+        let mut b = SkipList::<String, 8>::new();
+        assert_eq!(hash(&a), hash(&b));
+
+        a.extend(["x".to_string(), "y".to_string()]);
+        b.extend(["y".to_string(), "x".to_string()]);
+        assert_eq!(hash(&a), hash(&b));
+
+        // same bytes, different elements
+        let mut c = SkipList::<String, 8>::new();
+        c.insert("xy".to_string());
+        assert_ne!(hash(&a), hash(&c));
+
+        b.pop_back();
+        assert_ne!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn default() {
+        #[derive(Default)]
+        struct Index {
+            keys: SkipList<u64, 8>,
+        }
+
+        let mut index = Index::default();
+        assert!(index.keys.is_empty());
+        index.keys.extend([2, 1]);
+        assert_eq!(index.keys.first(), Some(&1));
+        assert_eq!(SkipList::<u64, 8>::default(), SkipList::new());
+    }
+
+    #[test]
+    fn split_off() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..100).collect();
+        fastrand::shuffle(nums.as_mut());
+        l.extend(nums);
+
+        let mut r = l.split_off(&60);
+        assert_eq!(l.len(), 60);
+        assert_eq!(r.len(), 40);
+        assert!(l.iter().copied().eq(0..60));
+        assert!(r.iter().copied().eq(60..100));
+        assert!(r.iter().rev().copied().eq((60..100).rev()));
+        assert_eq!(l.last(), Some(&59));
+        assert_eq!(r.first(), Some(&60));
+        for i in 0..100 {
+            assert_eq!(l.contains(&i), i < 60);
+            assert_eq!(r.contains(&i), i >= 60);
+        }
+
+        // both halves stay fully usable
+        l.insert(1000);
+        assert_eq!(r.pop_front(), Some(60));
+        assert_eq!(r.remove(&99), Some(99));
+        assert_eq!(l.pop_back(), Some(1000));
+
+        let everything = l.split_off(&-1);
+        assert!(l.is_empty());
+        assert_eq!(l.last(), None);
+        assert_eq!(everything.len(), 60);
+        assert!(r.split_off(&1000).is_empty());
+        assert_eq!(r.len(), 38);
+
+        // with few levels, the top level holds nodes the split passes over
+        let mut l = SkipList::<i32, 2>::new();
+        l.extend(0..1000);
+        let r = l.split_off(&700);
+        assert_eq!((l.len(), r.len()), (700, 300));
+        assert_eq!(l.validate(), Ok(()));
+        assert_eq!(r.validate(), Ok(()));
+    }
+
+    #[test]
+    fn append() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut r = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..200).map(|_| fastrand::i32(0..100)).collect();
+        l.extend(nums[..120].iter().copied());
+        r.extend(nums[120..].iter().copied());
+        nums.sort();
+
+        l.append(r);
+        assert_eq!(l.len(), 200);
+        assert!(l.iter().eq(nums.iter()));
+        assert!(l.iter().rev().eq(nums.iter().rev()));
+        for i in nums.iter() {
+            assert!(l.contains(i));
+        }
+        assert_eq!(l.remove(&nums[199]), Some(nums[199]));
+        assert_eq!(l.last(), Some(&nums[198]));
+
+        l.append(SkipList::new());
+        assert_eq!(l.len(), 199);
+        let mut e = SkipList::<i32, 8>::new();
+        e.append(l);
+        assert_eq!(e.len(), 199);
+        assert_eq!(e.first(), Some(&nums[0]));
+    }
+
+    #[test]
+    fn from_sorted_iter() {
+        let mut l = SkipList::<i32, 8>::from_sorted_iter(0..1000);
+        assert_eq!(l.len(), 1000);
+        assert!(l.iter().copied().eq(0..1000));
+        for i in 0..1000 {
+            assert!(l.contains(&i));
+        }
+
+        let levels: Vec<usize> = l.iter_nodes().map(|node| node.level).take(8).collect();
+        assert_eq!(levels, [0, 1, 0, 2, 0, 1, 0, 3]);
+        assert_eq!(l.iter_nodes().filter(|node| node.level == 7).count(), 1000 / 128);
+
+        l.insert(500);
+        assert_eq!(l.remove(&999), Some(999));
+        assert_eq!(l.last(), Some(&998));
+        assert!(SkipList::<i32, 8>::from_sorted_iter(None).is_empty());
+    }
+
+    #[test]
+    fn get_by_index() {
+        fn check(l: &SkipList<i32, 6>) {
+            assert_eq!(l.validate(), Ok(()));
+            let expected: Vec<i32> = l.iter().copied().collect();
+            for (i, v) in expected.iter().enumerate() {
+                assert_eq!(l.get(i), Some(v));
+            }
+            assert_eq!(l.get(expected.len()), None);
+        }
+
+        let mut l = SkipList::<i32, 6>::new();
+        assert_eq!(l.get(0), None);
+        for _ in 0..200 {
+            let i = fastrand::i32(0..100);
+            l.insert(i);
+        }
+        check(&l);
+
+        for i in (0..100).step_by(3) {
+            l.remove_all(&i);
+        }
+        l.pop_front();
+        l.pop_back();
+        check(&l);
+
+        let mut other = l.split_off(&50);
+        check(&l);
+        check(&other);
+        other.extend((0..100).map(|_| fastrand::i32(0..100)));
+        other.retain(|v| v % 5 != 0);
+        check(&other);
+        l.append(other);
+        check(&l);
+
+        let mut cursor = l.cursor_at(&40);
+        cursor.remove_current();
+        let _ = cursor.insert_before(40);
+        let _ = cursor.insert_after(41);
+        check(&l);
+        check(&l.clone());
+        check(&SkipList::from_sorted_iter(0..77));
+
+        l.clear();
+        l.extend([3, 1, 2]);
+        check(&l);
+    }
+
+    #[test]
+    fn rank() {
+        let mut l = SkipList::<i32, 6>::new();
+        assert_eq!(l.rank(&5), 0);
+
+        l.extend([10, 20, 20, 30, 40]);
+        assert_eq!(l.rank(&5), 0);
+        assert_eq!(l.rank(&10), 0);
+        assert_eq!(l.rank(&20), 1);
+        assert_eq!(l.rank(&25), 3);
+        assert_eq!(l.rank(&40), 4);
+        assert_eq!(l.rank(&50), 5);
+
+        let nums: Vec<i32> = (0..300).map(|_| fastrand::i32(0..100)).collect();
+        l.clear();
+        l.extend(nums.iter());
+        for i in 0..=100 {
+            assert_eq!(l.rank(&i), nums.iter().filter(|&&v| v < i).count());
+        }
+    }
+
+    #[test]
+    fn range_count() {
+        let mut l = SkipList::<i32, 6>::new();
+        assert_eq!(l.range_count(..), 0);
+
+        let nums: Vec<i32> = (0..300).map(|_| fastrand::i32(0..100)).collect();
+        l.extend(nums.iter());
+        let count = |r: &dyn Fn(i32) -> bool| nums.iter().filter(|&&v| r(v)).count();
+
+        assert_eq!(l.range_count(..), 300);
+        assert_eq!(l.range_count(10..20), count(&|v| (10..20).contains(&v)));
+        assert_eq!(l.range_count(10..=20), count(&|v| (10..=20).contains(&v)));
+        assert_eq!(l.range_count(50..), count(&|v| v >= 50));
+        assert_eq!(l.range_count(..50), count(&|v| v < 50));
+        assert_eq!(l.range_count((Bound::Excluded(30), Bound::Unbounded)), count(&|v| v > 30));
+        assert_eq!(l.range_count(200..300), 0);
+        assert_eq!(l.range_count((Bound::Included(60), Bound::Excluded(40))), 0);
+    }
+
+    #[test]
+    fn select_and_median() {
+        let mut l = SkipList::<i32, 6>::new();
+        assert_eq!(l.select(0), None);
+        assert_eq!(l.median(), None);
+
+        l.extend([5]);
+        assert_eq!(l.median(), Some(&5));
+        l.extend([1, 9, 3]);
+        assert_eq!(l.median(), Some(&3));
+        l.extend([7]);
+        assert_eq!(l.median(), Some(&5));
+
+        for k in 0..l.len() {
+            assert_eq!(l.rank(l.select(k).unwrap()), k);
+        }
+        assert_eq!(l.select(5), None);
+    }
+
+    #[test]
+    fn remove_at() {
+        let mut l = SkipList::<i32, 6>::new();
+        assert_eq!(l.remove_at(0), None);
+
+        let mut nums: Vec<i32> = (0..200).map(|_| fastrand::i32(0..100)).collect();
+        l.extend(nums.iter());
+        nums.sort();
+        while !nums.is_empty() {
+            let i = fastrand::usize(..nums.len());
+            assert_eq!(l.remove_at(i), Some(nums.remove(i)));
+            assert_eq!(l.len(), nums.len());
+            assert_eq!(l.get(i), nums.get(i));
+        }
+        assert_eq!(l.last(), None);
+
+        l.extend([1, 2, 3]);
+        assert_eq!(l.remove_at(3), None);
+        assert_eq!(l.remove_at(2), Some(3));
+        assert_eq!(l.last(), Some(&2));
+    }
+
+    #[test]
+    fn remove_range() {
+        let mut l = SkipList::<i32, 6>::new();
+        assert_eq!(l.remove_range(..), 0);
+
+        let mut nums: Vec<i32> = (0..300).map(|_| fastrand::i32(0..100)).collect();
+        l.extend(nums.iter());
+        nums.sort();
+
+        assert_eq!(l.remove_range(20..30), nums.iter().filter(|v| (20..30).contains(*v)).count());
+        nums.retain(|v| !(20..30).contains(v));
+        assert_eq!(l.remove_range(20..30), 0);
+        assert_eq!(l.remove_range((Bound::Excluded(60), Bound::Included(70))), nums.iter().filter(|&&v| v > 60 && v <= 70).count());
+        nums.retain(|&v| !(v > 60 && v <= 70));
+        assert_eq!(l.remove_range(90..), nums.iter().filter(|&&v| v >= 90).count());
+        nums.retain(|&v| v < 90);
+        assert_eq!(l.remove_range(..=5), nums.iter().filter(|&&v| v <= 5).count());
+        nums.retain(|&v| v > 5);
+
+        assert_eq!(l.len(), nums.len());
+        assert!(l.iter().eq(nums.iter()));
+        for (i, v) in nums.iter().enumerate() {
+            assert_eq!(l.get(i), Some(v));
+        }
+        assert_eq!(l.last(), nums.last());
+        assert_eq!(l.iter().next_back(), nums.last());
+
+        assert_eq!(l.remove_range(..), nums.len());
+        assert!(l.is_empty());
+        assert_eq!(l.last(), None);
+        l.extend([1, 2]);
+        assert_eq!(l.get(1), Some(&2));
+    }
+
+    #[test]
+    fn insert_unique() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..100).collect();
+        fastrand::shuffle(nums.as_mut());
+        for &i in nums.iter() {
+            assert_eq!(l.insert_unique(i), Ok(()));
+        }
+        for &i in nums.iter() {
+            assert_eq!(l.insert_unique(i), Err(i));
+        }
+
+        assert_eq!(l.len(), 100);
+        assert!(l.iter().copied().eq(0..100));
+        assert_eq!(l.insert_unique(100), Ok(()));
+        assert_eq!(l.last(), Some(&100));
+        assert_eq!(l.insert_unique(-1), Ok(()));
+        assert_eq!(l.first(), Some(&-1));
+    }
+
+    #[test]
+    fn count_and_remove_all() {
+        let mut l = SkipList::<i32, 8>::new();
+        let mut nums: Vec<i32> = (0..300).map(|i| i % 10).collect();
+        fastrand::shuffle(nums.as_mut());
+        l.extend(nums);
+
+        for i in 0..10 {
+            assert_eq!(l.count(&i), 30);
+        }
+        assert_eq!(l.count(&10), 0);
+        assert_eq!(l.count(&-1), 0);
+
+        assert_eq!(l.remove_all(&0), 30);
+        assert_eq!(l.remove_all(&9), 30);
+        assert_eq!(l.remove_all(&5), 30);
+        assert_eq!(l.remove_all(&5), 0);
+        assert_eq!(l.len(), 210);
+        assert_eq!(l.count(&5), 0);
+        assert_eq!(l.count(&4), 30);
+        assert_eq!(l.first(), Some(&1));
+        assert_eq!(l.last(), Some(&8));
+        assert!(l.iter().rev().copied().eq(l.clone().into_iter().rev()));
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SkipList<i32, 8>>();
+        assert_send_sync::<SkipList<i32, 8, OrdComparator, Biased>>();
+        assert_send_sync::<SkipList<i32, 8, OrdComparator, Deterministic>>();
+        assert_send_sync::<super::Iter<'static, i32, 8>>();
+        assert_send_sync::<super::IterMut<'static, i32, 8>>();
+        assert_send_sync::<Range<'static, i32, 8>>();
+        assert_send_sync::<super::IntoIter<i32, 8>>();
+        assert_send_sync::<super::CursorMut<'static, i32, 8>>();
+        assert_send_sync::<super::SkipListMap<i32, String, 8>>();
+        assert_send_sync::<super::DynSkipList<i32>>();
+        assert_send_sync::<super::DeterministicSkipList<i32>>();
+    }
+
+    #[test]
+    fn append_relinks_nodes() {
+        use std::collections::HashSet;
+        use std::thread;
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                thread::spawn(move || {
+                    let mut l = SkipList::<u32, 8>::new();
+                    l.extend((0..100).map(|i| i * 4 + t));
+                    l
+                })
+            })
+            .collect();
+        let lists: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+        let nodes: HashSet<_> = lists.iter().flat_map(|l| l.iter().map(|x| x as *const u32)).collect();
+
+        let mut merged = SkipList::<u32, 8>::new();
+        for l in lists {
+            merged.append(l);
+        }
+        assert!(merged.iter().copied().eq(0..400));
+        assert!(merged.iter().all(|x| nodes.contains(&(x as *const u32))));
+        assert_eq!(merged.validate(), Ok(()));
+    }
+
+    #[test]
+    fn move_across_threads() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        let mut l = SkipList::<i32, 8>::new();
+        l.extend(0..100);
+        let l = thread::spawn(move || {
+            l.extend(100..200);
+            l
+        })
+        .join()
+        .unwrap();
+        assert!(l.iter().copied().eq(0..200));
+
+        let shared = Arc::new(Mutex::new(l));
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for i in 0..50 {
+                        shared.lock().unwrap().insert(200 + t * 50 + i);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let l = Arc::try_unwrap(shared).unwrap().into_inner().unwrap();
+        assert!(l.iter().copied().eq(0..400));
+
+        thread::scope(|s| {
+            for t in 0..4 {
+                let l = &l;
+                s.spawn(move || {
+                    for i in (t..400).step_by(4) {
+                        assert!(l.contains(&i));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn arena() {
+        let mut l = SkipList::<i32, 8>::new().with_arena();
+        let mut nums: Vec<i32> = (0..1_000).collect();
+        fastrand::shuffle(&mut nums);
+        l.extend(nums);
+        assert!(l.iter().copied().eq(0..1_000));
+        assert_eq!(l.remove(&500), Some(500));
+        assert_eq!(l.remove_range(100..200), 100);
+        assert_eq!(l.get(100), Some(&200));
+        l.retain(|&x| x % 2 == 0);
+        assert_eq!(l.len(), 449);
+
+        l.clear();
+        assert!(l.is_empty());
+        l.extend([3, 1, 2]);
+        assert!(l.iter().copied().eq(1..=3));
+        assert!(l.clone().iter().copied().eq(1..=3));
+    }
+
+    #[test]
+    fn arena_split_off_and_append() {
+        let mut arena = SkipList::<i32, 8>::new().with_arena();
+        arena.extend((0..200).step_by(2));
+        let mut tail = arena.split_off(&100);
+        // the split off nodes outlive the list they were allocated by
+        arena.clear();
+        assert!(tail.iter().copied().eq((100..200).step_by(2)));
+
+        let mut boxed = SkipList::<i32, 8>::new();
+        boxed.extend((101..200).step_by(2));
+        tail.append(boxed);
+        assert!(tail.iter().copied().eq(100..200));
+        assert_eq!(tail.get(50), Some(&150));
+
+        let mut boxed = SkipList::<i32, 8>::new();
+        boxed.extend([-1, 300]);
+        boxed.append(tail);
+        assert_eq!(boxed.len(), 102);
+        boxed.insert(0);
+        assert_eq!(boxed.iter().nth(1), Some(&0));
+
+        arena.extend([5, 6]);
+        let mut other = SkipList::<i32, 8>::new().with_arena();
+        other.extend([4, 7]);
+        arena.append(other);
+        assert!(arena.iter().copied().eq(4..8));
+    }
+
+    #[test]
+    fn arena_drops_values() {
+        use std::rc::Rc;
+
+        let counter = Rc::new(());
+        let mut l = SkipList::<(i32, Rc<()>), 8>::new().with_arena();
+        l.extend((0..100).map(|i| (i, Rc::clone(&counter))));
+        let tail = l.split_off(&(50, Rc::clone(&counter)));
+        assert_eq!(Rc::strong_count(&counter), 101);
+        l.remove_range(..(10, Rc::clone(&counter)));
+        assert_eq!(Rc::strong_count(&counter), 91);
+        l.clear();
+        assert_eq!(Rc::strong_count(&counter), 51);
+        drop(tail);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    // counts the live allocations made through it
+    #[derive(Clone, Copy)]
+    struct Counting<'a>(&'a std::cell::Cell<usize>);
+
+    unsafe impl crate::Allocator for Counting<'_> {
+        fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, crate::AllocError> {
+            self.0.set(self.0.get() + 1);
+            crate::Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+            self.0.set(self.0.get() - 1);
+            crate::Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn custom_allocator() {
+        use std::cell::Cell;
+
+        let live = Cell::new(0);
+        {
+            let mut l = SkipList::<i32, 8, _, _, _>::new_in(Counting(&live));
+            assert_eq!(live.get(), 1);
+            l.extend(0..100);
+            assert_eq!(live.get(), 101);
+            assert_eq!(l.remove(&50), Some(50));
+            assert_eq!(live.get(), 100);
+
+            let mut other = l.split_off(&60);
+            assert_eq!(live.get(), 101);
+            let copy = other.clone();
+            assert_eq!(live.get(), 142);
+            other.clear();
+            assert_eq!(live.get(), 102);
+            l.append(copy);
+            assert_eq!(live.get(), 101);
+            assert!(l.iter().copied().eq((0..100).filter(|&x| x != 50)));
+        }
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn append_across_allocators() {
+        use std::cell::Cell;
+
+        let (live, other_live) = (Cell::new(0), Cell::new(0));
+        {
+            let mut l = SkipList::<i32, 8, _, _, _>::new_in(Counting(&live));
+            l.extend((0..100).map(|x| x * 2));
+            let mut other = SkipList::<i32, 8, _, _, _>::new_in(Counting(&other_live));
+            other.extend((0..50).map(|x| x * 2 + 1));
+            assert_eq!((live.get(), other_live.get()), (101, 51));
+
+            l.append(other);
+            assert_eq!((live.get(), other_live.get()), (151, 0));
+            let mut expected: Vec<_> = (0..100).map(|x| x * 2).chain((0..50).map(|x| x * 2 + 1)).collect();
+            expected.sort();
+            assert!(l.iter().copied().eq(expected));
+            assert_eq!(l.validate(), Ok(()));
+        }
+        assert_eq!(live.get(), 0);
+    }
+
+    // panics when dropped if its flag is set, and counts the drops either way
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Bomb<'a>(i32, bool, &'a std::cell::Cell<usize>);
+
+    impl Drop for Bomb<'_> {
+        fn drop(&mut self) {
+            self.2.set(self.2.get() + 1);
+            if self.1 {
+                panic!("boom");
+            }
+        }
+    }
+
+    #[test]
+    fn drop_panic_frees_the_rest() {
+        use std::cell::Cell;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let live = Cell::new(0);
+        let drops = Cell::new(0);
+        let mut l = SkipList::<Bomb, 8, _, _, _>::new_in(Counting(&live)).with_free_list(4);
+        l.extend((0..100).map(|i| Bomb(i, i == 30, &drops)));
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(l))).is_err());
+        assert_eq!(drops.get(), 100);
+        assert_eq!(live.get(), 0);
+
+        drops.set(0);
+        let mut l = SkipList::<Bomb, 8, _, _, _>::new_in(Counting(&live));
+        l.extend((0..100).map(|i| Bomb(i, i == 70, &drops)));
+        assert!(catch_unwind(AssertUnwindSafe(|| l.clear())).is_err());
+        assert_eq!(drops.get(), 100);
+        assert!(l.is_empty());
+        assert_eq!(l.validate(), Ok(()));
+        l.insert(Bomb(1, false, &drops));
+        drop(l);
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drop_long_list() {
+        let l = SkipList::<u32, 4>::from_sorted_iter(0..1_000_000);
+        drop(l);
+    }
+
+    // fails once its budget of allocations runs out
+    struct Budget<'a>(&'a std::cell::Cell<usize>);
+
+    unsafe impl crate::Allocator for Budget<'_> {
+        fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, crate::AllocError> {
+            let left = self.0.get().checked_sub(1).ok_or(crate::AllocError)?;
+            self.0.set(left);
+            crate::Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+            crate::Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn try_insert() {
+        use std::cell::Cell;
+
+        let budget = Cell::new(4);
+        let mut l = SkipList::<i32, 8, _, _, _>::new_in(Budget(&budget));
+        for i in [1, 3, 5] {
+            assert_eq!(l.try_insert(i).unwrap(), None);
+        }
+
+        let err = l.try_insert(2).unwrap_err();
+        assert_eq!(err.into_inner(), 2);
+        assert_eq!(l.len(), 3);
+        assert!(l.iter().copied().eq([1, 3, 5]));
+
+        // replacing a duplicate doesn't need a node
+        assert_eq!(l.try_insert_with_policy(3, crate::DuplicatePolicy::Replace).unwrap(), Some(3));
+
+        budget.set(1);
+        assert_eq!(l.try_insert(2).unwrap(), None);
+        assert!(l.iter().copied().eq([1, 2, 3, 5]));
+    }
+
+    #[test]
+    fn free_list() {
+        use std::cell::Cell;
+
+        // levels 0, 1, 2, 0, 1, 2...
+        struct Cycle(usize);
+        impl crate::LevelGenerator for Cycle {
+            fn gen_level(&mut self, _: usize) -> usize {
+                self.0 += 1;
+                (self.0 - 1) % 3
+            }
+        }
+
+        let live = Cell::new(0);
+        {
+            let mut l = SkipList::<i32, 4, _, _, _>::with_level_generator_in(OrdComparator, Cycle(0), Counting(&live))
+                .with_free_list(10);
+            l.extend(0..30);
+            assert_eq!(live.get(), 31);
+            assert_eq!(l.remove_range(..10), 10);
+            assert_eq!(live.get(), 31);
+            // the new nodes get the same mix of levels as the removed ones
+            l.extend(30..40);
+            assert_eq!(live.get(), 31);
+            assert!(l.iter().copied().eq(10..40));
+            assert_eq!(l.get(15), Some(&25));
+
+            l.clear();
+            assert_eq!(live.get(), 11);
+            l.extend(0..5);
+            assert_eq!(live.get(), 11);
+            l.release_free_nodes();
+            assert_eq!(live.get(), 6);
+
+            let mut arena = SkipList::<i32, 4>::new().with_arena().with_free_list(4);
+            arena.extend(0..10);
+            arena.retain(|&x| x % 2 == 0);
+            arena.extend((1..10).step_by(2));
+            assert!(arena.iter().copied().eq(0..10));
+            let mut boxed = SkipList::<i32, 4>::new().with_free_list(4);
+            boxed.extend(10..20);
+            boxed.remove_range(..15);
+            arena.append(boxed);
+            assert!(arena.iter().copied().eq((0..10).chain(15..20)));
+        }
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn towers_sized_to_level() {
+        type Node = super::SkipListNode<u64, 32>;
+        let level = std::mem::size_of::<super::Level<u64, 32>>();
+        assert_eq!(Node::layout(0).size(), std::mem::size_of::<Node>() + level);
+        assert_eq!(Node::layout(31).size(), std::mem::size_of::<Node>() + 32 * level);
+
+        // over-aligned elements get aligned nodes, boxed or from an arena
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        #[repr(align(64))]
+        struct Aligned(u32);
+
+        for mut l in [SkipList::<Aligned, 16>::new(), SkipList::<Aligned, 16>::new().with_arena()] {
+            l.extend((0..500).rev().map(Aligned));
+            assert!(l.iter().all(|x| (x as *const Aligned as usize).is_multiple_of(64)));
+            assert!(l.iter().map(|x| x.0).eq(0..500));
+            l.retain(|x| x.0 % 2 == 1);
+            assert_eq!(l.get(10), Some(&Aligned(21)));
+        }
+    }
+
+    #[test]
+    fn compact() {
+        let mut l = SkipList::<i32, 12>::new().with_free_list(16);
+        let mut nums: Vec<i32> = (0..2_000).collect();
+        fastrand::shuffle(&mut nums);
+        l.extend(nums.iter().copied());
+        for &x in &nums[..1_000] {
+            l.remove(&x);
+        }
+        let left: Vec<i32> = l.iter().copied().collect();
+
+        l.compact();
+        let ideal = SkipList::<i32, 12>::from_sorted_iter(left.iter().copied());
+        assert!(l.iter().eq(left.iter()));
+        assert!(l.iter_nodes().map(|node| node.level).eq(ideal.iter_nodes().map(|node| node.level)));
+        assert_eq!(l.height, ideal.height);
+        assert_eq!(l.memory_usage().free_list_bytes, 0);
+        assert_eq!(l.memory_usage().node_bytes, ideal.memory_usage().node_bytes);
+        assert_eq!(l.get(500), left.get(500));
+        l.insert(-1);
+        assert_eq!(l.first(), Some(&-1));
+
+        let mut arena = SkipList::<i32, 12>::new().with_arena();
+        arena.extend(0..100);
+        arena.retain(|&x| x % 10 == 0);
+        arena.compact();
+        assert!(arena.iter().copied().eq((0..100).step_by(10)));
+        assert!(arena.memory_usage().arena_bytes < 100 * std::mem::size_of::<super::SkipListNode<i32, 12>>());
+
+        let mut empty = SkipList::<i32, 12>::new();
+        empty.compact();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn max_len() {
+        use crate::EvictPolicy;
+
+        // the top 5
+        let mut top = SkipList::<i32, 8>::new().with_max_len(5, EvictPolicy::Smallest);
+        for x in [3, 9, 1, 7, 5] {
+            assert_eq!(top.insert(x), None);
+        }
+        assert_eq!(top.insert(0), Some(0));
+        assert_eq!(top.insert(8), Some(1));
+        assert_eq!(top.insert(3), Some(3));
+        assert_eq!(top.len(), 5);
+        assert!(top.iter().eq(&[3, 5, 7, 8, 9]));
+        top.extend([10, 2, 11]);
+        assert!(top.iter().eq(&[7, 8, 9, 10, 11]));
+
+        let mut bottom = SkipList::<i32, 8>::new().with_max_len(3, EvictPolicy::Largest);
+        bottom.extend(0..10);
+        assert!(bottom.iter().eq(&[0, 1, 2]));
+        assert_eq!(bottom.insert(2), Some(2));
+        assert_eq!(bottom.insert(-1), Some(2));
+        assert_eq!(bottom.max_len(), Some(3));
+        assert_eq!(bottom.clone().insert(-2), Some(1));
+
+        let trimmed = SkipList::<i32, 8>::from_sorted_iter(0..10).with_max_len(4, EvictPolicy::Smallest);
+        assert!(trimmed.iter().eq(&[6, 7, 8, 9]));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn finger_search() {
+        use std::cell::Cell;
+
+        // inserting in order only compares against the finger's node
+        let compares = Cell::new(0);
+        let mut l = SkipList::<i32, 16, _>::with_comparator(|a: &i32, b: &i32| {
+            compares.set(compares.get() + 1);
+            a.cmp(b)
+        });
+        for i in 0..10_000 {
+            l.insert(i);
+        }
+        assert!(compares.get() < 2 * 10_000, "{} comparisons", compares.get());
+        for i in (0..10_000).step_by(2) {
+            assert_eq!(l.remove(&i), Some(i));
+        }
+        assert!(l.iter().copied().eq((1..10_000).step_by(2)));
+
+        // the finger never outlives the nodes or links it was taken from
+        let mut l = SkipList::<i32, 6>::new();
+        let mut model = Vec::new();
+        for _ in 0..2000 {
+            let x = fastrand::i32(0..200);
+            match fastrand::u8(0..8) {
+                0..=2 => {
+                    l.insert(x);
+                    model.insert(model.partition_point(|&y| y <= x), x);
+                }
+                3 | 4 => {
+                    let found = model.iter().position(|&y| y == x).map(|i| model.remove(i));
+                    assert_eq!(l.remove(&x), found);
+                }
+                5 => {
+                    l.remove_range(x..x + 10);
+                    model.retain(|y| !(x..x + 10).contains(y));
+                }
+                6 => {
+                    let mut other = l.split_off(&x);
+                    other.insert(x);
+                    l.insert(x - 1);
+                    l.append(other);
+                    model.insert(model.partition_point(|&y| y < x), x);
+                    model.insert(model.partition_point(|&y| y < x), x - 1);
+                }
+                _ => {
+                    l.pop_front();
+                    l.retain(|&y| y != x);
+                    if !model.is_empty() {
+                        model.remove(0);
+                    }
+                    model.retain(|&y| y != x);
+                }
+            }
+            assert!(l.iter().eq(&model));
+            assert_eq!(l.validate(), Ok(()));
+        }
+        for (i, v) in model.iter().enumerate() {
+            assert_eq!(l.get(i), Some(v));
+        }
+    }
+
+    #[test]
+    fn append_past_tail() {
+        use std::cell::Cell;
+
+        fn check<C>(l: &mut SkipList<i32, 8, C>) {
+            for level in 0..8 {
+                let mut last = l.head.0;
+                // SAFETY: links only point to valid nodes of the list
+                while let Some(next) = unsafe { SkipListNode::tower(last) }[level].next {
+                    last = next;
+                }
+                assert_eq!(l.rightmost[level], last, "level {}", level);
+            }
+        }
+
+        // every append compares against the tail only, even with the finger at the front
+        let compares = Cell::new(0);
+        let mut l = SkipList::<i32, 8, _>::with_comparator(|a: &i32, b: &i32| {
+            compares.set(compares.get() + 1);
+            a.cmp(b)
+        });
+        l.insert(0);
+        for i in 1..5000 {
+            l.remove(&-1);
+            let before = compares.get();
+            l.insert(i);
+            assert_eq!(compares.get() - before, 1);
+        }
+        check(&mut l);
+
+        let mut l = SkipList::<i32, 8>::from_sorted_iter(0..100);
+        check(&mut l);
+        l.pop_back();
+        l.remove(&98);
+        check(&mut l);
+        l.remove_range(90..);
+        check(&mut l);
+        let mut other = l.split_off(&50);
+        check(&mut l);
+        check(&mut other);
+        other.extend(200..300);
+        l.extend(100..150);
+        check(&mut other);
+        l.append(other);
+        check(&mut l);
+        l.retain(|x| x % 7 != 0);
+        l.compact();
+        check(&mut l);
+        assert!(l.iter().copied().eq((0..50).chain(50..90).chain(100..150).chain(200..300).filter(|x| x % 7 != 0)));
+        l.clear();
+        check(&mut l);
+        l.extend([5, 1, 9]);
+        check(&mut l);
+        assert!(l.iter().eq(&[1, 5, 9]));
+    }
+
+    #[test]
+    fn validate() {
+        use crate::{InvariantError, Level};
+
+        let mut l = SkipList::<i32, 8>::from_sorted_iter(0..100);
+        assert_eq!(l.validate(), Ok(()));
+
+        l.len += 1;
+        assert_eq!(l.validate(), Err(InvariantError::Len { expected: 101, found: 100 }));
+        l.len -= 1;
+
+        *l.iter_mut().nth(10).unwrap() = 50;
+        assert_eq!(l.validate(), Err(InvariantError::Unsorted { level: 0, index: 11 }));
+        *l.iter_mut().nth(10).unwrap() = 10;
+
+        l.head.tower_mut()[0].width += 1;
+        assert_eq!(l.validate(), Err(InvariantError::Width { level: 0, index: None }));
+        l.head.tower_mut()[0].width -= 1;
+
+        // skip a level 1 node on level 2, which it isn't on
+        let node = l.head.tower()[1].next.unwrap();
+        let saved = l.head.tower()[2];
+        l.head.tower_mut()[2] = Level { next: Some(node), width: 1 };
+        assert_eq!(l.validate(), Err(InvariantError::NotSubsequence { level: 2, index: None }));
+        l.head.tower_mut()[2] = saved;
+
+        let mut first = l.head.tower()[0].next.unwrap();
+        // SAFETY: first is the first node of the list, whose prev is restored right after
+        let prev = unsafe { first.as_mut().prev.replace(first) };
+        assert_eq!(l.validate(), Err(InvariantError::BrokenPrev { index: 0 }));
+        unsafe { first.as_mut().prev = prev };
+        assert_eq!(l.validate(), Ok(()));
+
+        l.remove(&50);
+        l.finger.as_mut().unwrap()[0] = l.rightmost[0];
+        assert_eq!(l.validate(), Err(InvariantError::Finger { level: 1 }));
+        l.finger = None;
+        assert!(InvariantError::Height.to_string().contains("height"));
+        assert_eq!(l.validate(), Ok(()));
+    }
+}
+
+// Small workloads over every path that walks raw links, sized to run under Miri with stacked
+// borrows in a few seconds with `cargo +nightly miri test --lib miri`. The miri workflow runs the
+// whole lib suite
+#[cfg(test)]
+mod miri {
+    use crate::SkipList;
+
+    #[test]
+    fn insert_remove_iterate() {
+        let mut l = SkipList::<u32, 4>::new();
+        for i in (0..40).map(|i| i * 7 % 40) {
+            l.insert(i);
+        }
+        assert!(l.iter().copied().eq(0..40));
+        assert!(l.iter().rev().copied().eq((0..40).rev()));
+
+        for i in (0..40).step_by(3) {
+            assert!(l.remove(&i).is_some());
+        }
+        assert_eq!(l.remove_at(0), Some(1));
+        assert_eq!(l.pop_front(), Some(2));
+        assert_eq!(l.pop_back(), Some(38));
+        for x in l.iter_mut() {
+            *x *= 2;
+        }
+        assert!(l.iter().copied().eq((4..38).filter(|i| i % 3 != 0).map(|i| i * 2)));
+        assert_eq!(l.validate(), Ok(()));
+    }
+
+    #[test]
+    fn cursors_and_bulk_operations() {
+        let mut l = SkipList::<u32, 4>::from_sorted_iter(0..20);
+        let mut cursor = l.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(1));
+        cursor.insert_before(1).unwrap();
+
+        let mut tail = l.split_off(&10);
+        l.extract_if(|x| x % 2 == 0).for_each(drop);
+        tail.append(SkipList::from_sorted_iter(20..25));
+        l.append(tail);
+        l.extend([0, 2, 4]);
+        l.compact();
+        assert!(l.iter().copied().eq([0, 1, 2, 3, 4, 5, 7, 9].iter().copied().chain(10..25)));
+
+        let clone = l.clone();
+        l.clear();
+        assert!(l.is_empty());
+        assert_eq!(clone.len(), 23);
+        assert_eq!(clone.validate(), Ok(()));
+    }
+}
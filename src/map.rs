@@ -0,0 +1,671 @@
+use core::borrow::Borrow;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use core::mem;
+use core::ptr::NonNull;
+
+use crate::{SkipList, SkipListNode};
+
+#[derive(Clone)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub(crate) struct MapEntry<K, V> {
+    pub(crate) key: K,
+    pub(crate) value: V,
+}
+
+/// An ordered map built on the same nodes as `SkipList`. Entries are ordered by key only,
+/// and every key appears at most once.
+pub struct SkipListMap<K, V, const NUM_LEVELS: usize> {
+    pub(crate) list: SkipList<MapEntry<K, V>, NUM_LEVELS>,
+}
+
+impl<K, V, const NUM_LEVELS: usize> SkipListMap<K, V, NUM_LEVELS> {
+    pub fn new() -> Self {
+        SkipListMap { list: SkipList::new() }
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns true if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Removes all entries from the map.
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// Retains only the entries for which `f` returns true, letting `f` change the values it
+    /// keeps along the way.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        // the last kept node at every level, which are the predecessors of the next node
+        let mut path = [self.list.head.0; NUM_LEVELS];
+        let mut next = self.list.head.tower()[0].next;
+        while let Some(mut node) = next {
+            // SAFETY: If a link is Some, it points to a SkipListNode, which nothing else borrows
+            next = unsafe { SkipListNode::tower(node) }[0].next;
+            let node_ref = unsafe { node.as_mut() };
+
+            let keep = node_ref.val.as_mut().is_some_and(|entry| f(&entry.key, &mut entry.value));
+            if keep {
+                path[..=node_ref.level].fill(node);
+            } else {
+                // SAFETY: path holds the last kept node at every level, which are node's predecessors
+                drop(unsafe { self.list.unlink(node, &path) });
+            }
+        }
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V, NUM_LEVELS> {
+        Iter { inner: self.list.iter() }
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key, with mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, NUM_LEVELS> {
+        IterMut { inner: self.list.iter_mut() }
+    }
+
+    /// Returns an iterator over the keys of the map, in sorted order.
+    pub fn keys(&self) -> Keys<'_, K, V, NUM_LEVELS> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Returns an iterator over the values of the map, sorted by key.
+    pub fn values(&self) -> Values<'_, K, V, NUM_LEVELS> {
+        Values { inner: self.iter() }
+    }
+
+    /// Returns an iterator over mutable references to the values of the map, sorted by key.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, NUM_LEVELS> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+}
+
+impl<K: Ord, V, const NUM_LEVELS: usize> SkipListMap<K, V, NUM_LEVELS> {
+    /// Inserts `value` under `key`. If the key was already present, its value is replaced
+    /// and the old one is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let path = self.list.find_path_mut(|next| next.val().is_some_and(|entry| entry.key < key));
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        if let Some(mut next) = unsafe { SkipListNode::tower(path[0])[0].next } {
+            if let Some(entry) = unsafe { next.as_mut() }.val.as_mut().filter(|entry| entry.key == key) {
+                return Some(mem::replace(&mut entry.value, value));
+            }
+        }
+
+        let level = self.list.gen_level();
+        // SAFETY: path holds the last node with a smaller key at every level, and no node has this key
+        unsafe { self.list.link(MapEntry { key, value }, level, &path) };
+        None
+    }
+
+    /// Returns the entry for `key`, for in-place manipulation with a single search.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, NUM_LEVELS> {
+        let path = self.list.find_path_mut(|next| next.val().is_some_and(|entry| entry.key < key));
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        let next = unsafe { SkipListNode::tower(path[0])[0].next };
+        match next.filter(|next| unsafe { next.as_ref() }.val().is_some_and(|entry| entry.key == key)) {
+            Some(node) => Entry::Occupied(OccupiedEntry { map: self, path, node }),
+            None => Entry::Vacant(VacantEntry { map: self, path, key }),
+        }
+    }
+
+    fn find_entry<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&MapEntry<K, V>>
+    where
+        K: Borrow<Q>,
+    {
+        let node = self.list.find_node_ref(|entry| entry.key.borrow() < key).next(0)?;
+        node.val().filter(|entry| entry.key.borrow() == key)
+    }
+
+    /// Returns a reference to the value stored under `key`.
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.find_entry(key).map(|entry| &entry.value)
+    }
+
+    /// Returns the stored key and value for `key`. Useful when equal keys aren't identical.
+    pub fn get_key_value<Q: Ord + ?Sized>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        self.find_entry(key).map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Returns a mutable reference to the value stored under `key`.
+    pub fn get_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        let node = self.list.find_node_ref(|entry| entry.key.borrow() < key).next(0)?.ptr();
+        // SAFETY: node is a node in the list, which is borrowed mutably
+        unsafe { &mut *node.as_ptr() }.val.as_mut().filter(|entry| entry.key.borrow() == key).map(|entry| &mut entry.value)
+    }
+
+    /// Returns true if the map contains an entry for `key`.
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Removes the entry for `key` from the map and returns its value.
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let path = self.list.find_path_mut(|next| next.val().is_some_and(|entry| entry.key.borrow() < key));
+
+        // SAFETY: path[0] is the head or a node in the list, and a link is Some iff it points to a valid SkipListNode
+        let target = unsafe { SkipListNode::tower(path[0])[0].next? };
+        if unsafe { target.as_ref().val() }.is_none_or(|entry| entry.key.borrow() != key) {
+            return None;
+        }
+
+        // SAFETY: target is the first node whose key isn't smaller, so path[level] is its predecessor at every level it occupies
+        Some(unsafe { self.list.unlink(target, &path) }.value)
+    }
+}
+
+impl<K, V, const NUM_LEVELS: usize> Default for SkipListMap<K, V, NUM_LEVELS> {
+    fn default() -> Self {
+        SkipListMap::new()
+    }
+}
+
+impl<K: Clone, V: Clone, const NUM_LEVELS: usize> Clone for SkipListMap<K, V, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        SkipListMap { list: self.list.clone() }
+    }
+}
+
+impl<K: Debug, V: Debug, const NUM_LEVELS: usize> Debug for SkipListMap<K, V, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+type Path<K, V, const NUM_LEVELS: usize> = [NonNull<SkipListNode<MapEntry<K, V>, NUM_LEVELS>>; NUM_LEVELS];
+
+/// A view into a single entry of a `SkipListMap`, which may be vacant or occupied.
+///
+/// Created by [`SkipListMap::entry`].
+pub enum Entry<'a, K, V, const NUM_LEVELS: usize> {
+    Vacant(VacantEntry<'a, K, V, NUM_LEVELS>),
+    Occupied(OccupiedEntry<'a, K, V, NUM_LEVELS>),
+}
+
+/// A view into a vacant entry of a `SkipListMap`. It remembers where the key belongs, so
+/// inserting doesn't search the list again.
+pub struct VacantEntry<'a, K, V, const NUM_LEVELS: usize> {
+    map: &'a mut SkipListMap<K, V, NUM_LEVELS>,
+    path: Path<K, V, NUM_LEVELS>,
+    key: K,
+}
+
+/// A view into an occupied entry of a `SkipListMap`.
+pub struct OccupiedEntry<'a, K, V, const NUM_LEVELS: usize> {
+    map: &'a mut SkipListMap<K, V, NUM_LEVELS>,
+    path: Path<K, V, NUM_LEVELS>,
+    node: NonNull<SkipListNode<MapEntry<K, V>, NUM_LEVELS>>,
+}
+
+// SAFETY: path and node only point into the map the entry borrows mutably, so the entries are
+// as thread-safe as that borrow
+unsafe impl<'a, K: Send, V: Send, const NUM_LEVELS: usize> Send for VacantEntry<'a, K, V, NUM_LEVELS> {}
+unsafe impl<'a, K: Sync, V: Sync, const NUM_LEVELS: usize> Sync for VacantEntry<'a, K, V, NUM_LEVELS> {}
+unsafe impl<'a, K: Send, V: Send, const NUM_LEVELS: usize> Send for OccupiedEntry<'a, K, V, NUM_LEVELS> {}
+unsafe impl<'a, K: Sync, V: Sync, const NUM_LEVELS: usize> Sync for OccupiedEntry<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Entry<'a, K, V, NUM_LEVELS> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Vacant(entry) => entry.key(),
+            Entry::Occupied(entry) => entry.key(),
+        }
+    }
+
+    /// Inserts `default` if the entry is vacant, and returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, and returns a mutable reference to the value.
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    /// Inserts the result of `default` called with the key if the entry is vacant, and returns
+    /// a mutable reference to the value.
+    pub fn or_insert_with_key(self, default: impl FnOnce(&K) -> V) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    /// Calls `f` on the value if the entry is occupied, then returns the entry.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V: Default, const NUM_LEVELS: usize> Entry<'a, K, V, NUM_LEVELS> {
+    /// Inserts the default value if the entry is vacant, and returns a mutable reference to the value.
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> VacantEntry<'a, K, V, NUM_LEVELS> {
+    /// Returns the key that would be used when inserting.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts `value` under the entry's key and returns a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let level = self.map.list.gen_level();
+        // SAFETY: path was found by the search for key, and the map hasn't been touched since
+        let mut node = unsafe { self.map.list.link(MapEntry { key: self.key, value }, level, &self.path) };
+        // SAFETY: node was just linked into the map, which stays mutably borrowed for 'a
+        &mut unsafe { node.as_mut() }.val.as_mut().unwrap().value
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> OccupiedEntry<'a, K, V, NUM_LEVELS> {
+    fn entry(&self) -> &MapEntry<K, V> {
+        // SAFETY: node is a non-head node in the map
+        unsafe { self.node.as_ref() }.val().unwrap()
+    }
+
+    fn entry_mut(&mut self) -> &mut MapEntry<K, V> {
+        // SAFETY: node is a non-head node in the map, which is mutably borrowed
+        unsafe { self.node.as_mut() }.val.as_mut().unwrap()
+    }
+
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        &self.entry().key
+    }
+
+    /// Returns a reference to the value of this entry.
+    pub fn get(&self) -> &V {
+        &self.entry().value
+    }
+
+    /// Returns a mutable reference to the value of this entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.entry_mut().value
+    }
+
+    /// Converts the entry into a mutable reference to its value, bound to the map's lifetime.
+    pub fn into_mut(mut self) -> &'a mut V {
+        // SAFETY: node is a non-head node in the map, which stays mutably borrowed for 'a
+        &mut unsafe { self.node.as_mut() }.val.as_mut().unwrap().value
+    }
+
+    /// Replaces the value of this entry and returns the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry from the map and returns its key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        // SAFETY: node is the first node with this key, so path[level] is its predecessor at every level it occupies
+        let entry = unsafe { self.map.list.unlink(self.node, &self.path) };
+        (entry.key, entry.value)
+    }
+
+    /// Removes the entry from the map and returns its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+/// An iterator over the entries of a `SkipListMap`, sorted by key.
+///
+/// Created by [`SkipListMap::iter`].
+pub struct Iter<'a, K, V, const NUM_LEVELS: usize> {
+    inner: crate::Iter<'a, MapEntry<K, V>, NUM_LEVELS>,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for Iter<'a, K, V, NUM_LEVELS> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| (&entry.key, &entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for Iter<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> ExactSizeIterator for Iter<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for Iter<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> IntoIterator for &'a SkipListMap<K, V, NUM_LEVELS> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A mutable iterator over the entries of a `SkipListMap`, sorted by key.
+///
+/// Created by [`SkipListMap::iter_mut`].
+pub struct IterMut<'a, K, V, const NUM_LEVELS: usize> {
+    inner: crate::IterMut<'a, MapEntry<K, V>, NUM_LEVELS>,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for IterMut<'a, K, V, NUM_LEVELS> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| (&entry.key, &mut entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for IterMut<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|entry| (&entry.key, &mut entry.value))
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> ExactSizeIterator for IterMut<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for IterMut<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> IntoIterator for &'a mut SkipListMap<K, V, NUM_LEVELS> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator over the keys of a `SkipListMap`, in sorted order.
+///
+/// Created by [`SkipListMap::keys`].
+pub struct Keys<'a, K, V, const NUM_LEVELS: usize> {
+    inner: Iter<'a, K, V, NUM_LEVELS>,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for Keys<'a, K, V, NUM_LEVELS> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for Keys<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> ExactSizeIterator for Keys<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for Keys<'a, K, V, NUM_LEVELS> {}
+
+/// An iterator over the values of a `SkipListMap`, sorted by key.
+///
+/// Created by [`SkipListMap::values`].
+pub struct Values<'a, K, V, const NUM_LEVELS: usize> {
+    inner: Iter<'a, K, V, NUM_LEVELS>,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for Values<'a, K, V, NUM_LEVELS> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for Values<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> ExactSizeIterator for Values<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for Values<'a, K, V, NUM_LEVELS> {}
+
+/// A mutable iterator over the values of a `SkipListMap`, sorted by key.
+///
+/// Created by [`SkipListMap::values_mut`].
+pub struct ValuesMut<'a, K, V, const NUM_LEVELS: usize> {
+    inner: IterMut<'a, K, V, NUM_LEVELS>,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for ValuesMut<'a, K, V, NUM_LEVELS> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for ValuesMut<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> ExactSizeIterator for ValuesMut<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for ValuesMut<'a, K, V, NUM_LEVELS> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, SkipListMap};
+
+    #[test]
+    fn insert_get_remove() {
+        let mut m = SkipListMap::<i32, String, 8>::new();
+        let mut keys: Vec<i32> = (0..100).collect();
+        fastrand::shuffle(keys.as_mut());
+        for &k in keys.iter() {
+            assert_eq!(m.insert(k, k.to_string()), None);
+        }
+        assert_eq!(m.len(), 100);
+
+        for k in 0..100 {
+            assert_eq!(m.get(&k), Some(&k.to_string()));
+        }
+        assert_eq!(m.get(&100), None);
+        assert!(!m.contains_key(&-1));
+
+        assert_eq!(m.insert(5, "five".to_string()), Some("5".to_string()));
+        assert_eq!(m.len(), 100);
+        m.get_mut(&6).unwrap().push('!');
+        assert_eq!(m.get(&5).map(String::as_str), Some("five"));
+        assert_eq!(m.get(&6).map(String::as_str), Some("6!"));
+        assert_eq!(m.get_mut(&1000), None);
+
+        for k in (0..100).step_by(2) {
+            assert!(m.remove(&k).is_some());
+        }
+        assert_eq!(m.remove(&0), None);
+        assert_eq!(m.len(), 50);
+        assert!(m.iter().map(|(k, _)| *k).eq((1..100).step_by(2)));
+    }
+
+    #[test]
+    fn iter_and_debug() {
+        let mut m = SkipListMap::<&str, usize, 4>::default();
+        for (i, k) in ["c", "a", "b"].iter().enumerate() {
+            m.insert(*k, i);
+        }
+
+        assert_eq!(m.iter().collect::<Vec<_>>(), [(&"a", &1), (&"b", &2), (&"c", &0)]);
+        assert_eq!(m.iter().next_back(), Some((&"c", &0)));
+        assert_eq!(format!("{:?}", m), r#"{"a": 1, "b": 2, "c": 0}"#);
+
+        let c = m.clone();
+        m.clear();
+        assert!(m.is_empty());
+        assert_eq!((&c).into_iter().len(), 3);
+    }
+
+    #[test]
+    fn entry() {
+        let mut counts = SkipListMap::<char, usize, 8>::new();
+        for c in "hello world".chars() {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        assert_eq!(counts.get(&'l'), Some(&3));
+        assert_eq!(counts.get(&'o'), Some(&2));
+        assert_eq!(counts.len(), 8);
+
+        counts.entry('h').and_modify(|n| *n += 10).or_default();
+        counts.entry('z').and_modify(|n| *n += 10).or_default();
+        assert_eq!(counts.get(&'h'), Some(&11));
+        assert_eq!(counts.get(&'z'), Some(&0));
+        assert_eq!(*counts.entry('q').or_insert_with_key(|k| *k as usize), 'q' as usize);
+
+        match counts.entry('l') {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &'l');
+                assert_eq!(entry.insert(7), 3);
+                assert_eq!(entry.remove_entry(), ('l', 7));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(!counts.contains_key(&'l'));
+
+        match counts.entry('l') {
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 'l'),
+            Entry::Occupied(_) => unreachable!(),
+        }
+        assert!(counts.iter().map(|(k, _)| *k).eq(" dehoqrwz".chars()));
+    }
+
+    #[test]
+    fn projections() {
+        let mut m = SkipListMap::<i32, i32, 8>::new();
+        for k in [3, 1, 2, 0] {
+            m.insert(k, k * 10);
+        }
+
+        assert!(m.keys().copied().eq(0..4));
+        assert!(m.values().copied().eq([0, 10, 20, 30]));
+        assert_eq!(m.keys().next_back(), Some(&3));
+        assert_eq!(m.values().len(), 4);
+
+        for v in m.values_mut() {
+            *v += 1;
+        }
+        for (k, v) in &mut m {
+            *v += k;
+        }
+        *m.iter_mut().next_back().unwrap().1 = 0;
+        assert!(m.values().copied().eq([1, 12, 23, 0]));
+    }
+
+    #[test]
+    fn get_key_value() {
+        #[derive(Debug)]
+        struct Key(i32, &'static str);
+
+        impl PartialEq for Key {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Key {}
+        impl PartialOrd for Key {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Key {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut m = SkipListMap::<Key, u8, 8>::new();
+        m.insert(Key(1, "stored"), 10);
+        let (k, v) = m.get_key_value(&Key(1, "probe")).unwrap();
+        assert_eq!((k.1, *v), ("stored", 10));
+        assert!(m.get_key_value(&Key(2, "probe")).is_none());
+
+        *m.get_mut(&Key(1, "probe")).unwrap() += 1;
+        assert_eq!(m.get(&Key(1, "")), Some(&11));
+    }
+
+    #[test]
+    fn retain() {
+        let mut m: SkipListMap<i32, i32, 8> = SkipListMap::new();
+        for k in 0..20 {
+            m.insert(k, k);
+        }
+        m.retain(|&k, v| {
+            *v *= 10;
+            k % 3 == 0
+        });
+        assert!(m.iter().map(|(&k, &v)| (k, v)).eq((0..20).step_by(3).map(|k| (k, k * 10))));
+        assert_eq!(m.get(&4), None);
+        assert_eq!(m.get(&18), Some(&180));
+        m.insert(4, 4);
+        assert_eq!(m.len(), 8);
+        m.retain(|_, _| false);
+        assert!(m.is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use core::mem;
+use alloc::vec::Vec;
+use alloc::vec;
+
+use crate::{Allocator, SkipList, SkipListNode};
+
+/// How much memory a `SkipList` takes, as reported by [`SkipList::memory_usage`].
+///
+/// Only the list's own allocations are counted. Memory the elements own themselves, like the
+/// buffer of a `String`, isn't.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes taken by the nodes holding the elements, towers included.
+    pub node_bytes: usize,
+    /// Bytes taken by the head, which has a tower reaching every level.
+    pub head_bytes: usize,
+    /// Bytes taken by removed nodes kept for reuse by
+    /// [`with_free_list`](SkipList::with_free_list).
+    pub free_list_bytes: usize,
+    /// Bytes of every arena chunk the list keeps alive, used or not, if it was built with
+    /// [`with_arena`](SkipList::with_arena). The nodes of such a list live in these chunks, and
+    /// chunks shared with lists split off from it are counted in full by each of them.
+    pub arena_bytes: usize,
+    /// Bytes taken by the elements themselves, which is `len * size_of::<T>()`.
+    pub element_bytes: usize,
+    /// The number of nodes linked into each level, from level 0 up to the list's height. Level
+    /// 0 holds every node.
+    pub nodes_per_level: Vec<usize>,
+}
+
+impl MemoryUsage {
+    /// Returns the number of bytes the list holds on to.
+    pub fn total_bytes(&self) -> usize {
+        if self.arena_bytes > 0 {
+            self.head_bytes + self.arena_bytes
+        } else {
+            self.head_bytes + self.node_bytes + self.free_list_bytes
+        }
+    }
+
+    /// Returns how many bytes the list holds on to for every byte of element, beyond that byte.
+    /// This is 0 for an empty list, and infinite for a list of zero-sized elements.
+    pub fn overhead_ratio(&self) -> f64 {
+        let overhead = self.total_bytes() - self.element_bytes;
+        match self.element_bytes {
+            0 if self.nodes_per_level.first().is_none_or(|&len| len == 0) => 0.0,
+            element_bytes => overhead as f64 / element_bytes as f64,
+        }
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Reports how much memory the list takes, in O(n).
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut nodes_per_level = vec![0; self.height];
+        let mut node_bytes = 0;
+        for node in self.iter_nodes() {
+            nodes_per_level[..=node.level].iter_mut().for_each(|count| *count += 1);
+            node_bytes += SkipListNode::<T, NUM_LEVELS>::layout(node.level).size();
+        }
+
+        let mut free_list_bytes = 0;
+        for (level, free) in self.free.iter().enumerate() {
+            let cached = core::iter::successors(*free, |node| {
+                // SAFETY: cached nodes are owned by the list and chained through their level 0 links
+                unsafe { SkipListNode::tower(*node)[0].next }
+            });
+            free_list_bytes += cached.count() * SkipListNode::<T, NUM_LEVELS>::layout(level).size();
+        }
+
+        MemoryUsage {
+            node_bytes,
+            head_bytes: SkipListNode::<T, NUM_LEVELS>::layout(NUM_LEVELS - 1).size(),
+            free_list_bytes,
+            arena_bytes: self.arena.as_ref().map_or(0, |arena| arena.capacity_bytes()),
+            element_bytes: self.len * mem::size_of::<T>(),
+            nodes_per_level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Level, SkipList, SkipListNode};
+
+    #[test]
+    fn memory_usage() {
+        let empty = SkipList::<u64, 16>::new().memory_usage();
+        assert_eq!(empty.node_bytes, 0);
+        assert_eq!(empty.nodes_per_level, vec![0]);
+        assert_eq!(empty.total_bytes(), empty.head_bytes);
+        assert_eq!(empty.overhead_ratio(), 0.0);
+
+        let mut l = SkipList::<u64, 16>::from_sorted_iter(0..1_000);
+        let usage = l.memory_usage();
+        assert_eq!(usage.nodes_per_level[..4], [1_000, 500, 250, 125]);
+        assert_eq!(usage.nodes_per_level.len(), 10);
+        let tower_bytes = usage.nodes_per_level.iter().sum::<usize>() * std::mem::size_of::<Level<u64, 16>>();
+        assert_eq!(usage.node_bytes, 1_000 * std::mem::size_of::<SkipListNode<u64, 16>>() + tower_bytes);
+        assert_eq!(usage.element_bytes, 8_000);
+        assert!(usage.overhead_ratio() > 1.0);
+
+        let mut cached = SkipList::<u64, 16>::from_sorted_iter(0..10).with_free_list(4);
+        cached.remove_range(..5);
+        assert_eq!(cached.memory_usage().nodes_per_level[0], 5);
+        assert!(cached.memory_usage().free_list_bytes > 0);
+
+        let mut arena = SkipList::<u64, 16>::new().with_arena();
+        arena.extend(0..1_000);
+        let usage = arena.memory_usage();
+        assert!(usage.arena_bytes >= usage.node_bytes);
+        assert_eq!(usage.total_bytes(), usage.head_bytes + usage.arena_bytes);
+        l.clear();
+        assert_eq!(l.memory_usage(), empty);
+    }
+}
@@ -0,0 +1,247 @@
+use core::borrow::Borrow;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use core::mem;
+
+use crate::map::{self, Entry, SkipListMap};
+
+/// The in-memory write buffer of a log-structured merge tree: a sorted map that records
+/// deletes as tombstones and keeps a running estimate of its size.
+///
+/// A delete can't just remove a key, since older tables on disk may still hold it. Instead it
+/// stores a tombstone that shadows them, which [`get`](MemTable::get) reports apart from a key
+/// the table knows nothing about. Once [`approximate_size`](MemTable::approximate_size) passes
+/// some limit, [`freeze`](MemTable::freeze) the table, flush its entries in sorted order, and
+/// start a new one.
+pub struct MemTable<K, V, const NUM_LEVELS: usize = 16> {
+    map: SkipListMap<K, Option<V>, NUM_LEVELS>,
+    size: usize,
+    entry_size: fn(&K, Option<&V>) -> usize,
+}
+
+impl<K, V, const NUM_LEVELS: usize> MemTable<K, V, NUM_LEVELS> {
+    /// Creates an empty table that estimates an entry's size as the size of its key and value
+    /// themselves, which leaves out anything they own on the heap.
+    pub fn new() -> Self {
+        MemTable::with_size_fn(|_, _| mem::size_of::<K>() + mem::size_of::<Option<V>>())
+    }
+
+    /// Creates an empty table that estimates an entry's size with `entry_size`, which gets the
+    /// value of the entry, or None for a tombstone. For byte string keys and values,
+    /// `|k, v| k.len() + v.map_or(0, Vec::len)` is a good start.
+    pub fn with_size_fn(entry_size: fn(&K, Option<&V>) -> usize) -> Self {
+        MemTable { map: SkipListMap::new(), size: 0, entry_size }
+    }
+
+    /// Returns the number of entries in the table, tombstones included.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if the table holds neither values nor tombstones.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the sum of the estimated sizes of the table's entries.
+    pub fn approximate_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns an iterator over the entries of the table, sorted by key, with None for the
+    /// value of a tombstone.
+    pub fn iter(&self) -> Iter<'_, K, V, NUM_LEVELS> {
+        Iter { inner: self.map.iter() }
+    }
+
+    /// Makes the table read-only, ready to be flushed.
+    pub fn freeze(self) -> FrozenMemTable<K, V, NUM_LEVELS> {
+        FrozenMemTable { table: self }
+    }
+}
+
+impl<K: Ord, V, const NUM_LEVELS: usize> MemTable<K, V, NUM_LEVELS> {
+    /// Stores `value` under `key`, replacing any value or tombstone already there.
+    pub fn put(&mut self, key: K, value: V) {
+        self.set(key, Some(value));
+    }
+
+    /// Stores a tombstone for `key`, replacing any value already there. The tombstone is stored
+    /// even if the table doesn't have the key, to hide it in older tables.
+    pub fn delete(&mut self, key: K) {
+        self.set(key, None);
+    }
+
+    fn set(&mut self, key: K, value: Option<V>) {
+        let size = (self.entry_size)(&key, value.as_ref());
+        match self.map.entry(key) {
+            Entry::Occupied(mut entry) => {
+                self.size -= (self.entry_size)(entry.key(), entry.get().as_ref());
+                entry.insert(value);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+        self.size += size;
+    }
+
+    /// Looks `key` up. Returns `Some(Some(value))` if the table has a value for it,
+    /// `Some(None)` if it has a tombstone, and None if it has neither, in which case older
+    /// tables need to be searched.
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<Option<&V>>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.get(key).map(Option::as_ref)
+    }
+}
+
+impl<K, V, const NUM_LEVELS: usize> Default for MemTable<K, V, NUM_LEVELS> {
+    fn default() -> Self {
+        MemTable::new()
+    }
+}
+
+impl<K: Debug, V: Debug, const NUM_LEVELS: usize> Debug for MemTable<K, V, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> IntoIterator for &'a MemTable<K, V, NUM_LEVELS> {
+    type Item = (&'a K, Option<&'a V>);
+    type IntoIter = Iter<'a, K, V, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A [`MemTable`] that can't be changed anymore, made by [`MemTable::freeze`]. It can still
+/// be read from, and shared between threads, while its entries are being flushed.
+pub struct FrozenMemTable<K, V, const NUM_LEVELS: usize = 16> {
+    table: MemTable<K, V, NUM_LEVELS>,
+}
+
+impl<K, V, const NUM_LEVELS: usize> FrozenMemTable<K, V, NUM_LEVELS> {
+    /// Returns the number of entries in the table, tombstones included.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns true if the table holds neither values nor tombstones.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns the sum of the estimated sizes of the table's entries.
+    pub fn approximate_size(&self) -> usize {
+        self.table.approximate_size()
+    }
+
+    /// Returns an iterator over the entries of the table, sorted by key, with None for the
+    /// value of a tombstone.
+    pub fn iter(&self) -> Iter<'_, K, V, NUM_LEVELS> {
+        self.table.iter()
+    }
+}
+
+impl<K: Ord, V, const NUM_LEVELS: usize> FrozenMemTable<K, V, NUM_LEVELS> {
+    /// Looks `key` up, like [`MemTable::get`].
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<Option<&V>>
+    where
+        K: Borrow<Q>,
+    {
+        self.table.get(key)
+    }
+}
+
+impl<K: Debug, V: Debug, const NUM_LEVELS: usize> Debug for FrozenMemTable<K, V, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.table.fmt(f)
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> IntoIterator for &'a FrozenMemTable<K, V, NUM_LEVELS> {
+    type Item = (&'a K, Option<&'a V>);
+    type IntoIter = Iter<'a, K, V, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `MemTable`, sorted by key.
+///
+/// Created by [`MemTable::iter`] and [`FrozenMemTable::iter`].
+pub struct Iter<'a, K, V, const NUM_LEVELS: usize> {
+    inner: map::Iter<'a, K, Option<V>, NUM_LEVELS>,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for Iter<'a, K, V, NUM_LEVELS> {
+    type Item = (&'a K, Option<&'a V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| (key, value.as_ref()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for Iter<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, value)| (key, value.as_ref()))
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> ExactSizeIterator for Iter<'a, K, V, NUM_LEVELS> {}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for Iter<'a, K, V, NUM_LEVELS> {}
+
+#[cfg(test)]
+mod tests {
+    use super::MemTable;
+
+    #[test]
+    fn put_get_delete() {
+        let mut t = MemTable::<String, u32>::new();
+        t.put("b".to_string(), 2);
+        t.put("a".to_string(), 1);
+        t.delete("c".to_string());
+        t.put("b".to_string(), 20);
+        t.delete("a".to_string());
+
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.get("a"), Some(None));
+        assert_eq!(t.get("b"), Some(Some(&20)));
+        assert_eq!(t.get("c"), Some(None));
+        assert_eq!(t.get("d"), None);
+
+        let frozen = t.freeze();
+        assert_eq!(frozen.get("b"), Some(Some(&20)));
+        let entries: Vec<_> = frozen.iter().map(|(k, v)| (k.as_str(), v.copied())).collect();
+        assert_eq!(entries, [("a", None), ("b", Some(20)), ("c", None)]);
+    }
+
+    #[test]
+    fn approximate_size() {
+        let mut t = MemTable::<Vec<u8>, Vec<u8>>::with_size_fn(|k, v| k.len() + v.map_or(0, Vec::len));
+        assert_eq!(t.approximate_size(), 0);
+        t.put(b"key".to_vec(), b"value".to_vec());
+        assert_eq!(t.approximate_size(), 8);
+        t.put(b"key".to_vec(), b"v".to_vec());
+        assert_eq!(t.approximate_size(), 4);
+        t.delete(b"key".to_vec());
+        t.delete(b"other".to_vec());
+        assert_eq!(t.approximate_size(), 8);
+        assert_eq!(t.freeze().approximate_size(), 8);
+
+        let mut t = MemTable::<u64, u32>::new();
+        t.put(1, 1);
+        t.delete(2);
+        assert_eq!(t.approximate_size(), 2 * (8 + 8));
+    }
+}
@@ -0,0 +1,255 @@
+use std::cmp::Ordering;
+use std::ptr::NonNull;
+
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSlice;
+
+use crate::{Allocator, Comparator, NodeRef, SkipList, SkipListNode};
+
+/// A parallel iterator over the elements of a `SkipList` in sorted order.
+///
+/// Created by [`SkipList::par_iter`], or by `into_par_iter` on a `&SkipList`. The list is split
+/// at nodes reaching its upper levels, so finding where to split never walks the bottom level.
+pub struct ParIter<'a, T, const NUM_LEVELS: usize> {
+    head: NodeRef<'a, T, NUM_LEVELS>,
+    height: usize,
+    len: usize,
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Returns a parallel iterator over the elements of the list in sorted order.
+    pub fn par_iter(&self) -> ParIter<'_, T, NUM_LEVELS>
+    where
+        T: Sync,
+    {
+        ParIter { head: self.head.node(), height: self.height, len: self.len }
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C: Comparator<T>, G> SkipList<T, NUM_LEVELS, C, G> {
+    /// Builds a list from a sorted slice like [`SkipList::from_sorted_iter`], cloning the items
+    /// in parallel.
+    ///
+    /// The slice is cut into chunks whose nodes are allocated and linked on the rayon thread
+    /// pool, then the chunks' towers are stitched together in order, which only touches the
+    /// last node of every level of each chunk. Every node gets the level it would get from
+    /// `from_sorted_iter`, so the result is the same ideally balanced list.
+    pub fn par_from_sorted(items: &[T]) -> Self
+    where
+        T: Clone + Send + Sync,
+        C: Default,
+        G: Default,
+    {
+        let mut list = SkipList::with_level_generator(C::default(), G::default());
+        debug_assert!(
+            items.windows(2).all(|w| list.cmp.compare(&w[0], &w[1]) != Ordering::Greater),
+            "par_from_sorted: items are not sorted"
+        );
+
+        let chunk_len = (items.len() / (rayon::current_num_threads() * 4)).max(1 << 12);
+        let chunks: Vec<Chunk<T, NUM_LEVELS>> = items
+            .par_chunks(chunk_len)
+            .enumerate()
+            .map(|(c, items)| {
+                let mut list = SkipList::with_level_generator((), ());
+                let mut last = [list.head.0; NUM_LEVELS];
+                let mut ranks = [0; NUM_LEVELS];
+                for (i, item) in items.iter().enumerate() {
+                    let level = SkipList::<T, NUM_LEVELS, C, G>::balanced_level(c * chunk_len + i);
+                    // SAFETY: last holds the last node at every level, and items come in sorted order
+                    unsafe { list.push_back_with_level(item.clone(), level, &mut last) };
+                    ranks[..=level].fill(i + 1);
+                }
+                Chunk { list, last, ranks }
+            })
+            .collect();
+
+        let mut path = [list.head.0; NUM_LEVELS];
+        // the position of path[level] in level 0 order, counting the head as 0
+        let mut ranks = [0; NUM_LEVELS];
+        for mut chunk in chunks {
+            for level in 0..NUM_LEVELS {
+                let Some(mut first) = chunk.list.head.tower_mut()[level].next.take() else {
+                    continue;
+                };
+                // SAFETY: path only holds the head or nodes of this list, and first is a node of
+                // the chunk, which gives up all of its nodes
+                unsafe {
+                    let prev = SkipListNode::tower_mut(path[level]);
+                    prev[level].next = Some(first);
+                    prev[level].width = list.len - ranks[level] + chunk.list.head.tower()[level].width;
+                    if level == 0 {
+                        first.as_mut().prev = Some(path[0]);
+                    }
+                }
+                path[level] = chunk.last[level];
+                ranks[level] = list.len + chunk.ranks[level];
+            }
+            list.len += chunk.list.len;
+            list.height = list.height.max(chunk.list.height);
+            chunk.list.len = 0;
+            chunk.list.tail = None;
+        }
+
+        for (level, &last) in path.iter().enumerate() {
+            // SAFETY: path only holds the head or nodes of this list
+            unsafe { SkipListNode::tower_mut(last)[level].width = list.len + 1 - ranks[level] };
+        }
+        // SAFETY: path[0] is the head or the last node of this list
+        list.tail = Some(path[0]).filter(|last| !unsafe { last.as_ref() }.is_head());
+        list.rightmost = path;
+        list
+    }
+}
+
+/// One chunk of a parallel build.
+struct Chunk<T, const NUM_LEVELS: usize> {
+    list: SkipList<T, NUM_LEVELS, (), ()>,
+    // the last node of the chunk at every level, or its head
+    last: [NonNull<SkipListNode<T, NUM_LEVELS>>; NUM_LEVELS],
+    // the position of last[level] in the chunk, counting the head as 0
+    ranks: [usize; NUM_LEVELS],
+}
+
+// SAFETY: last only points into the list, which owns its nodes like a Box
+unsafe impl<T: Send, const NUM_LEVELS: usize> Send for Chunk<T, NUM_LEVELS> {}
+
+impl<'a, T: Sync, const NUM_LEVELS: usize, C, G, A: Allocator> IntoParallelIterator for &'a SkipList<T, NUM_LEVELS, C, G, A> {
+    type Iter = ParIter<'a, T, NUM_LEVELS>;
+    type Item = &'a T;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<'a, T: Sync, const NUM_LEVELS: usize> ParallelIterator for ParIter<'a, T, NUM_LEVELS> {
+    type Item = &'a T;
+
+    fn drive_unindexed<CO>(self, consumer: CO) -> CO::Result
+    where
+        CO: UnindexedConsumer<Self::Item>,
+    {
+        let producer = Producer { pred: self.head, level: self.height - 1, len: self.len };
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+/// The `len` elements right after `pred`, whose tower reaches `level`.
+struct Producer<'a, T, const NUM_LEVELS: usize> {
+    pred: NodeRef<'a, T, NUM_LEVELS>,
+    level: usize,
+    len: usize,
+}
+
+impl<'a, T: Sync, const NUM_LEVELS: usize> UnindexedProducer for Producer<'a, T, NUM_LEVELS> {
+    type Item = &'a T;
+
+    fn split(self) -> (Self, Option<Self>) {
+        // find the furthest node on the highest level that doesn't pass the middle, and split
+        // right before it. The bottom level is left alone, since walking it costs as much as
+        // iterating
+        let half = self.len / 2;
+        for level in (1..=self.level).rev() {
+            let mut node = self.pred;
+            let mut offset = 0;
+            while offset + node.tower()[level].width <= half {
+                offset += node.tower()[level].width;
+                match node.next(level) {
+                    Some(next) => node = next,
+                    None => break,
+                }
+            }
+            if offset > 0 {
+                let left = Producer { pred: self.pred, level, len: offset };
+                let right = Producer { pred: node, level, len: self.len - offset };
+                return (left, Some(right));
+            }
+        }
+        (self, None)
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        let mut node = self.pred;
+        for _ in 0..self.len {
+            match node.next(0) {
+                Some(next) => node = next,
+                None => break,
+            }
+            if let Some(val) = node.val() {
+                folder = folder.consume(val);
+            }
+            if folder.full() {
+                break;
+            }
+        }
+        folder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use crate::SkipList;
+
+    #[test]
+    fn par_iter() {
+        let mut l = SkipList::<u64, 12>::new();
+        assert_eq!(l.par_iter().count(), 0);
+
+        let mut nums: Vec<u64> = (0..10_000).collect();
+        fastrand::shuffle(&mut nums);
+        l.extend(nums);
+        assert_eq!(l.par_iter().sum::<u64>(), (0..10_000u64).sum::<u64>());
+        assert_eq!((&l).into_par_iter().count(), 10_000);
+        let collected: Vec<u64> = l.par_iter().copied().collect();
+        assert!(collected.iter().copied().eq(0..10_000));
+        assert_eq!(l.par_iter().find_first(|&&x| x > 500), Some(&501));
+
+        l.retain(|&x| x % 3 == 0);
+        assert!(l.par_iter().copied().collect::<Vec<_>>().into_iter().eq((0..10_000).step_by(3)));
+    }
+
+    #[test]
+    fn par_from_sorted() {
+        let nums: Vec<i32> = (0..3 * 4096 + 17).collect();
+        let par = SkipList::<i32, 10>::par_from_sorted(&nums);
+        let seq = SkipList::<i32, 10>::from_sorted_iter(nums.iter().copied());
+        assert_eq!(par.len(), nums.len());
+        assert_eq!(par.height, seq.height);
+        assert!(par.iter_nodes().map(|node| node.level).eq(seq.iter_nodes().map(|node| node.level)));
+        assert!(par.iter().rev().eq(nums.iter().rev()));
+        for i in (0..nums.len()).step_by(97) {
+            assert_eq!(par.get(i), Some(&nums[i]));
+        }
+        assert_eq!(par.get(nums.len()), None);
+
+        let mut par = par;
+        par.insert(-1);
+        assert_eq!(par.remove(&4096), Some(4096));
+        assert_eq!(par.get(4097), Some(&4097));
+        assert!(SkipList::<i32, 10>::par_from_sorted(&[]).is_empty());
+        assert!(SkipList::<i32, 10>::par_from_sorted(&[1]).iter().eq([1].iter()));
+    }
+
+    #[test]
+    fn split_at_upper_levels() {
+        use rayon::iter::plumbing::UnindexedProducer;
+
+        let mut l = SkipList::<u32, 8>::new();
+        l.extend(0..1_000);
+        let producer = super::Producer { pred: l.head.node(), level: l.height - 1, len: l.len() };
+        let (left, right) = producer.split();
+        let right = right.unwrap();
+        assert_eq!(left.len + right.len, 1_000);
+        assert!(left.len > 0 && left.len <= 500);
+        // the right half starts right after a node reaching an upper level
+        assert!(right.pred.level >= 1);
+        assert_eq!(right.pred.val(), Some(&(left.len as u32 - 1)));
+    }
+}
@@ -0,0 +1,455 @@
+//! A skip list of byte strings that lives in a memory-mapped file.
+//!
+//! [`PersistentSkipList`] keeps every node in the file itself, linked by file offsets instead of
+//! pointers, so the list works wherever the file happens to be mapped. Opening a list maps the
+//! file and reads its header, and costs the same for a few bytes as for many gigabytes: pages
+//! are only read in as searches touch them.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::iter::FusedIterator;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use crate::level::Seed;
+
+const MAGIC: [u8; 8] = *b"SKIPLST\0";
+const VERSION: u32 = 1;
+const MAX_HEIGHT: usize = 32;
+const INITIAL_LEN: usize = 64 << 10;
+
+/// The start of the file. Offset 0 stands for the head in a search path, and for the end of
+/// the list in a link, since no node can live there.
+#[repr(C)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    height: u32,
+    len: u64,
+    // the end of the last node allocated, where the next one goes
+    used: u64,
+    // the level generator's state, so a reopened list doesn't repeat its levels
+    seed: u64,
+    head: [u64; MAX_HEIGHT],
+}
+
+/// The start of a node, which is followed by `level + 1` links, then the key, then the value,
+/// padded to a multiple of 8 bytes.
+#[repr(C)]
+struct NodeHeader {
+    level: u32,
+    key_len: u32,
+    val_len: u64,
+}
+
+/// A sorted map from byte strings to byte strings, ordered lexicographically, stored in a
+/// memory-mapped file.
+///
+/// Changes are written straight into the mapping and reach the file whenever the OS writes the
+/// pages back, which it does even if the process exits without closing the list. Call
+/// [`flush`](PersistentSkipList::flush) to wait until they're on disk. Nothing makes a change
+/// atomic: a machine crash in the middle of an insert can leave the file corrupt.
+///
+/// Removing or replacing an entry unlinks its node but doesn't reclaim its space, so a file
+/// only ever grows.
+pub struct PersistentSkipList {
+    file: File,
+    map: NonNull<u8>,
+    map_len: usize,
+}
+
+// SAFETY: the list owns its mapping, and only changes it through &mut self
+unsafe impl Send for PersistentSkipList {}
+unsafe impl Sync for PersistentSkipList {}
+
+impl PersistentSkipList {
+    /// Opens the list stored in the file at `path`, creating an empty one if the file doesn't
+    /// exist or is empty.
+    ///
+    /// Fails if the file can't be opened or mapped, or doesn't start with a list header.
+    ///
+    /// # Safety
+    ///
+    /// The file must have been written by this module, and nothing else may change it while
+    /// the list is open, including another `PersistentSkipList`. Links are followed without
+    /// checking them, so a corrupt file can make the list read out of bounds.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let file_len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file too large to map"))?;
+
+        if file_len == 0 {
+            file.set_len(INITIAL_LEN as u64)?;
+            let mut list = PersistentSkipList { map: map(&file, INITIAL_LEN)?, file, map_len: INITIAL_LEN };
+            *list.header_mut() = Header {
+                magic: MAGIC,
+                version: VERSION,
+                height: 0,
+                len: 0,
+                used: mem::size_of::<Header>() as u64,
+                seed: Seed::new().0,
+                head: [0; MAX_HEIGHT],
+            };
+            return Ok(list);
+        }
+
+        if file_len < mem::size_of::<Header>() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "file too short for a skip list header"));
+        }
+        let list = PersistentSkipList { map: map(&file, file_len)?, file, map_len: file_len };
+        let header = list.header();
+        if header.magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a skip list file"));
+        }
+        if header.version != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported skip list file version"));
+        }
+        if header.used > file_len as u64 || header.height as usize > MAX_HEIGHT {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt skip list header"));
+        }
+        Ok(list)
+    }
+
+    /// Returns the number of entries in the list.
+    pub fn len(&self) -> usize {
+        self.header().len as usize
+    }
+
+    /// Returns true if the list contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value stored under `key`.
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        let node = self.next(self.find_path(key)[0], 0);
+        (node != 0 && self.key(node) == key).then(|| self.value(node))
+    }
+
+    /// Returns true if the list has an entry for `key`.
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Stores `value` under `key`, replacing any value already there. Returns true if the key
+    /// wasn't in the list before.
+    ///
+    /// Fails if the file can't grow to fit the new node, in which case the list is unchanged.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> io::Result<bool> {
+        let key_len = u32::try_from(key.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "key longer than u32::MAX bytes"))?;
+
+        let mut rng = Seed(self.header().seed);
+        let level = (rng.u64() & ((1 << (MAX_HEIGHT - 1)) - 1)).trailing_ones() as usize;
+
+        let size = mem::size_of::<NodeHeader>() + (level + 1) * mem::size_of::<u64>() + key.len() + value.len();
+        let node = self.alloc(size)?;
+        self.header_mut().seed = rng.0;
+        // SAFETY: alloc handed out size bytes at node, aligned to 8
+        unsafe {
+            let ptr = self.map.as_ptr().add(node as usize);
+            ptr.cast::<NodeHeader>().write(NodeHeader { level: level as u32, key_len, val_len: value.len() as u64 });
+            let tower = ptr.add(mem::size_of::<NodeHeader>());
+            ptr::write_bytes(tower, 0, (level + 1) * mem::size_of::<u64>());
+            let bytes = tower.add((level + 1) * mem::size_of::<u64>());
+            ptr::copy_nonoverlapping(key.as_ptr(), bytes, key.len());
+            ptr::copy_nonoverlapping(value.as_ptr(), bytes.add(key.len()), value.len());
+        }
+
+        let path = self.find_path(key);
+        let old = self.next(path[0], 0);
+        let replacing = old != 0 && self.key(old) == key;
+        if replacing {
+            self.unlink(old, &path);
+        }
+        for (l, &prev) in path.iter().enumerate().take(level + 1) {
+            self.set_next(node, l, self.next(prev, l));
+            self.set_next(prev, l, node);
+        }
+        let header = self.header_mut();
+        header.height = header.height.max(level as u32 + 1);
+        header.len += 1;
+        Ok(!replacing)
+    }
+
+    /// Removes the entry for `key`. Returns true if there was one.
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        let path = self.find_path(key);
+        let node = self.next(path[0], 0);
+        if node == 0 || self.key(node) != key {
+            return false;
+        }
+        self.unlink(node, &path);
+        true
+    }
+
+    /// Returns an iterator over the entries of the list, sorted by key.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { list: self, node: self.next(0, 0) }
+    }
+
+    /// Returns an iterator over the entries whose keys are at least `key`, sorted by key.
+    pub fn iter_from(&self, key: &[u8]) -> Iter<'_> {
+        Iter { list: self, node: self.next(self.find_path(key)[0], 0) }
+    }
+
+    /// Blocks until every change made so far is written to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        // SAFETY: map and map_len describe a live mapping
+        if unsafe { libc::msync(self.map.as_ptr().cast(), self.map_len, libc::MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping always starts with a header, and is page aligned
+        unsafe { &*self.map.as_ptr().cast() }
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        // SAFETY: as in header
+        unsafe { &mut *self.map.as_ptr().cast() }
+    }
+
+    fn node(&self, node: u64) -> &NodeHeader {
+        // SAFETY: links only ever hold offsets of nodes, which are 8 byte aligned
+        unsafe { &*self.map.as_ptr().add(node as usize).cast() }
+    }
+
+    /// Returns a pointer to the links of `node`, or of the head if `node` is 0.
+    fn tower(&self, node: u64) -> *mut u64 {
+        if node == 0 {
+            // SAFETY: head is in bounds of the header
+            return unsafe { ptr::addr_of_mut!((*self.map.as_ptr().cast::<Header>()).head).cast() };
+        }
+        // SAFETY: the links follow the node header
+        unsafe { self.map.as_ptr().add(node as usize + mem::size_of::<NodeHeader>()).cast() }
+    }
+
+    /// Returns the node after `node` at `level`, or 0 at the end of the list.
+    fn next(&self, node: u64, level: usize) -> u64 {
+        // SAFETY: the head has every level, and search paths only visit nodes at levels they have
+        unsafe { *self.tower(node).add(level) }
+    }
+
+    fn set_next(&mut self, node: u64, level: usize, next: u64) {
+        // SAFETY: as in next, and &mut self means nobody's reading it
+        unsafe { *self.tower(node).add(level) = next }
+    }
+
+    fn key(&self, node: u64) -> &[u8] {
+        let header = self.node(node);
+        let links = (header.level as usize + 1) * mem::size_of::<u64>();
+        // SAFETY: the key follows the links
+        unsafe { slice::from_raw_parts(self.tower(node).cast::<u8>().add(links), header.key_len as usize) }
+    }
+
+    fn value(&self, node: u64) -> &[u8] {
+        let header = self.node(node);
+        let key = self.key(node);
+        // SAFETY: the value follows the key
+        unsafe { slice::from_raw_parts(key.as_ptr().add(key.len()), header.val_len as usize) }
+    }
+
+    /// Returns the last node whose key is less than `key` at every level, or 0 for the head.
+    fn find_path(&self, key: &[u8]) -> [u64; MAX_HEIGHT] {
+        let mut path = [0; MAX_HEIGHT];
+        let mut at = 0;
+        for level in (0..self.header().height as usize).rev() {
+            loop {
+                let next = self.next(at, level);
+                if next == 0 || self.key(next).cmp(key) != Ordering::Less {
+                    break;
+                }
+                at = next;
+            }
+            path[level] = at;
+        }
+        path
+    }
+
+    /// Unlinks `node`, which `path` leads to, from every level it's in.
+    fn unlink(&mut self, node: u64, path: &[u64; MAX_HEIGHT]) {
+        for (level, &prev) in path.iter().enumerate().take(self.node(node).level as usize + 1) {
+            if self.next(prev, level) == node {
+                self.set_next(prev, level, self.next(node, level));
+            }
+        }
+        let header = self.header_mut();
+        header.len -= 1;
+        while header.height > 0 && header.head[header.height as usize - 1] == 0 {
+            header.height -= 1;
+        }
+    }
+
+    /// Returns the offset of `size` unused bytes, rounded up to a multiple of 8, growing the
+    /// file and remapping it if they don't fit.
+    fn alloc(&mut self, size: usize) -> io::Result<u64> {
+        let size = size.next_multiple_of(8);
+        let used = self.header().used as usize;
+        let end = used
+            .checked_add(size)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::OutOfMemory, "skip list file too large"))?;
+        if end > self.map_len {
+            let new_len = end.max(self.map_len.saturating_mul(2));
+            self.file.set_len(new_len as u64)?;
+            let new_map = map(&self.file, new_len)?;
+            // SAFETY: the old mapping is ours, and nothing borrows it through &mut self
+            unsafe { libc::munmap(self.map.as_ptr().cast(), self.map_len) };
+            self.map = new_map;
+            self.map_len = new_len;
+        }
+        self.header_mut().used = end as u64;
+        Ok(used as u64)
+    }
+}
+
+impl Drop for PersistentSkipList {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is ours, and nothing can borrow it anymore
+        unsafe { libc::munmap(self.map.as_ptr().cast(), self.map_len) };
+    }
+}
+
+impl fmt::Debug for PersistentSkipList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a PersistentSkipList {
+    type Item = (&'a [u8], &'a [u8]);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Maps the first `len` bytes of `file` for reading and writing, shared with the file.
+fn map(file: &File, len: usize) -> io::Result<NonNull<u8>> {
+    // SAFETY: a fresh mapping aliases nothing
+    let ptr = unsafe {
+        libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0)
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(NonNull::new(ptr.cast()).expect("mmap returned null"))
+}
+
+/// An iterator over the entries of a [`PersistentSkipList`], sorted by key.
+#[derive(Clone)]
+pub struct Iter<'a> {
+    list: &'a PersistentSkipList,
+    node: u64,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.node == 0 {
+            return None;
+        }
+        let node = self.node;
+        self.node = self.list.next(node, 0);
+        Some((self.list.key(node), self.list.value(node)))
+    }
+}
+
+impl FusedIterator for Iter<'_> {}
+
+impl fmt::Debug for Iter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PersistentSkipList;
+    use std::convert::TryInto;
+    use std::path::PathBuf;
+
+    // a file in the temp directory that's removed when the test is done with it
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("skiplist-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let file = TempFile::new("insert_get_remove");
+        let mut l = unsafe { PersistentSkipList::open(&file.0) }.unwrap();
+        assert!(l.is_empty());
+
+        let mut keys: Vec<u32> = (0..1000).collect();
+        fastrand::shuffle(&mut keys);
+        for k in keys.iter() {
+            assert!(l.insert(&k.to_be_bytes(), k.to_string().as_bytes()).unwrap());
+        }
+        assert_eq!(l.len(), 1000);
+        assert!(l.iter().map(|(k, _)| u32::from_be_bytes(k.try_into().unwrap())).eq(0..1000));
+        assert_eq!(l.get(&7u32.to_be_bytes()), Some(&b"7"[..]));
+        assert_eq!(l.get(b"missing"), None);
+
+        assert!(!l.insert(&7u32.to_be_bytes(), b"seven").unwrap());
+        assert_eq!(l.get(&7u32.to_be_bytes()), Some(&b"seven"[..]));
+        assert_eq!(l.len(), 1000);
+
+        for k in (0..1000u32).filter(|k| k % 2 == 0) {
+            assert!(l.remove(&k.to_be_bytes()));
+        }
+        assert!(!l.remove(&0u32.to_be_bytes()));
+        assert_eq!(l.len(), 500);
+        assert!(l.iter_from(&991u32.to_be_bytes()).map(|(_, v)| v).eq([&b"991"[..], b"993", b"995", b"997", b"999"]));
+    }
+
+    #[test]
+    fn reopen() {
+        let file = TempFile::new("reopen");
+        {
+            let mut l = unsafe { PersistentSkipList::open(&file.0) }.unwrap();
+            // enough to grow the file past its first size a few times
+            for k in 0..20_000u32 {
+                l.insert(&k.to_be_bytes(), &[0; 16]).unwrap();
+            }
+            l.remove(&5u32.to_be_bytes());
+            l.flush().unwrap();
+        }
+
+        let mut l = unsafe { PersistentSkipList::open(&file.0) }.unwrap();
+        assert_eq!(l.len(), 19_999);
+        assert!(!l.contains_key(&5u32.to_be_bytes()));
+        assert!(l.iter().map(|(k, _)| u32::from_be_bytes(k.try_into().unwrap())).eq((0..20_000).filter(|&k| k != 5)));
+        assert!(l.insert(&5u32.to_be_bytes(), b"back").unwrap());
+        assert_eq!(l.get(&5u32.to_be_bytes()), Some(&b"back"[..]));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let file = TempFile::new("rejects_other_files");
+        std::fs::write(&file.0, vec![1; 4096]).unwrap();
+        let err = unsafe { PersistentSkipList::open(&file.0) }.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
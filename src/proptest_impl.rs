@@ -0,0 +1,111 @@
+//! Strategies generating random lists and maps, for property testing code that embeds them.
+//!
+//! Every generated collection draws its node levels from a generator seeded by proptest, so a
+//! failing case replays with the same towers, and shrinks like the `Vec` of elements it's
+//! built from.
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use proptest::arbitrary::{any, any_with, Arbitrary};
+use proptest::collection::{vec, SizeRange, VecStrategy};
+use proptest::strategy::{Map, Strategy};
+
+use crate::{Geometric, OrdComparator, SkipList, SkipListMap};
+
+type ListStrategy<T, const NUM_LEVELS: usize> =
+    Map<(VecStrategy<<T as Arbitrary>::Strategy>, <u64 as Arbitrary>::Strategy), fn((Vec<T>, u64)) -> SkipList<T, NUM_LEVELS>>;
+
+type MapStrategy<K, V, const NUM_LEVELS: usize> = Map<
+    (VecStrategy<<(K, V) as Arbitrary>::Strategy>, <u64 as Arbitrary>::Strategy),
+    fn((Vec<(K, V)>, u64)) -> SkipListMap<K, V, NUM_LEVELS>,
+>;
+
+fn build_list<T: Ord, const NUM_LEVELS: usize>((items, seed): (Vec<T>, u64)) -> SkipList<T, NUM_LEVELS> {
+    let mut list = SkipList::with_level_generator(OrdComparator, Geometric::with_seed(seed));
+    list.extend(items);
+    list
+}
+
+fn build_map<K: Ord, V, const NUM_LEVELS: usize>((entries, seed): (Vec<(K, V)>, u64)) -> SkipListMap<K, V, NUM_LEVELS> {
+    let mut map = SkipListMap { list: SkipList::with_level_generator(OrdComparator, Geometric::with_seed(seed)) };
+    for (key, value) in entries {
+        map.insert(key, value);
+    }
+    map
+}
+
+/// Returns a strategy generating lists of elements drawn from `element`, as many as `size`
+/// allows. Equal elements are all kept.
+pub fn skiplist<T: Ord + Debug, const NUM_LEVELS: usize>(
+    element: impl Strategy<Value = T>,
+    size: impl Into<SizeRange>,
+) -> impl Strategy<Value = SkipList<T, NUM_LEVELS>> {
+    (vec(element, size), any::<u64>()).prop_map(build_list)
+}
+
+/// Returns a strategy generating maps with keys drawn from `key` and values from `value`. Up
+/// to `size` entries are drawn, and a later entry replaces an earlier one with an equal key,
+/// so the map may end up smaller.
+pub fn skiplist_map<K: Ord + Debug, V: Debug, const NUM_LEVELS: usize>(
+    key: impl Strategy<Value = K>,
+    value: impl Strategy<Value = V>,
+    size: impl Into<SizeRange>,
+) -> impl Strategy<Value = SkipListMap<K, V, NUM_LEVELS>> {
+    (vec((key, value), size), any::<u64>()).prop_map(build_map)
+}
+
+impl<T: Arbitrary + Ord, const NUM_LEVELS: usize> Arbitrary for SkipList<T, NUM_LEVELS> {
+    /// The range of lengths, and the parameters of the elements, like `Vec`'s.
+    type Parameters = (SizeRange, T::Parameters);
+    type Strategy = ListStrategy<T, NUM_LEVELS>;
+
+    fn arbitrary_with((size, params): Self::Parameters) -> Self::Strategy {
+        (vec(any_with::<T>(params), size), any::<u64>()).prop_map(build_list as fn(_) -> _)
+    }
+}
+
+impl<K: Arbitrary + Ord, V: Arbitrary, const NUM_LEVELS: usize> Arbitrary for SkipListMap<K, V, NUM_LEVELS> {
+    /// The range of entries drawn, and the parameters of the keys and values.
+    type Parameters = (SizeRange, K::Parameters, V::Parameters);
+    type Strategy = MapStrategy<K, V, NUM_LEVELS>;
+
+    fn arbitrary_with((size, key, value): Self::Parameters) -> Self::Strategy {
+        (vec(any_with::<(K, V)>((key, value)), size), any::<u64>()).prop_map(build_map as fn(_) -> _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{skiplist, skiplist_map};
+    use crate::{SkipList, SkipListMap};
+
+    proptest! {
+        #[test]
+        fn arbitrary_lists_are_valid(l in any::<SkipList<i16, 8>>()) {
+            prop_assert_eq!(l.validate(), Ok(()));
+            prop_assert!(l.iter().zip(l.iter().skip(1)).all(|(a, b)| a <= b));
+        }
+
+        #[test]
+        fn sized_lists(l in skiplist::<_, 4>(0..10u8, 20..50)) {
+            prop_assert_eq!(l.validate(), Ok(()));
+            prop_assert!((20..50).contains(&l.len()));
+            prop_assert!(l.iter().all(|&x| x < 10));
+        }
+
+        #[test]
+        fn arbitrary_maps_are_valid(m in any::<SkipListMap<u8, String, 8>>()) {
+            prop_assert!(m.keys().zip(m.keys().skip(1)).all(|(a, b)| a < b));
+            prop_assert!(m.iter().all(|(key, value)| m.get(key) == Some(value)));
+        }
+
+        #[test]
+        fn sized_maps(m in skiplist_map::<_, _, 8>(any::<u32>(), any::<bool>(), 0..30)) {
+            prop_assert!(m.len() < 30);
+            prop_assert!(m.keys().all(|key| m.contains_key(key)));
+        }
+    }
+}
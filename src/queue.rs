@@ -0,0 +1,182 @@
+use core::borrow::Borrow;
+use core::fmt::{self, Debug};
+use core::iter::FromIterator;
+use alloc::vec::Vec;
+
+use crate::{Iter, SkipList};
+
+/// A double-ended priority queue on a `SkipList`, for code that would use `BinaryHeap` but
+/// also needs to look at or pop the largest element, or remove arbitrary ones.
+///
+/// Pushing and removing take O(log n) on average, and peeking or popping either end O(1).
+/// Equal elements are all kept, and [`pop_min`](SkipQueue::pop_min) pops them in the order
+/// they were pushed.
+pub struct SkipQueue<T, const NUM_LEVELS: usize = 16> {
+    list: SkipList<T, NUM_LEVELS>,
+}
+
+impl<T: Ord, const NUM_LEVELS: usize> SkipQueue<T, NUM_LEVELS> {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        SkipQueue { list: SkipList::new() }
+    }
+
+    /// Adds `item` to the queue.
+    pub fn push(&mut self, item: T) {
+        self.list.insert(item);
+    }
+
+    /// Returns the smallest element.
+    pub fn peek_min(&self) -> Option<&T> {
+        self.list.first()
+    }
+
+    /// Returns the largest element.
+    pub fn peek_max(&self) -> Option<&T> {
+        self.list.last()
+    }
+
+    /// Removes and returns the smallest element.
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    /// Removes and returns the largest element.
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+
+    /// Removes an element equal to `item` and returns it.
+    pub fn remove<Q: Ord + ?Sized>(&mut self, item: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+    {
+        self.list.remove(item)
+    }
+
+    /// Returns true if the queue holds an element equal to `item`.
+    pub fn contains<Q: Ord + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.list.contains(item)
+    }
+
+    /// Returns the number of elements in the queue.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns true if the queue holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Removes every element from the queue.
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// Returns an iterator over the elements, smallest first.
+    pub fn iter(&self) -> Iter<'_, T, NUM_LEVELS> {
+        self.list.iter()
+    }
+
+    /// Consumes the queue and returns its elements, smallest first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.list.into_iter().collect()
+    }
+}
+
+impl<T: Ord, const NUM_LEVELS: usize> Default for SkipQueue<T, NUM_LEVELS> {
+    fn default() -> Self {
+        SkipQueue::new()
+    }
+}
+
+impl<T: Clone, const NUM_LEVELS: usize> Clone for SkipQueue<T, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        SkipQueue { list: self.list.clone() }
+    }
+}
+
+impl<T: Debug, const NUM_LEVELS: usize> Debug for SkipQueue<T, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.list.iter()).finish()
+    }
+}
+
+impl<T: Ord, const NUM_LEVELS: usize> Extend<T> for SkipQueue<T, NUM_LEVELS> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.list.extend(iter);
+    }
+}
+
+impl<T: Ord, const NUM_LEVELS: usize> FromIterator<T> for SkipQueue<T, NUM_LEVELS> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut queue = SkipQueue::new();
+        queue.extend(iter);
+        queue
+    }
+}
+
+impl<'a, T: Ord, const NUM_LEVELS: usize> IntoIterator for &'a SkipQueue<T, NUM_LEVELS> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, NUM_LEVELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkipQueue;
+
+    #[test]
+    fn both_ends() {
+        let mut q: SkipQueue<i32> = [5, 1, 8, 3, 8, 2].iter().copied().collect();
+        assert_eq!(q.len(), 6);
+        assert_eq!(q.peek_min(), Some(&1));
+        assert_eq!(q.peek_max(), Some(&8));
+
+        assert_eq!(q.pop_min(), Some(1));
+        assert_eq!(q.pop_max(), Some(8));
+        assert_eq!(q.pop_max(), Some(8));
+        assert_eq!(q.remove(&3), Some(3));
+        assert_eq!(q.remove(&3), None);
+        assert!(q.contains(&5) && !q.contains(&8));
+
+        q.push(0);
+        assert_eq!(q.into_sorted_vec(), [0, 2, 5]);
+    }
+
+    #[test]
+    fn equal_elements_pop_in_push_order() {
+        #[derive(Debug)]
+        struct Task(u8, &'static str);
+        impl PartialEq for Task {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Task {}
+        impl PartialOrd for Task {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Task {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut q = SkipQueue::<Task>::new();
+        q.push(Task(1, "first"));
+        q.push(Task(0, "urgent"));
+        q.push(Task(1, "second"));
+        let order: Vec<_> = std::iter::from_fn(|| q.pop_min()).map(|task| task.1).collect();
+        assert_eq!(order, ["urgent", "first", "second"]);
+    }
+}
@@ -0,0 +1,155 @@
+use core::fmt::{self, Debug, Display, Write};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::format;
+
+use crate::{Allocator, SkipList, SkipListNode};
+
+/// Escapes the characters that are special in a Graphviz record label.
+fn escape_record(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        if matches!(c, '\\' | '"' | '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl<T: Debug, const NUM_LEVELS: usize, C, G, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Renders the list in the Graphviz DOT language, for looking at its towers with `dot -Tsvg`.
+    ///
+    /// Every node is drawn as its tower, with its element at the bottom, and every link as an
+    /// edge between the levels it joins, labeled with its width. Nodes are named by their
+    /// position in level 0 order, and a link to a node that isn't on level 0, which only a bug
+    /// can make, points to a node named by its address instead.
+    pub fn to_dot(&self) -> String {
+        let mut names = BTreeMap::new();
+        names.insert(self.head.0.as_ptr() as *const SkipListNode<T, NUM_LEVELS>, String::from("head"));
+        for (index, node) in self.iter_nodes().enumerate() {
+            names.insert(node.ptr().as_ptr() as *const _, format!("n{}", index));
+        }
+        let name = |node: *const SkipListNode<T, NUM_LEVELS>| names.get(&node).cloned().unwrap_or_else(|| format!("\"{:p}\"", node));
+
+        let mut dot = String::from("digraph skiplist {\n    rankdir=LR;\n    node [shape=record];\n");
+        let nodes = || core::iter::once(self.head.node()).chain(self.iter_nodes());
+        for node in nodes() {
+            let levels = if node.is_head() { self.height } else { node.level + 1 };
+            let mut label = String::new();
+            for level in (0..levels).rev() {
+                write!(label, "<l{}> {}|", level, level).unwrap();
+            }
+            match node.val() {
+                Some(val) => label.push_str(&escape_record(&format!("{:?}", val))),
+                None => label.push_str("head"),
+            }
+            writeln!(dot, "    {} [label=\"{}\"];", name(node.ptr().as_ptr()), label).unwrap();
+        }
+        for node in nodes() {
+            for (level, link) in node.tower().iter().enumerate() {
+                if let Some(next) = link.next {
+                    let next = name(next.as_ptr());
+                    writeln!(dot, "    {}:l{} -> {}:l{} [label=\"{}\"];", name(node.ptr().as_ptr()), level, next, level, link.width).unwrap();
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Returns a rendering of the list's levels for printing with `{}`, one row per level from
+    /// the top down, drawn like the classic skip list diagram:
+    ///
+    /// ```text
+    /// head ----------------> 3 -------> nil
+    /// head ------> 1 ------> 3 -------> nil
+    /// head -> 0 -> 1 -> 2 -> 3 -> 44 -> nil
+    /// ```
+    ///
+    /// Every element is printed with `Debug` in its own column, so a node's tower lines up down
+    /// the rows it reaches, and every link is an arrow from one node to the next on its level.
+    /// Unlike the list's `Debug` output, which only shows the elements, this is meant for looking
+    /// at the shape of small lists.
+    pub fn fmt_levels(&self) -> impl Display + '_ {
+        Levels(self)
+    }
+}
+
+/// The rendering returned by [`SkipList::fmt_levels`].
+struct Levels<'a, T, const NUM_LEVELS: usize, C, G, A: Allocator>(&'a SkipList<T, NUM_LEVELS, C, G, A>);
+
+impl<T: Debug, const NUM_LEVELS: usize, C, G, A: Allocator> Display for Levels<'_, T, NUM_LEVELS, C, G, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = self.0;
+        // every column is a label, the level of the tower under it, and where it starts. The
+        // head's column comes first and nil's last, and both reach every level
+        let mut columns = Vec::with_capacity(list.len + 2);
+        columns.push((String::from("head"), usize::MAX, 0));
+        for node in list.iter_nodes() {
+            let (label, _, start) = columns.last().unwrap();
+            let start = start + label.chars().count() + 4;
+            columns.push((format!("{:?}", node.val().unwrap()), node.level, start));
+        }
+        let (label, _, start) = columns.last().unwrap();
+        let start = start + label.chars().count() + 4;
+        columns.push((String::from("nil"), usize::MAX, start));
+
+        for level in (0..list.height).rev() {
+            let mut end = 0;
+            for (label, _, start) in columns.iter().filter(|(_, top, _)| *top >= level) {
+                if *start > 0 {
+                    f.write_str(" ")?;
+                    for _ in end + 3..*start {
+                        f.write_str("-")?;
+                    }
+                    f.write_str("> ")?;
+                }
+                f.write_str(label)?;
+                end = start + label.chars().count();
+            }
+            if level > 0 {
+                f.write_str("\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Deterministic, OrdComparator, SkipList};
+
+    #[test]
+    fn to_dot() {
+        let l = SkipList::<&str, 4>::from_sorted_iter(["a", "b|c", "d"]);
+        let dot = l.to_dot();
+        assert!(dot.starts_with("digraph skiplist {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("    n1 [label=\""));
+        assert!(dot.contains("\\\"b\\|c\\\"\"];"));
+        assert!(dot.contains("    head:l0 -> n0:l0 [label=\"1\"];"));
+        assert!(dot.contains("    n1:l0 -> n2:l0 [label=\"1\"];"));
+
+        // one edge into every node on every level it reaches
+        let links: usize = l.iter_nodes().map(|node| node.level + 1).sum();
+        assert_eq!(dot.matches(" -> ").count(), links);
+        assert!(!SkipList::<i32, 4>::new().to_dot().contains(" -> "));
+    }
+
+    #[test]
+    fn fmt_levels() {
+        // appending in order builds the towers 0, 1, 0, 2, 0
+        let mut l = SkipList::<i32, 4, _, _>::with_level_generator(OrdComparator, Deterministic::new());
+        l.extend([0, 1, 2, 3, 44]);
+        assert_eq!(l.fmt_levels().to_string(), "\
+head ----------------> 3 -------> nil
+head ------> 1 ------> 3 -------> nil
+head -> 0 -> 1 -> 2 -> 3 -> 44 -> nil");
+
+        assert_eq!(SkipList::<i32, 4>::new().fmt_levels().to_string(), "head -> nil");
+        let l = SkipList::<&str, 4>::from_sorted_iter(["a"]);
+        assert_eq!(l.fmt_levels().to_string(), "head -> \"a\" -> nil");
+    }
+}
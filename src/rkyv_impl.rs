@@ -0,0 +1,234 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::slice;
+use alloc::vec::Vec;
+
+use rkyv::ser::{ScratchSpace, Serializer};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::{Archive, Archived, Deserialize, Fallible, Serialize};
+
+use crate::map::{ArchivedMapEntry, MapEntry};
+use crate::{Allocator, Comparator, LevelGenerator, OrdComparator, SkipList, SkipListMap};
+
+/// The archived form of a `SkipList`: its elements in sorted order, laid out as an rkyv
+/// `ArchivedVec` that can be read and searched in place without deserializing.
+///
+/// Only lists ordered by [`OrdComparator`] can be archived, so the archive is sorted by `Ord`
+/// and searching it agrees with the list. Deserializing rebuilds the list with evenly spaced
+/// towers.
+#[repr(transparent)]
+pub struct ArchivedSkipList<T>(ArchivedVec<T>);
+
+impl<T> ArchivedSkipList<T> {
+    /// Returns the number of archived elements.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the archive holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the archived elements in sorted order.
+    pub fn as_slice(&self) -> &[T] {
+        self.0.as_slice()
+    }
+
+    /// Returns an iterator over the archived elements in sorted order.
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.0.iter()
+    }
+
+    /// Returns true if the archive holds an element equal to `item`, by binary search.
+    pub fn contains<Q: Ord + ?Sized>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.0.binary_search_by(|x| x.borrow().cmp(item)).is_ok()
+    }
+}
+
+impl<T: Archive, const NUM_LEVELS: usize, G, A: Allocator> Archive for SkipList<T, NUM_LEVELS, OrdComparator, G, A> {
+    type Archived = ArchivedSkipList<T::Archived>;
+    type Resolver = VecResolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: VecResolver, out: *mut Self::Archived) {
+        // ArchivedSkipList is a transparent wrapper of the ArchivedVec
+        ArchivedVec::<T::Archived>::resolve_from_len(self.len(), pos, resolver, out.cast());
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, G, A, S> Serialize<S> for SkipList<T, NUM_LEVELS, OrdComparator, G, A>
+where
+    T: Serialize<S>,
+    A: Allocator,
+    S: ScratchSpace + Serializer + ?Sized,
+{
+    /// Archives the elements of the list in sorted order.
+    fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        ArchivedVec::serialize_from_iter::<T, _, _, _>(self.iter(), serializer)
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C, G, A, D> Deserialize<SkipList<T, NUM_LEVELS, C, G, A>, D> for ArchivedSkipList<T::Archived>
+where
+    T: Archive,
+    Archived<T>: Deserialize<T, D>,
+    C: Comparator<T> + Default,
+    G: LevelGenerator + Default,
+    A: Allocator + Default,
+    D: Fallible + ?Sized,
+{
+    /// Rebuilds the list from its archived elements. Elements that aren't sorted by the list's
+    /// comparator are inserted one by one instead.
+    fn deserialize(&self, deserializer: &mut D) -> Result<SkipList<T, NUM_LEVELS, C, G, A>, D::Error> {
+        let items = self.iter().map(|item| item.deserialize(deserializer)).collect::<Result<Vec<T>, _>>()?;
+
+        let cmp = C::default();
+        if items.windows(2).all(|pair| cmp.compare(&pair[0], &pair[1]) != Ordering::Greater) {
+            return Ok(SkipList::from_sorted_iter(items));
+        }
+
+        let mut list = SkipList::with_level_generator_in(cmp, G::default(), A::default());
+        list.extend(items);
+        Ok(list)
+    }
+}
+
+/// The archived form of a `SkipListMap`: its entries sorted by key, which can be read and
+/// searched in place without deserializing.
+#[repr(transparent)]
+pub struct ArchivedSkipListMap<K: Archive, V: Archive>(ArchivedVec<ArchivedMapEntry<K, V>>);
+
+impl<K: Archive, V: Archive> ArchivedSkipListMap<K, V> {
+    /// Returns the number of archived entries.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the archive holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the archived entries, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&Archived<K>, &Archived<V>)> + '_ {
+        self.0.iter().map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Returns the archived value stored under `key`, by binary search.
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&Archived<V>>
+    where
+        Archived<K>: Borrow<Q>,
+    {
+        let index = self.0.binary_search_by(|entry| entry.key.borrow().cmp(key)).ok()?;
+        Some(&self.0[index].value)
+    }
+}
+
+impl<K: Archive, V: Archive, const NUM_LEVELS: usize> Archive for SkipListMap<K, V, NUM_LEVELS> {
+    type Archived = ArchivedSkipListMap<K, V>;
+    type Resolver = VecResolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: VecResolver, out: *mut Self::Archived) {
+        // ArchivedSkipListMap is a transparent wrapper of the ArchivedVec
+        ArchivedVec::<ArchivedMapEntry<K, V>>::resolve_from_len(self.len(), pos, resolver, out.cast());
+    }
+}
+
+impl<K, V, const NUM_LEVELS: usize, S> Serialize<S> for SkipListMap<K, V, NUM_LEVELS>
+where
+    K: Serialize<S>,
+    V: Serialize<S>,
+    S: ScratchSpace + Serializer + ?Sized,
+{
+    /// Archives the entries of the map, sorted by key.
+    fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+        ArchivedVec::serialize_from_iter::<MapEntry<K, V>, _, _, _>(self.list.iter(), serializer)
+    }
+}
+
+impl<K, V, const NUM_LEVELS: usize, D> Deserialize<SkipListMap<K, V, NUM_LEVELS>, D> for ArchivedSkipListMap<K, V>
+where
+    K: Archive + Ord,
+    V: Archive,
+    ArchivedMapEntry<K, V>: Deserialize<MapEntry<K, V>, D>,
+    D: Fallible + ?Sized,
+{
+    /// Rebuilds the map from its archived entries, in O(n) if their keys are strictly
+    /// increasing. Otherwise they are inserted one by one, and later duplicates win.
+    fn deserialize(&self, deserializer: &mut D) -> Result<SkipListMap<K, V, NUM_LEVELS>, D::Error> {
+        let entries = self.0.iter().map(|entry| entry.deserialize(deserializer)).collect::<Result<Vec<_>, _>>()?;
+
+        let mut map = SkipListMap::new();
+        if entries.windows(2).all(|pair| pair[0].key < pair[1].key) {
+            let mut path = [map.list.head.0; NUM_LEVELS];
+            for (i, entry) in entries.into_iter().enumerate() {
+                let level = SkipList::<MapEntry<K, V>, NUM_LEVELS>::balanced_level(i);
+                // SAFETY: path holds the last node at every level, and keys come in increasing order
+                unsafe { map.list.push_back_with_level(entry, level, &mut path) };
+            }
+        } else {
+            for MapEntry { key, value } in entries {
+                map.insert(key, value);
+            }
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rkyv::{archived_root, Deserialize, Infallible};
+
+    use crate::{SkipList, SkipListMap};
+
+    #[test]
+    fn round_trip() {
+        let mut l = SkipList::<i32, 8>::new();
+        l.extend([5, -3, 12, 0, 5]);
+
+        let bytes = rkyv::to_bytes::<_, 256>(&l).unwrap();
+        // SAFETY: the bytes were just archived from a list of the same type
+        let archived = unsafe { archived_root::<SkipList<i32, 8>>(&bytes) };
+        assert_eq!(archived.as_slice(), [-3, 0, 5, 5, 12]);
+        assert!(archived.contains(&12));
+        assert!(!archived.contains(&1));
+
+        let de: SkipList<i32, 8> = archived.deserialize(&mut Infallible).unwrap();
+        assert_eq!(de, l);
+        assert_eq!(de.validate(), Ok(()));
+    }
+
+    #[test]
+    fn map_round_trip() {
+        let mut m = SkipListMap::<String, u32, 8>::new();
+        for (i, word) in ["pear", "apple", "fig"].iter().enumerate() {
+            m.insert(word.to_string(), i as u32);
+        }
+
+        let bytes = rkyv::to_bytes::<_, 256>(&m).unwrap();
+        // SAFETY: the bytes were just archived from a map of the same type
+        let archived = unsafe { archived_root::<SkipListMap<String, u32, 8>>(&bytes) };
+        assert_eq!(archived.len(), 3);
+        assert_eq!(archived.get("fig"), Some(&2));
+        assert_eq!(archived.get("kiwi"), None);
+        assert!(archived.iter().map(|(key, _)| key.as_str()).eq(["apple", "fig", "pear"]));
+
+        let de: SkipListMap<String, u32, 8> = archived.deserialize(&mut Infallible).unwrap();
+        assert!(de.iter().eq(m.iter()));
+        assert_eq!(de.get("apple"), Some(&1));
+
+        let mut m = SkipListMap::<u32, u32, 4>::new();
+        for i in 0..100 {
+            m.insert(i, i * i);
+        }
+        let bytes = rkyv::to_bytes::<_, 256>(&m).unwrap();
+        // SAFETY: the bytes were just archived from a map of the same type
+        let archived = unsafe { archived_root::<SkipListMap<u32, u32, 4>>(&bytes) };
+        let de: SkipListMap<u32, u32, 4> = archived.deserialize(&mut Infallible).unwrap();
+        assert!(de.iter().eq(m.iter()));
+        assert!((0..100).all(|i| de.get(&i) == Some(&(i * i))));
+    }
+}
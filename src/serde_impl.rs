@@ -0,0 +1,186 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+use alloc::vec::Vec;
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Unexpected, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use crate::{Allocator, Comparator, LevelGenerator, SkipList};
+
+impl<T: Serialize, const NUM_LEVELS: usize, C, G, A: Allocator> Serialize for SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Serializes the list as a sequence of its elements in sorted order.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for item in self.iter() {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+}
+
+struct SkipListVisitor<T, const NUM_LEVELS: usize, C, G> {
+    _marker: PhantomData<(T, C, G)>,
+}
+
+impl<'de, T: Deserialize<'de>, const NUM_LEVELS: usize, C: Comparator<T> + Default, G: LevelGenerator + Default> Visitor<'de>
+    for SkipListVisitor<T, NUM_LEVELS, C, G>
+{
+    type Value = SkipList<T, NUM_LEVELS, C, G>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+
+        let cmp = C::default();
+        if items.windows(2).all(|pair| cmp.compare(&pair[0], &pair[1]) != Ordering::Greater) {
+            return Ok(SkipList::from_sorted_iter(items));
+        }
+
+        let mut list = SkipList::with_level_generator(cmp, G::default());
+        list.extend(items);
+        Ok(list)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const NUM_LEVELS: usize, C: Comparator<T> + Default, G: LevelGenerator + Default> Deserialize<'de>
+    for SkipList<T, NUM_LEVELS, C, G>
+{
+    /// Deserializes a list from a sequence of elements. The sequence doesn't have to be sorted.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SkipListVisitor { _marker: PhantomData })
+    }
+}
+
+/// Serializes a `SkipList` along with the level of every node, so deserializing it rebuilds the
+/// exact same towers in O(n) instead of drawing new levels.
+///
+/// The list is written as a sequence of `(level, element)` pairs in sorted order. Paired with
+/// a binary format, that costs one byte per node over the plain element sequence. Serialize a
+/// `Towers(&list)`, and deserialize a `Towers<SkipList<..>>`, which fails if the elements
+/// aren't sorted or a level doesn't fit the list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Towers<L>(pub L);
+
+impl<T: Serialize, const NUM_LEVELS: usize, C, G, A: Allocator> Serialize for Towers<&SkipList<T, NUM_LEVELS, C, G, A>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let list = self.0;
+        let mut seq = serializer.serialize_seq(Some(list.len()))?;
+        let mut node = list.head.next(0);
+        while let Some(n) = node {
+            seq.serialize_element(&(n.level as u8, n.val().unwrap()))?;
+            node = n.next(0);
+        }
+        seq.end()
+    }
+}
+
+struct TowersVisitor<T, const NUM_LEVELS: usize, C, G, A> {
+    _marker: PhantomData<(T, C, G, A)>,
+}
+
+impl<'de, T, const NUM_LEVELS: usize, C, G, A> Visitor<'de> for TowersVisitor<T, NUM_LEVELS, C, G, A>
+where
+    T: Deserialize<'de>,
+    C: Comparator<T> + Default,
+    G: LevelGenerator + Default,
+    A: Allocator + Default,
+{
+    type Value = Towers<SkipList<T, NUM_LEVELS, C, G, A>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of (level, element) pairs in sorted order")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        let mut list = SkipList::with_level_generator_in(C::default(), G::default(), A::default());
+        let mut path = [list.head.0; NUM_LEVELS];
+        while let Some((level, item)) = seq.next_element::<(u8, T)>()? {
+            if usize::from(level) >= NUM_LEVELS {
+                return Err(S::Error::invalid_value(Unexpected::Unsigned(level.into()), &"a level below NUM_LEVELS"));
+            }
+            if list.last().is_some_and(|last| list.cmp.compare(last, &item) == Ordering::Greater) {
+                return Err(S::Error::custom("elements are not sorted"));
+            }
+            // SAFETY: path holds the last node at every level, and item isn't less than any element
+            unsafe { list.push_back_with_level(item, level.into(), &mut path) };
+        }
+        Ok(Towers(list))
+    }
+}
+
+impl<'de, T, const NUM_LEVELS: usize, C, G, A> Deserialize<'de> for Towers<SkipList<T, NUM_LEVELS, C, G, A>>
+where
+    T: Deserialize<'de>,
+    C: Comparator<T> + Default,
+    G: LevelGenerator + Default,
+    A: Allocator + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(TowersVisitor { _marker: PhantomData })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Towers;
+    use crate::SkipList;
+
+    #[test]
+    fn round_trip() {
+        let mut l = SkipList::<i32, 8>::new();
+        l.extend([5, -3, 12, 0, 5]);
+
+        let json = serde_json::to_string(&l).unwrap();
+        assert_eq!(json, "[-3,0,5,5,12]");
+
+        let de: SkipList<i32, 8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(de, l);
+        assert_eq!(de.last(), Some(&12));
+    }
+
+    #[test]
+    fn unsorted_input() {
+        let de: SkipList<String, 8> = serde_json::from_str(r#"["b", "c", "a"]"#).unwrap();
+        assert_eq!(de.iter().map(String::as_str).collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert!(serde_json::from_str::<SkipList<String, 8>>("{}").is_err());
+    }
+
+    #[test]
+    fn towers_round_trip() {
+        let mut l = SkipList::<i32, 8>::new();
+        l.extend((0..200).rev());
+
+        let json = serde_json::to_string(&Towers(&l)).unwrap();
+        let Towers(de): Towers<SkipList<i32, 8>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(de, l);
+
+        let levels = |l: &SkipList<i32, 8>| {
+            let mut levels = Vec::new();
+            let mut node = l.head.next(0);
+            while let Some(n) = node {
+                levels.push(n.level);
+                node = n.next(0);
+            }
+            levels
+        };
+        assert_eq!(levels(&de), levels(&l));
+        for i in [0, 57, 199] {
+            assert_eq!(de.get(i), l.get(i));
+        }
+        assert_eq!(serde_json::to_string(&Towers(&de)).unwrap(), json);
+    }
+
+    #[test]
+    fn towers_rejects_bad_input() {
+        assert!(serde_json::from_str::<Towers<SkipList<i32, 8>>>("[[0,2],[1,1]]").is_err());
+        assert!(serde_json::from_str::<Towers<SkipList<i32, 8>>>("[[8,1]]").is_err());
+        assert!(serde_json::from_str::<Towers<SkipList<i32, 8>>>("[[0,1],[3,1]]").is_ok());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,390 @@
+use core::cmp::Ordering;
+use core::iter::{FusedIterator, Peekable};
+
+use crate::{Allocator, Comparator, Iter, NodeRef, OrdComparator, SkipList};
+
+impl<T, const NUM_LEVELS: usize> NodeRef<'_, T, NUM_LEVELS> {
+    /// Returns the last node, starting from this one, such that `f` returns true for its
+    /// element and every element between. `f` must hold for this node unless it's the head.
+    ///
+    /// The search climbs this node's tower and the towers it lands on while the links skip
+    /// nodes `f` holds for, then descends, so a target d nodes ahead takes O(log d) steps on
+    /// average however long the list is.
+    fn seek_while(self, prefetch: bool, mut f: impl FnMut(&T) -> bool) -> Self {
+        let mut passes = |node: Option<Self>| node.and_then(Self::val).is_some_and(&mut f);
+        let mut node = self;
+        let mut level = 0;
+        loop {
+            while level < node.level && passes(node.next(level + 1)) {
+                level += 1;
+            }
+            match node.next(level) {
+                Some(next) if passes(Some(next)) => node = next,
+                _ => break,
+            }
+        }
+        for level in (0..level).rev() {
+            node = node.proceed_at_level_while(level, prefetch, |_, next| passes(Some(next)));
+        }
+        node
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, C: Comparator<T>, G, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Returns a new list holding every element that is in this list or in `other`, compared
+    /// with this list's comparator.
+    ///
+    /// Both lists are merged along level 0 in a single O(n + m) pass, and the new list is
+    /// built bottom-up with evenly spaced towers, like
+    /// [`from_sorted_iter`](SkipList::from_sorted_iter) would. An element in both lists is
+    /// taken from this one, and a run of equal elements is kept as long as the longer of the
+    /// two runs. The new list has this list's settings and a copy of its level generator.
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        C: Clone,
+        G: Clone,
+        A: Clone,
+    {
+        let mut list = self.empty_like(self.gen.clone());
+        let mut path = [list.head.0; NUM_LEVELS];
+        let (mut a, mut b) = (self.iter().peekable(), other.iter().peekable());
+        let mut len = 0;
+        loop {
+            let next = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) => match self.cmp.compare(x, y) {
+                    Ordering::Less => a.next(),
+                    Ordering::Greater => b.next(),
+                    Ordering::Equal => {
+                        b.next();
+                        a.next()
+                    }
+                },
+                (Some(_), None) => a.next(),
+                (None, _) => b.next(),
+            };
+            let Some(item) = next else {
+                return list;
+            };
+            // SAFETY: path holds the last node at every level, and the merge yields items in sorted order
+            unsafe { list.push_back_with_level(item.clone(), Self::balanced_level(len), &mut path) };
+            len += 1;
+        }
+    }
+
+    /// Returns a new list holding every element that is in both this list and `other`,
+    /// compared with this list's comparator.
+    ///
+    /// The lists are walked in turns, leapfrogging: each side seeks to the first element not
+    /// less than the other side's current one, skipping a run of d elements the other list
+    /// has no match for in O(log d) steps along the towers instead of d. Intersecting a short
+    /// list with a long one costs about O(n log(m / n)). Common elements are taken from this
+    /// list, a run of equal elements is kept as long as the shorter of the two runs, and the
+    /// new list is built with evenly spaced towers and this list's settings.
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        C: Clone,
+        G: Clone,
+        A: Clone,
+    {
+        let mut list = self.empty_like(self.gen.clone());
+        let mut path = [list.head.0; NUM_LEVELS];
+        let mut len = 0;
+        let cmp = &self.cmp;
+        let (mut a, mut b) = (self.head.node(), other.head.node());
+        while let Some(x) = a.next(0).and_then(|next| next.val().map(|val| (next, val))) {
+            b = b.seek_while(other.prefetches(), |y| cmp.compare(y, x.1) == Ordering::Less);
+            let Some(y) = b.next(0).and_then(|next| next.val().map(|val| (next, val))) else {
+                break;
+            };
+            if cmp.compare(x.1, y.1) == Ordering::Equal {
+                // SAFETY: path holds the last node at every level, and matches are found in sorted order
+                unsafe { list.push_back_with_level(x.1.clone(), Self::balanced_level(len), &mut path) };
+                len += 1;
+                a = x.0;
+                b = y.0;
+            } else {
+                a = a.seek_while(self.prefetches(), |x| cmp.compare(x, y.1) == Ordering::Less);
+            }
+        }
+        list
+    }
+
+    /// Returns a new list holding the elements of this list that aren't in `other`, compared
+    /// with this list's comparator.
+    ///
+    /// This list is walked along level 0, while `other` is only searched: it seeks ahead to
+    /// each element along its towers, so a gap of d elements in `other` between two elements
+    /// of this list is passed in O(log d) steps. Each element of `other` cancels out one equal
+    /// element of this list, and the new list is built with evenly spaced towers and this
+    /// list's settings.
+    pub fn difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        C: Clone,
+        G: Clone,
+        A: Clone,
+    {
+        let mut list = self.empty_like(self.gen.clone());
+        let mut path = [list.head.0; NUM_LEVELS];
+        let mut len = 0;
+        let cmp = &self.cmp;
+        let mut b = other.head.node();
+        for x in self.iter() {
+            b = b.seek_while(other.prefetches(), |y| cmp.compare(y, x) == Ordering::Less);
+            match b.next(0).filter(|y| y.val().is_some_and(|y| cmp.compare(x, y) == Ordering::Equal)) {
+                Some(y) => b = y,
+                None => {
+                    // SAFETY: path holds the last node at every level, and the elements are kept in sorted order
+                    unsafe { list.push_back_with_level(x.clone(), Self::balanced_level(len), &mut path) };
+                    len += 1;
+                }
+            }
+        }
+        list
+    }
+
+    /// Returns a lazy iterator over the elements that are in this list or in `other` but not
+    /// in both, in sorted order, compared with this list's comparator.
+    ///
+    /// Both lists are merged along level 0 as the iterator advances, and each element of one
+    /// list cancels out one equal element of the other.
+    pub fn symmetric_difference_iter<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, NUM_LEVELS, C> {
+        SymmetricDifference::new(self, other)
+    }
+
+    /// Returns a new list holding the elements that are in this list or in `other` but not in
+    /// both, collected from [`symmetric_difference_iter`](SkipList::symmetric_difference_iter)
+    /// in O(n + m) with evenly spaced towers and this list's settings.
+    pub fn symmetric_difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        C: Clone,
+        G: Clone,
+        A: Clone,
+    {
+        let mut list = self.empty_like(self.gen.clone());
+        let mut path = [list.head.0; NUM_LEVELS];
+        for (i, item) in self.symmetric_difference_iter(other).enumerate() {
+            // SAFETY: path holds the last node at every level, and the iterator yields items in sorted order
+            unsafe { list.push_back_with_level(item.clone(), Self::balanced_level(i), &mut path) };
+        }
+        list
+    }
+
+    /// Returns true if every element of this list is also in `other`, each element of `other`
+    /// standing in for only one equal element of this list.
+    ///
+    /// `other` is searched like [`difference`](SkipList::difference) does, and the search
+    /// stops at the first element it's missing, without building anything.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        if self.len > other.len {
+            return false;
+        }
+        let cmp = &self.cmp;
+        let mut b = other.head.node();
+        self.iter().all(|x| {
+            b = b.seek_while(other.prefetches(), |y| cmp.compare(y, x) == Ordering::Less);
+            match b.next(0).filter(|y| y.val().is_some_and(|y| cmp.compare(x, y) == Ordering::Equal)) {
+                Some(y) => {
+                    b = y;
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// Returns true if every element of `other` is also in this list, like
+    /// `other.is_subset(self)`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns true if no element of this list is in `other`.
+    ///
+    /// The lists are leapfrogged like [`intersection`](SkipList::intersection) does, and the
+    /// search stops at the first common element.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        let cmp = &self.cmp;
+        let (mut a, mut b) = (self.head.node(), other.head.node());
+        while let Some(x) = a.next(0).and_then(NodeRef::val) {
+            b = b.seek_while(other.prefetches(), |y| cmp.compare(y, x) == Ordering::Less);
+            let Some(y) = b.next(0).and_then(NodeRef::val) else {
+                return true;
+            };
+            if cmp.compare(x, y) == Ordering::Equal {
+                return false;
+            }
+            a = a.seek_while(self.prefetches(), |x| cmp.compare(x, y) == Ordering::Less);
+        }
+        true
+    }
+}
+
+/// A lazy iterator over the elements that are in exactly one of two `SkipList`s, in sorted
+/// order.
+///
+/// Created by [`SkipList::symmetric_difference_iter`].
+pub struct SymmetricDifference<'a, T, const NUM_LEVELS: usize, C = OrdComparator> {
+    a: Peekable<Iter<'a, T, NUM_LEVELS>>,
+    b: Peekable<Iter<'a, T, NUM_LEVELS>>,
+    cmp: &'a C,
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C> SymmetricDifference<'a, T, NUM_LEVELS, C> {
+    fn new<G, A: Allocator>(
+        a: &'a SkipList<T, NUM_LEVELS, C, G, A>,
+        b: &'a SkipList<T, NUM_LEVELS, C, G, A>,
+    ) -> Self {
+        SymmetricDifference { a: a.iter().peekable(), b: b.iter().peekable(), cmp: &a.cmp }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C: Comparator<T>> Iterator for SymmetricDifference<'a, T, NUM_LEVELS, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => match self.cmp.compare(x, y) {
+                    Ordering::Less => return self.a.next(),
+                    Ordering::Greater => return self.b.next(),
+                    Ordering::Equal => {
+                        self.a.next();
+                        self.b.next();
+                    }
+                },
+                (Some(_), None) => return self.a.next(),
+                (None, _) => return self.b.next(),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.a.len().checked_add(self.b.len()))
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, C: Comparator<T>> FusedIterator for SymmetricDifference<'a, T, NUM_LEVELS, C> {}
+
+impl<'a, T, const NUM_LEVELS: usize, C> Clone for SymmetricDifference<'a, T, NUM_LEVELS, C> {
+    fn clone(&self) -> Self {
+        SymmetricDifference { a: self.a.clone(), b: self.b.clone(), cmp: self.cmp }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SkipList;
+
+    #[test]
+    fn union() {
+        let a = SkipList::<u32, 8>::from_sorted_iter((0..100).filter(|x| x % 2 == 0));
+        let b = SkipList::<u32, 8>::from_sorted_iter((0..100).filter(|x| x % 3 == 0));
+        let u = a.union(&b);
+        assert!(u.iter().copied().eq((0..100).filter(|x| x % 2 == 0 || x % 3 == 0)));
+        assert_eq!(u.validate(), Ok(()));
+        assert_eq!(u.union(&SkipList::new()), u);
+        assert_eq!(SkipList::new().union(&u), u);
+
+        let a = SkipList::<u32, 8>::from_sorted_iter([1, 1, 2, 3, 3, 3].iter().copied());
+        let b = SkipList::<u32, 8>::from_sorted_iter([1, 1, 1, 3, 4].iter().copied());
+        assert!(a.union(&b).iter().eq(&[1, 1, 1, 2, 3, 3, 3, 4]));
+    }
+
+    #[test]
+    fn intersection() {
+        let a = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 2 == 0));
+        let b = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 3 == 0));
+        let i = a.intersection(&b);
+        assert!(i.iter().copied().eq((0..1000).filter(|x| x % 6 == 0)));
+        assert_eq!(i.validate(), Ok(()));
+        assert!(a.intersection(&SkipList::new()).is_empty());
+
+        // a few elements spread over a long list, with long runs in between
+        let mut sparse = SkipList::<u32, 8>::new();
+        sparse.extend([3, 400, 401, 998, 5000].iter().copied());
+        assert!(sparse.intersection(&a).iter().eq(&[400, 998]));
+        assert!(a.intersection(&sparse).iter().eq(&[400, 998]));
+
+        let a = SkipList::<u32, 8>::from_sorted_iter([1, 1, 2, 3, 3, 3].iter().copied());
+        let b = SkipList::<u32, 8>::from_sorted_iter([1, 1, 1, 3, 4].iter().copied());
+        assert!(a.intersection(&b).iter().eq(&[1, 1, 3]));
+    }
+
+    #[test]
+    fn difference() {
+        let a = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 2 == 0));
+        let b = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 3 == 0));
+        let d = a.difference(&b);
+        assert!(d.iter().copied().eq((0..1000).filter(|x| x % 2 == 0 && x % 3 != 0)));
+        assert_eq!(d.validate(), Ok(()));
+        assert_eq!(a.difference(&SkipList::new()), a);
+        assert!(a.difference(&a).is_empty());
+
+        let mut sparse = SkipList::<u32, 8>::new();
+        sparse.extend([3, 400, 401, 998, 5000].iter().copied());
+        assert!(sparse.difference(&a).iter().eq(&[3, 401, 5000]));
+
+        let a = SkipList::<u32, 8>::from_sorted_iter([1, 1, 2, 3, 3, 3].iter().copied());
+        let b = SkipList::<u32, 8>::from_sorted_iter([1, 1, 1, 3, 4].iter().copied());
+        assert!(a.difference(&b).iter().eq(&[2, 3, 3]));
+        assert!(b.difference(&a).iter().eq(&[1, 4]));
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let a = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 2 == 0));
+        let b = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 3 == 0));
+        let expected = || (0..1000).filter(|x| (x % 2 == 0) != (x % 3 == 0));
+        assert!(a.symmetric_difference_iter(&b).copied().eq(expected()));
+        assert!(b.symmetric_difference_iter(&a).copied().eq(expected()));
+        let d = a.symmetric_difference(&b);
+        assert!(d.iter().copied().eq(expected()));
+        assert_eq!(d.validate(), Ok(()));
+        assert_eq!(a.symmetric_difference(&SkipList::new()), a);
+        assert_eq!(a.symmetric_difference_iter(&a).next(), None);
+
+        let a = SkipList::<u32, 8>::from_sorted_iter([1, 1, 2, 3, 3, 3].iter().copied());
+        let b = SkipList::<u32, 8>::from_sorted_iter([1, 1, 1, 3, 4].iter().copied());
+        let mut iter = a.symmetric_difference_iter(&b);
+        assert_eq!(iter.next(), Some(&1));
+        assert!(iter.clone().eq(&[2, 3, 3, 4]));
+        assert!(iter.eq(&[2, 3, 3, 4]));
+    }
+
+    #[test]
+    fn relations() {
+        let evens = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 2 == 0));
+        let sixes = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 6 == 0));
+        let odds = SkipList::<u32, 8>::from_sorted_iter((0..1000).filter(|x| x % 2 == 1));
+        let empty = SkipList::<u32, 8>::new();
+
+        assert!(sixes.is_subset(&evens));
+        assert!(!evens.is_subset(&sixes));
+        assert!(evens.is_superset(&sixes));
+        assert!(evens.is_subset(&evens));
+        assert!(empty.is_subset(&odds));
+        assert!(!odds.is_subset(&empty));
+
+        assert!(evens.is_disjoint(&odds));
+        assert!(odds.is_disjoint(&sixes));
+        assert!(!evens.is_disjoint(&sixes));
+        assert!(empty.is_disjoint(&empty));
+
+        let mut sparse = SkipList::<u32, 8>::new();
+        sparse.extend([3, 401, 999].iter().copied());
+        assert!(sparse.is_subset(&odds));
+        assert!(sparse.is_disjoint(&evens));
+        sparse.insert(400);
+        assert!(!sparse.is_subset(&odds));
+        assert!(!sparse.is_disjoint(&evens));
+
+        let a = SkipList::<u32, 8>::from_sorted_iter([1, 1, 3].iter().copied());
+        let b = SkipList::<u32, 8>::from_sorted_iter([1, 2, 3, 4].iter().copied());
+        assert!(!a.is_subset(&b));
+        assert!(!a.is_disjoint(&b));
+    }
+}
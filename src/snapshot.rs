@@ -0,0 +1,159 @@
+use std::cmp::Ordering;
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::{Allocator, Comparator, LevelGenerator, SkipList};
+
+const MAGIC: [u8; 4] = *b"SKPL";
+const VERSION: u32 = 1;
+
+impl<T, const NUM_LEVELS: usize, C: Comparator<T>, G: LevelGenerator, A: Allocator> SkipList<T, NUM_LEVELS, C, G, A> {
+    /// Writes the list to `writer` as a snapshot that [`load_from`](SkipList::load_from) reads
+    /// back, encoding every element with `encode`, which appends its bytes to the buffer it's
+    /// given, like `|item, buf| serde_json::to_writer(buf, item)`.
+    ///
+    /// A snapshot is a header holding a magic number, a format version, the list's number of
+    /// levels and its length, then a record for every element in sorted order: the level of
+    /// its node, the length of its encoding as a little-endian `u32`, and the encoding. Writes
+    /// are buffered, so `writer` doesn't need to be.
+    pub fn save_to<W: Write, E>(&self, writer: W, mut encode: impl FnMut(&T, &mut Vec<u8>) -> Result<(), E>) -> io::Result<()>
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(NUM_LEVELS as u32).to_le_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+
+        let mut buf = Vec::new();
+        let mut node = self.head.next(0);
+        while let Some(n) = node {
+            buf.clear();
+            encode(n.val().unwrap(), &mut buf).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let len = u32::try_from(buf.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "element encoded to more than u32::MAX bytes"))?;
+            writer.write_all(&[n.level as u8])?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(&buf)?;
+            node = n.next(0);
+        }
+        writer.flush()
+    }
+
+    /// Reads a list written by [`save_to`](SkipList::save_to) from `reader`, decoding every
+    /// element with `decode`, like `|bytes| serde_json::from_slice(bytes)`.
+    ///
+    /// The nodes get the levels they had when the list was saved, so the list is rebuilt in
+    /// O(n) and searches as fast as the original. A list saved with more levels than this one
+    /// has gets its towers cut off at the top level.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the snapshot has the wrong magic number or
+    /// version, ends early, fails to decode, or isn't sorted by this list's comparator.
+    pub fn load_from<R: Read, E>(reader: R, mut decode: impl FnMut(&[u8]) -> Result<T, E>) -> io::Result<Self>
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+        C: Default,
+        G: Default,
+        A: Default,
+    {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut reader = BufReader::new(reader);
+        let mut header = [0; 20];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(invalid("not a skip list snapshot"));
+        }
+        if u32::from_le_bytes(header[4..8].try_into().unwrap()) != VERSION {
+            return Err(invalid("unsupported skip list snapshot version"));
+        }
+        let len = u64::from_le_bytes(header[12..20].try_into().unwrap());
+
+        let mut list = SkipList::with_level_generator_in(C::default(), G::default(), A::default());
+        let mut path = [list.head.0; NUM_LEVELS];
+        let mut buf = Vec::new();
+        for _ in 0..len {
+            let mut record = [0; 5];
+            reader.read_exact(&mut record)?;
+            let level = usize::from(record[0]).min(NUM_LEVELS - 1);
+            let size = u32::from_le_bytes(record[1..].try_into().unwrap()) as usize;
+            buf.clear();
+            reader.by_ref().take(size as u64).read_to_end(&mut buf)?;
+            if buf.len() < size {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let item = decode(&buf).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if list.last().is_some_and(|last| list.cmp.compare(last, &item) == Ordering::Greater) {
+                return Err(invalid("skip list snapshot is not sorted"));
+            }
+            // SAFETY: path holds the last node at every level, and item isn't less than any element
+            unsafe { list.push_back_with_level(item, level, &mut path) };
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SkipList;
+    use std::cmp::Reverse;
+    use std::convert::{Infallible, TryInto};
+    use std::io;
+
+    fn encode(item: &i64, buf: &mut Vec<u8>) -> Result<(), Infallible> {
+        buf.extend_from_slice(&item.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<i64, io::Error> {
+        let bytes = bytes.try_into().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not an i64"))?;
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut l = SkipList::<i64, 8>::new();
+        l.extend((0..500).map(|x| x * 7 % 500 - 250));
+
+        let mut bytes = Vec::new();
+        l.save_to(&mut bytes, encode).unwrap();
+        assert_eq!(bytes.len(), 20 + 500 * 13);
+
+        let loaded = SkipList::<i64, 8>::load_from(&bytes[..], decode).unwrap();
+        assert_eq!(loaded, l);
+        let mut resaved = Vec::new();
+        loaded.save_to(&mut resaved, encode).unwrap();
+        assert_eq!(resaved, bytes);
+
+        // a list with fewer levels takes the same snapshot
+        let short = SkipList::<i64, 2>::load_from(&bytes[..], decode).unwrap();
+        assert!(short.iter().eq(l.iter()));
+        assert_eq!(short.get(300), l.get(300));
+
+        let strings = SkipList::<String, 8>::from_sorted_iter(["a", "bb", "ccc"].map(String::from));
+        let mut bytes = Vec::new();
+        strings.save_to(&mut bytes, |s, buf| serde_json::to_writer(buf, s)).unwrap();
+        let loaded = SkipList::<String, 8>::load_from(&bytes[..], |bytes| serde_json::from_slice(bytes)).unwrap();
+        assert_eq!(loaded, strings);
+    }
+
+    #[test]
+    fn rejects_bad_snapshots() {
+        let l = SkipList::<i64, 8>::from_sorted_iter(0..10);
+        let mut bytes = Vec::new();
+        l.save_to(&mut bytes, encode).unwrap();
+
+        let err = SkipList::<i64, 8>::load_from(&bytes[..bytes.len() - 1], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(SkipList::<i64, 8>::load_from(&bad[..], decode).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // ordered the other way round, the snapshot isn't sorted
+        let err = SkipList::<Reverse<i64>, 8>::load_from(&bytes[..], |bytes| decode(bytes).map(Reverse)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,193 @@
+//! Timers ordered by deadline, for event loops and schedulers.
+//!
+//! [`Timers`] keeps scheduled items in a `SkipList` sorted by deadline. Scheduling and
+//! cancelling take O(log n) on average, and expiring the next timer O(1), which suits
+//! timeouts, most of which are cancelled before they ever fire.
+
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+
+use crate::SkipList;
+
+/// Identifies a scheduled timer, to cancel it. Handles are ordered by deadline, then by the
+/// order they were scheduled in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerHandle<D> {
+    deadline: D,
+    id: u64,
+}
+
+impl<D> TimerHandle<D> {
+    /// Returns the deadline the timer was scheduled for.
+    pub fn deadline(&self) -> &D {
+        &self.deadline
+    }
+}
+
+struct Timer<D, T> {
+    handle: TimerHandle<D>,
+    item: T,
+}
+
+impl<D: Ord, T> PartialEq for Timer<D, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<D: Ord, T> Eq for Timer<D, T> {}
+
+impl<D: Ord, T> PartialOrd for Timer<D, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<D: Ord, T> Ord for Timer<D, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.handle.cmp(&other.handle)
+    }
+}
+
+impl<D, T> Borrow<TimerHandle<D>> for Timer<D, T> {
+    fn borrow(&self) -> &TimerHandle<D> {
+        &self.handle
+    }
+}
+
+/// A set of items, each due at a deadline of type `D`, such as an `Instant` or a tick count.
+///
+/// Timers with the same deadline expire in the order they were scheduled.
+pub struct Timers<D, T, const NUM_LEVELS: usize = 16> {
+    list: SkipList<Timer<D, T>, NUM_LEVELS>,
+    next_id: u64,
+}
+
+impl<D: Ord, T, const NUM_LEVELS: usize> Timers<D, T, NUM_LEVELS> {
+    /// Creates an empty set of timers.
+    pub fn new() -> Self {
+        Timers { list: SkipList::new(), next_id: 0 }
+    }
+
+    /// Returns the number of timers scheduled.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns true if no timers are scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Schedules `item` to expire at `at`, and returns the handle that cancels it.
+    pub fn schedule(&mut self, at: D, item: T) -> TimerHandle<D>
+    where
+        D: Clone,
+    {
+        let handle = TimerHandle { deadline: at, id: self.next_id };
+        self.next_id += 1;
+        self.list.insert(Timer { handle: handle.clone(), item });
+        handle
+    }
+
+    /// Cancels the timer `handle` was returned for, and returns its item. Returns None if the
+    /// timer already expired or was cancelled.
+    pub fn cancel(&mut self, handle: &TimerHandle<D>) -> Option<T> {
+        self.list.remove(handle).map(|timer| timer.item)
+    }
+
+    /// Returns the earliest deadline of any timer.
+    pub fn next_deadline(&self) -> Option<&D> {
+        self.list.first().map(|timer| &timer.handle.deadline)
+    }
+
+    /// Returns an iterator that removes and yields the items of every timer whose deadline is
+    /// at or before `now`, earliest first. Timers it hasn't reached when it's dropped stay
+    /// scheduled.
+    pub fn expire(&mut self, now: D) -> Expire<'_, D, T, NUM_LEVELS> {
+        Expire { timers: self, now }
+    }
+
+    /// Cancels every timer.
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+}
+
+impl<D: Ord, T, const NUM_LEVELS: usize> Default for Timers<D, T, NUM_LEVELS> {
+    fn default() -> Self {
+        Timers::new()
+    }
+}
+
+impl<D: Debug, T: Debug, const NUM_LEVELS: usize> Debug for Timers<D, T, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.list.iter().map(|timer| (&timer.handle.deadline, &timer.item))).finish()
+    }
+}
+
+/// An iterator removing and yielding the items of expired timers, earliest first.
+///
+/// Created by [`Timers::expire`].
+pub struct Expire<'a, D, T, const NUM_LEVELS: usize> {
+    timers: &'a mut Timers<D, T, NUM_LEVELS>,
+    now: D,
+}
+
+impl<'a, D: Ord, T, const NUM_LEVELS: usize> Iterator for Expire<'a, D, T, NUM_LEVELS> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.timers.next_deadline()? > &self.now {
+            return None;
+        }
+        self.timers.list.pop_front().map(|timer| timer.item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.timers.len()))
+    }
+}
+
+impl<'a, D: Ord, T, const NUM_LEVELS: usize> FusedIterator for Expire<'a, D, T, NUM_LEVELS> {}
+
+#[cfg(test)]
+mod tests {
+    use super::Timers;
+
+    #[test]
+    fn schedule_cancel_expire() {
+        let mut timers = Timers::<u64, &str>::new();
+        let a = timers.schedule(30, "a");
+        timers.schedule(10, "b");
+        let c = timers.schedule(20, "c");
+        timers.schedule(20, "d");
+        timers.schedule(40, "e");
+        assert_eq!(timers.len(), 5);
+        assert_eq!(*a.deadline(), 30);
+
+        assert_eq!(timers.cancel(&c), Some("c"));
+        assert_eq!(timers.cancel(&c), None);
+        assert_eq!(timers.next_deadline(), Some(&10));
+
+        assert_eq!(timers.expire(5).next(), None);
+        assert!(timers.expire(20).eq(["b", "d"]));
+        assert_eq!(timers.cancel(&a), Some("a"));
+        assert!(timers.expire(100).eq(["e"]));
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn partially_consumed_expire() {
+        let mut timers = Timers::<u32, u32>::new();
+        for t in (0..10).rev() {
+            timers.schedule(t, t);
+        }
+        assert_eq!(timers.expire(5).next(), Some(0));
+        assert_eq!(timers.len(), 9);
+        assert_eq!(timers.expire(5).count(), 5);
+        assert_eq!(timers.next_deadline(), Some(&6));
+    }
+}
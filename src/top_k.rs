@@ -0,0 +1,117 @@
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+use core::iter::Rev;
+use alloc::vec::Vec;
+
+use crate::{EvictPolicy, Iter, SkipList};
+
+/// Keeps the `k` largest elements of a stream, like a leaderboard.
+///
+/// Once `k` elements are kept, an element that isn't larger than the smallest of them, the
+/// floor, is turned away in O(1) without searching the list. Larger ones are inserted in
+/// O(log k) on average, and push the floor out.
+pub struct TopK<T, const NUM_LEVELS: usize = 16> {
+    list: SkipList<T, NUM_LEVELS>,
+}
+
+impl<T: Ord, const NUM_LEVELS: usize> TopK<T, NUM_LEVELS> {
+    /// Creates an empty `TopK` keeping up to `k` elements, which must be at least 1.
+    pub fn new(k: usize) -> Self {
+        TopK { list: SkipList::new().with_max_len(k, EvictPolicy::Smallest) }
+    }
+
+    /// Offers `item`. Returns the element that didn't make the cut: `item` itself if it's no
+    /// larger than the floor of a full `TopK`, or the old floor if `item` pushed it out.
+    pub fn push(&mut self, item: T) -> Option<T> {
+        if self.is_full() && self.list.first().is_some_and(|floor| item.cmp(floor) != Ordering::Greater) {
+            return Some(item);
+        }
+        self.list.insert(item)
+    }
+
+    /// Returns the smallest element kept, which an element has to beat to get in once the
+    /// `TopK` is full.
+    pub fn floor(&self) -> Option<&T> {
+        self.list.first()
+    }
+
+    /// Returns the largest element kept.
+    pub fn max(&self) -> Option<&T> {
+        self.list.last()
+    }
+
+    /// Returns the number of elements kept, at most `k`.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns true if no elements are kept.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Returns true if `k` elements are kept.
+    pub fn is_full(&self) -> bool {
+        Some(self.list.len()) == self.list.max_len()
+    }
+
+    /// Returns the most elements kept.
+    pub fn k(&self) -> usize {
+        self.list.max_len().unwrap()
+    }
+
+    /// Returns an iterator over the elements kept, largest first.
+    pub fn iter(&self) -> Rev<Iter<'_, T, NUM_LEVELS>> {
+        self.list.iter().rev()
+    }
+
+    /// Consumes the `TopK` and returns the elements kept, largest first.
+    pub fn into_vec(self) -> Vec<T> {
+        self.list.into_iter().rev().collect()
+    }
+}
+
+impl<T: Clone, const NUM_LEVELS: usize> Clone for TopK<T, NUM_LEVELS> {
+    fn clone(&self) -> Self {
+        TopK { list: self.list.clone() }
+    }
+}
+
+impl<T: Debug, const NUM_LEVELS: usize> Debug for TopK<T, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.list.iter().rev()).finish()
+    }
+}
+
+impl<T: Ord, const NUM_LEVELS: usize> Extend<T> for TopK<T, NUM_LEVELS> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|item| drop(self.push(item)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopK;
+
+    #[test]
+    fn keeps_largest() {
+        let mut top = TopK::<u32>::new(3);
+        assert_eq!(top.k(), 3);
+        assert_eq!(top.push(5), None);
+        assert_eq!(top.push(1), None);
+        assert_eq!(top.push(9), None);
+        assert!(top.is_full());
+        assert_eq!(top.floor(), Some(&1));
+
+        assert_eq!(top.push(1), Some(1));
+        assert_eq!(top.push(0), Some(0));
+        assert_eq!(top.push(7), Some(1));
+        assert_eq!(top.floor(), Some(&5));
+        assert_eq!(top.max(), Some(&9));
+        assert!(top.iter().eq(&[9, 7, 5]));
+
+        top.extend((0..1000).map(|x| x * 7919 % 1000));
+        assert_eq!(top.len(), 3);
+        assert_eq!(top.into_vec(), [999, 998, 997]);
+    }
+}
@@ -0,0 +1,519 @@
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+use core::slice;
+use alloc::boxed::Box;
+
+use crate::level::Seed;
+use crate::{Comparator, OrdComparator};
+
+// INVARIANT: if a link is Some, it must point to an UnrolledNode
+type UnrolledLink<T, const NUM_LEVELS: usize, const B: usize> = Option<NonNull<UnrolledNode<T, NUM_LEVELS, B>>>;
+
+struct UnrolledNode<T, const NUM_LEVELS: usize, const B: usize> {
+    // INVARIANT: the first `len` items are initialized and sorted, and only the head is empty
+    items: [MaybeUninit<T>; B],
+    len: usize,
+    level: usize,
+    next: [UnrolledLink<T, NUM_LEVELS, B>; NUM_LEVELS],
+}
+
+// SAFETY: a node's links only lead to the nodes after it in the same list, which are never
+// mutated through a shared reference
+unsafe impl<T: Send, const NUM_LEVELS: usize, const B: usize> Send for UnrolledNode<T, NUM_LEVELS, B> {}
+unsafe impl<T: Sync, const NUM_LEVELS: usize, const B: usize> Sync for UnrolledNode<T, NUM_LEVELS, B> {}
+
+impl<T, const NUM_LEVELS: usize, const B: usize> UnrolledNode<T, NUM_LEVELS, B> {
+    fn new(level: usize) -> Box<Self> {
+        Box::new(UnrolledNode {
+            items: core::array::from_fn(|_| MaybeUninit::uninit()),
+            len: 0,
+            level,
+            next: [None; NUM_LEVELS],
+        })
+    }
+
+    fn items(&self) -> &[T] {
+        // SAFETY: the first len items are initialized
+        unsafe { slice::from_raw_parts(self.items.as_ptr().cast(), self.len) }
+    }
+
+    fn next(&self, level: usize) -> Option<&Self> {
+        // SAFETY: If a link is Some, it points to an UnrolledNode
+        unsafe { self.next[level].map(|p| p.as_ref()) }
+    }
+
+    /// Moves `item` in at `index`, shifting the items after it up by one.
+    fn insert_at(&mut self, index: usize, item: T) {
+        assert!(self.len < B && index <= self.len);
+        // SAFETY: the shifted items stay within the array since len < B, and the gap left at
+        // index is filled right away
+        unsafe {
+            let at = self.items.as_mut_ptr().add(index);
+            ptr::copy(at, at.add(1), self.len - index);
+            at.write(MaybeUninit::new(item));
+        }
+        self.len += 1;
+    }
+
+    /// Moves the item at `index` out, shifting the items after it down by one.
+    fn remove_at(&mut self, index: usize) -> T {
+        assert!(index < self.len);
+        self.len -= 1;
+        // SAFETY: the item at index is initialized, and is overwritten by the ones after it
+        // right after being read
+        unsafe {
+            let at = self.items.as_mut_ptr().add(index);
+            let item = at.read().assume_init();
+            ptr::copy(at.add(1), at, self.len - index);
+            item
+        }
+    }
+
+    /// Moves the items from `at` on to the end of `other`, which must have room for them.
+    fn move_tail_to(&mut self, at: usize, other: &mut Self) {
+        let count = self.len - at;
+        assert!(other.len + count <= B);
+        // SAFETY: the moved items are initialized, fit in other, and are forgotten here by
+        // shrinking len
+        unsafe {
+            ptr::copy_nonoverlapping(self.items.as_ptr().add(at), other.items.as_mut_ptr().add(other.len), count);
+        }
+        self.len = at;
+        other.len += count;
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, const B: usize> Drop for UnrolledNode<T, NUM_LEVELS, B> {
+    fn drop(&mut self) {
+        // SAFETY: the first len items are initialized, and nothing reads them afterwards
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.items.as_mut_ptr().cast::<T>(), self.len)) };
+    }
+}
+
+/// A sorted list like `SkipList`, except that every node holds a sorted run of up to `B`
+/// elements instead of just one, like the leaves of a B-tree.
+///
+/// Searches skip from node to node by each node's first element, then look through one run,
+/// so there are about `B` times fewer nodes to chase pointers through and to allocate. A full
+/// node is split in half to make room, and a node left less than a quarter full by a removal
+/// is merged with the next one when they fit in half a node.
+///
+/// The trade-off is that inserting and removing shift up to `B` elements, and that elements
+/// move between nodes, so there are no cursors or stable positions like `SkipList` has.
+pub struct UnrolledSkipList<T, const NUM_LEVELS: usize = 16, const B: usize = 16, C = OrdComparator> {
+    head: Box<UnrolledNode<T, NUM_LEVELS, B>>,
+    rng: Seed,
+    len: usize,
+    nodes: usize,
+    cmp: C,
+}
+
+// SAFETY: the list owns all of its nodes and only reaches them through its head, and shared
+// access to it only hands out shared access to the elements and the comparator
+unsafe impl<T: Send, const NUM_LEVELS: usize, const B: usize, C: Send> Send for UnrolledSkipList<T, NUM_LEVELS, B, C> {}
+unsafe impl<T: Sync, const NUM_LEVELS: usize, const B: usize, C: Sync> Sync for UnrolledSkipList<T, NUM_LEVELS, B, C> {}
+
+impl<T, const NUM_LEVELS: usize, const B: usize> UnrolledSkipList<T, NUM_LEVELS, B> {
+    /// Creates an empty list. `NUM_LEVELS` must be between 1 and 64, and `B` at least 4.
+    pub fn new() -> Self {
+        UnrolledSkipList::with_comparator(OrdComparator)
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, const B: usize, C> UnrolledSkipList<T, NUM_LEVELS, B, C> {
+    /// Creates an empty list ordered by `cmp`. `NUM_LEVELS` must be between 1 and 64, and `B`
+    /// at least 4.
+    pub fn with_comparator(cmp: C) -> Self {
+        assert!((1..=64).contains(&NUM_LEVELS), "UnrolledSkipList: NUM_LEVELS must be between 1 and 64");
+        assert!(B >= 4, "UnrolledSkipList: B must be at least 4");
+        UnrolledSkipList { head: UnrolledNode::new(NUM_LEVELS - 1), rng: Seed::new(), len: 0, nodes: 0, cmp }
+    }
+
+    /// Returns the number of elements in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of nodes the elements are spread over. Inserts alone leave every node
+    /// but the first at least half full.
+    pub fn node_count(&self) -> usize {
+        self.nodes
+    }
+
+    /// Returns an iterator over the elements of the list in sorted order.
+    pub fn iter(&self) -> Iter<'_, T, NUM_LEVELS, B> {
+        let run = self.head.next(0).map_or(&[][..], UnrolledNode::items);
+        Iter { node: self.head.next(0), run: run.iter(), len: self.len }
+    }
+
+    /// Returns a reference to the smallest element in the list.
+    pub fn first(&self) -> Option<&T> {
+        self.head.next(0)?.items().first()
+    }
+
+    /// Removes all elements from the list.
+    pub fn clear(&mut self) {
+        self.drop_nodes();
+        self.head.next.fill(None);
+        self.len = 0;
+        self.nodes = 0;
+    }
+
+    fn gen_level(&mut self) -> usize {
+        let mask = (1u64 << (NUM_LEVELS - 1)) - 1;
+        (self.rng.u64() & mask).trailing_ones() as usize
+    }
+
+    /// Returns, for every level, the last node at that level such that `f` returns true for
+    /// its first element and the first elements of every node before it, or the head if there
+    /// is none.
+    fn find_path_mut(&mut self, mut f: impl FnMut(&C, &T) -> bool) -> [NonNull<UnrolledNode<T, NUM_LEVELS, B>>; NUM_LEVELS] {
+        let cmp = &self.cmp;
+        let mut node = NonNull::from(self.head.as_mut());
+        let mut path = [node; NUM_LEVELS];
+        for level in (0..NUM_LEVELS).rev() {
+            // SAFETY: node is the head or a node reachable from it, and a link is Some iff it points to a valid UnrolledNode
+            unsafe {
+                while let Some(next) = node.as_ref().next[level] {
+                    if !f(cmp, &next.as_ref().items()[0]) {
+                        break;
+                    }
+                    node = next;
+                }
+            }
+            path[level] = node;
+        }
+        path
+    }
+
+    /// Returns the node holding the first element for which `f` is false, given that `f` is
+    /// true for a prefix of the list, and that element's index in it.
+    fn find_by(&self, mut f: impl FnMut(&T) -> bool) -> Option<(&UnrolledNode<T, NUM_LEVELS, B>, usize)> {
+        let mut node = self.head.as_ref();
+        for level in (0..NUM_LEVELS).rev() {
+            while let Some(next) = node.next(level) {
+                if !f(&next.items()[0]) {
+                    break;
+                }
+                node = next;
+            }
+        }
+        // the element is in the last node whose first element passes, or else the first element of the next one
+        let index = node.items().partition_point(&mut f);
+        if index < node.len {
+            Some((node, index))
+        } else {
+            node.next(0).map(|next| (next, 0))
+        }
+    }
+
+    /// Links the new node `node`, which must come right after the node `path[0]`, in at every
+    /// level it participates in.
+    ///
+    /// SAFETY: `path[level]` must be the last node at or before `path[0]` at every level.
+    unsafe fn link_after(&mut self, node: Box<UnrolledNode<T, NUM_LEVELS, B>>, path: &[NonNull<UnrolledNode<T, NUM_LEVELS, B>>; NUM_LEVELS]) {
+        let level = node.level;
+        // the list owns the node from now on, and frees it in unlink or drop_nodes
+        let mut node = NonNull::from(Box::leak(node));
+        for (level, prev) in path.iter().enumerate().take(level + 1) {
+            node.as_mut().next[level] = (*prev.as_ptr()).next[level].replace(node);
+        }
+        self.nodes += 1;
+    }
+
+    /// Unlinks `node` from every level it participates in and frees it, along with any
+    /// elements it still holds.
+    ///
+    /// SAFETY: `node` must be a non-head node in this list, and `path[level]` must be its
+    /// predecessor at every level it participates in.
+    unsafe fn unlink(&mut self, node: NonNull<UnrolledNode<T, NUM_LEVELS, B>>, path: &[NonNull<UnrolledNode<T, NUM_LEVELS, B>>; NUM_LEVELS]) {
+        let node = Box::from_raw(node.as_ptr());
+        for (level, next) in node.next.iter().enumerate().take(node.level + 1) {
+            (*path[level].as_ptr()).next[level] = *next;
+        }
+        self.nodes -= 1;
+    }
+
+    /// Frees every node after the head, without touching the head's links.
+    fn drop_nodes(&mut self) {
+        let mut node = self.head.next[0];
+        while let Some(next) = node {
+            // SAFETY: a link is Some iff it points to a valid UnrolledNode, and every node is owned by the list
+            let next = unsafe { Box::from_raw(next.as_ptr()) };
+            node = next.next[0];
+        }
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, const B: usize, C: Comparator<T>> UnrolledSkipList<T, NUM_LEVELS, B, C> {
+    /// Inserts `item` after any elements equal to it.
+    pub fn insert(&mut self, item: T) {
+        let mut path = self.find_path_mut(|cmp, v| cmp.compare(&item, v) != Ordering::Less);
+        // the item goes in the last node whose first element is <= item, or the first node if there's none
+        let mut node = match self.head.next[0] {
+            // SAFETY: path[0] is the head or a node in the list
+            Some(first) if unsafe { path[0].as_ref() }.len == 0 => first,
+            Some(_) => path[0],
+            None => {
+                let mut node = UnrolledNode::new(self.gen_level());
+                node.insert_at(0, item);
+                let path = [NonNull::from(self.head.as_mut()); NUM_LEVELS];
+                // SAFETY: the list is empty, so the head is the last node of every level
+                unsafe { self.link_after(node, &path) };
+                self.len += 1;
+                return;
+            }
+        };
+        // SAFETY: node is a node of the list, which is borrowed mutably
+        let node_ref = unsafe { node.as_mut() };
+        path[..=node_ref.level].fill(node);
+        let cmp = &self.cmp;
+        let mut index = node_ref.items().partition_point(|v| cmp.compare(&item, v) != Ordering::Less);
+
+        if node_ref.len == B {
+            let mut upper = UnrolledNode::new(self.gen_level());
+            node_ref.move_tail_to(B / 2, &mut upper);
+            if index > B / 2 {
+                index -= B / 2;
+                upper.insert_at(index, item);
+            } else {
+                node_ref.insert_at(index, item);
+            }
+            // SAFETY: path holds the last node at or before node at every level, and upper
+            // holds the elements right after node's
+            unsafe { self.link_after(upper, &path) };
+        } else {
+            node_ref.insert_at(index, item);
+        }
+        self.len += 1;
+    }
+
+    /// Returns the first element equal to `item`.
+    pub fn find<Q: ?Sized>(&self, item: &Q) -> Option<&T>
+    where
+        C: Comparator<T, Q>,
+    {
+        let (node, index) = self.find_by(|v| self.cmp.compare(v, item) == Ordering::Less)?;
+        Some(&node.items()[index]).filter(|&v| self.cmp.compare(v, item) == Ordering::Equal)
+    }
+
+    /// Returns true if the list contains an element equal to `item`.
+    pub fn contains<Q: ?Sized>(&self, item: &Q) -> bool
+    where
+        C: Comparator<T, Q>,
+    {
+        self.find(item).is_some()
+    }
+
+    /// Removes the first element equal to `item` from the list and returns it.
+    pub fn remove<Q: ?Sized>(&mut self, item: &Q) -> Option<T>
+    where
+        C: Comparator<T, Q>,
+    {
+        let path = self.find_path_mut(|cmp, v| cmp.compare(v, item) == Ordering::Less);
+        // SAFETY: path[0] is the head or a node in the list, which is borrowed mutably
+        let last = unsafe { &mut *path[0].as_ptr() };
+        let index = last.items().partition_point(|v| self.cmp.compare(v, item) == Ordering::Less);
+        // the element is in the last node that starts before it, or else starts the next one
+        let (mut node, index) = match last.next[0] {
+            _ if index < last.len => (path[0], index),
+            Some(next) => (next, 0),
+            None => return None,
+        };
+        // SAFETY: node is a node of the list
+        let node_ref = unsafe { node.as_mut() };
+        if self.cmp.compare(&node_ref.items()[index], item) != Ordering::Equal {
+            return None;
+        }
+        let removed = node_ref.remove_at(index);
+        self.len -= 1;
+
+        if node_ref.len == 0 {
+            // node can't be path[0], which still starts before item, so path holds its predecessors
+            // SAFETY: as above
+            unsafe { self.unlink(node, &path) };
+        } else if let Some(mut next) = node_ref.next[0] {
+            // SAFETY: next is a node of the list
+            let next_ref = unsafe { next.as_mut() };
+            if node_ref.len < B / 4 && node_ref.len + next_ref.len <= B / 2 {
+                next_ref.move_tail_to(0, node_ref);
+                // next's predecessors are node at the levels node reaches, and otherwise node's,
+                // which path holds whether node is path[0] or the node after it
+                let mut preds = path;
+                preds[..=node_ref.level].fill(node);
+                // SAFETY: as above
+                unsafe { self.unlink(next, &preds) };
+            }
+        }
+        Some(removed)
+    }
+
+    /// Removes the smallest element from the list and returns it.
+    pub fn pop_front(&mut self) -> Option<T> {
+        let mut first = self.head.next[0]?;
+        // SAFETY: first is a node of the list, which is borrowed mutably
+        let item = unsafe { first.as_mut() }.remove_at(0);
+        self.len -= 1;
+        if unsafe { first.as_ref() }.len == 0 {
+            let path = [NonNull::from(self.head.as_mut()); NUM_LEVELS];
+            // SAFETY: first is the first node after the head, so the head is its predecessor at every level
+            unsafe { self.unlink(first, &path) };
+        }
+        Some(item)
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, const B: usize> Default for UnrolledSkipList<T, NUM_LEVELS, B> {
+    fn default() -> Self {
+        UnrolledSkipList::new()
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, const B: usize, C> Drop for UnrolledSkipList<T, NUM_LEVELS, B, C> {
+    fn drop(&mut self) {
+        self.drop_nodes();
+    }
+}
+
+impl<T: Debug, const NUM_LEVELS: usize, const B: usize, C> Debug for UnrolledSkipList<T, NUM_LEVELS, B, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const NUM_LEVELS: usize, const B: usize, C: Comparator<T>> Extend<T> for UnrolledSkipList<T, NUM_LEVELS, B, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, const B: usize, C> IntoIterator for &'a UnrolledSkipList<T, NUM_LEVELS, B, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, NUM_LEVELS, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of an `UnrolledSkipList` in sorted order.
+///
+/// Created by [`UnrolledSkipList::iter`].
+pub struct Iter<'a, T, const NUM_LEVELS: usize, const B: usize> {
+    node: Option<&'a UnrolledNode<T, NUM_LEVELS, B>>,
+    run: slice::Iter<'a, T>,
+    len: usize,
+}
+
+impl<'a, T, const NUM_LEVELS: usize, const B: usize> Iterator for Iter<'a, T, NUM_LEVELS, B> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(item) = self.run.next() {
+                self.len -= 1;
+                return Some(item);
+            }
+            self.node = self.node?.next(0);
+            self.run = self.node?.items().iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, const NUM_LEVELS: usize, const B: usize> ExactSizeIterator for Iter<'a, T, NUM_LEVELS, B> {}
+
+impl<'a, T, const NUM_LEVELS: usize, const B: usize> FusedIterator for Iter<'a, T, NUM_LEVELS, B> {}
+
+impl<'a, T, const NUM_LEVELS: usize, const B: usize> Clone for Iter<'a, T, NUM_LEVELS, B> {
+    fn clone(&self) -> Self {
+        Iter { node: self.node, run: self.run.clone(), len: self.len }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnrolledSkipList;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn insert_find_remove() {
+        let mut l = UnrolledSkipList::<i32, 12, 8>::new();
+        assert!(l.is_empty());
+
+        let mut nums: Vec<i32> = (0..2000).map(|_| fastrand::i32(0..500)).collect();
+        l.extend(nums.iter().copied());
+        nums.sort();
+        assert_eq!(l.len(), 2000);
+        assert!(l.iter().eq(nums.iter()));
+        assert_eq!(l.iter().len(), 2000);
+        assert!(l.node_count() >= 2000 / 8 && l.node_count() <= 2000 / 4 + 1);
+
+        for i in 0..500 {
+            assert_eq!(l.contains(&i), nums.contains(&i));
+        }
+        for i in (0..500).filter(|i| i % 3 != 0) {
+            while l.remove(&i).is_some() {}
+            assert!(!l.contains(&i));
+        }
+        nums.retain(|v| v % 3 == 0);
+        assert!(l.iter().eq(nums.iter()));
+        assert!(l.node_count() <= nums.len());
+
+        while let Some(v) = l.pop_front() {
+            assert_eq!(v, nums.remove(0));
+        }
+        assert_eq!(l.node_count(), 0);
+        l.extend([3, 1, 2]);
+        assert_eq!(format!("{:?}", l), "[1, 2, 3]");
+        l.clear();
+        assert!(l.is_empty() && l.first().is_none());
+    }
+
+    #[test]
+    fn duplicates_across_nodes() {
+        #[derive(Debug)]
+        struct Tagged(u8, usize);
+        let mut l = UnrolledSkipList::<Tagged, 8, 4, _>::with_comparator(|a: &Tagged, b: &Tagged| a.0.cmp(&b.0));
+        for i in 0..40 {
+            l.insert(Tagged((i % 3) as u8, i));
+        }
+        // equal elements keep the order they were inserted in, and are removed first to last
+        let tags: Vec<_> = l.iter().filter(|t| t.0 == 1).map(|t| t.1).collect();
+        assert_eq!(tags, (1..40).step_by(3).collect::<Vec<_>>());
+        assert_eq!(l.remove(&Tagged(1, 0)).map(|t| t.1), Some(1));
+        assert_eq!(l.remove(&Tagged(1, 0)).map(|t| t.1), Some(4));
+        assert_eq!(l.find(&Tagged(2, 0)).map(|t| t.1), Some(2));
+        assert!(l.remove(&Tagged(7, 0)).is_none());
+    }
+
+    #[test]
+    fn drops_elements() {
+        use std::rc::Rc;
+
+        let rc = Rc::new(());
+        let mut l = UnrolledSkipList::<(i32, Rc<()>), 8, 4, _>::with_comparator(|a: &(i32, Rc<()>), b: &(i32, Rc<()>)| a.0.cmp(&b.0));
+        for i in 0..100 {
+            l.insert((i, rc.clone()));
+        }
+        for i in 0..50 {
+            drop(l.remove(&(i * 2, rc.clone())));
+        }
+        assert_eq!(Rc::strong_count(&rc), 51);
+        drop(l);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
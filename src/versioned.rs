@@ -0,0 +1,215 @@
+use core::borrow::Borrow;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use alloc::vec::Vec;
+use alloc::vec;
+
+use crate::map::{self, Entry, SkipListMap};
+
+/// A sorted map that keeps every version of a value, so reads can see the map as it was at
+/// any earlier point, like a storage engine with snapshot isolation.
+///
+/// Every write gets the next sequence number, and is added to the end of its key's version
+/// chain rather than replacing the value. A snapshot is just a sequence number: reading with
+/// [`get_at`](VersionedMap::get_at) or [`iter_at`](VersionedMap::iter_at) sees every write
+/// with that number or lower, and none after. Removing a key adds a tombstone to its chain.
+///
+/// Old versions stay until [`gc`](VersionedMap::gc) is told that no snapshot before some
+/// sequence number will be read anymore.
+pub struct VersionedMap<K, V, const NUM_LEVELS: usize = 16> {
+    // every chain is sorted by sequence number, oldest first, and never empty
+    map: SkipListMap<K, Vec<Version<V>>, NUM_LEVELS>,
+    seq: u64,
+}
+
+struct Version<V> {
+    seq: u64,
+    // None for a tombstone
+    value: Option<V>,
+}
+
+/// Returns the version of `chain` a snapshot at `seq` sees, if any.
+fn visible<V>(chain: &[Version<V>], seq: u64) -> Option<&V> {
+    chain[..chain.partition_point(|version| version.seq <= seq)].last()?.value.as_ref()
+}
+
+impl<K, V, const NUM_LEVELS: usize> VersionedMap<K, V, NUM_LEVELS> {
+    /// Creates an empty map, whose first write gets sequence number 1.
+    pub fn new() -> Self {
+        VersionedMap { map: SkipListMap::new(), seq: 0 }
+    }
+
+    /// Returns the sequence number of the last write, which is a snapshot of the map as it is
+    /// now. It's 0 if nothing has been written.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the number of keys with at least one version, including keys whose latest
+    /// version is a tombstone.
+    pub fn key_count(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns an iterator over the entries a snapshot at `seq` sees, sorted by key.
+    pub fn iter_at(&self, seq: u64) -> IterAt<'_, K, V, NUM_LEVELS> {
+        IterAt { inner: self.map.iter(), seq }
+    }
+
+    /// Returns an iterator over the latest value of every key, sorted by key.
+    pub fn iter(&self) -> IterAt<'_, K, V, NUM_LEVELS> {
+        self.iter_at(self.seq)
+    }
+
+    /// Drops the versions that no snapshot at `oldest` or later can see, which is every
+    /// version but the last one at or before `oldest`, and that one too if it's a tombstone.
+    /// Reading a snapshot before `oldest` afterwards may not see what it used to.
+    pub fn gc(&mut self, oldest: u64) {
+        self.map.retain(|_, chain| {
+            let visible = chain.partition_point(|version| version.seq <= oldest);
+            if visible > 0 {
+                let drop_visible = chain[visible - 1].value.is_none();
+                chain.drain(..visible - usize::from(!drop_visible));
+            }
+            !chain.is_empty()
+        });
+    }
+}
+
+impl<K: Ord, V, const NUM_LEVELS: usize> VersionedMap<K, V, NUM_LEVELS> {
+    /// Writes `value` as the new version of `key`, and returns the write's sequence number.
+    pub fn insert(&mut self, key: K, value: V) -> u64 {
+        self.seq += 1;
+        let version = Version { seq: self.seq, value: Some(value) };
+        match self.map.entry(key) {
+            Entry::Occupied(mut entry) => entry.get_mut().push(version),
+            Entry::Vacant(entry) => {
+                entry.insert(vec![version]);
+            }
+        }
+        self.seq
+    }
+
+    /// Writes a tombstone as the new version of `key`, and returns the write's sequence
+    /// number. If the key has no versions, there's nothing to hide and nothing is written.
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+    {
+        if let Some(chain) = self.map.get_mut(key) {
+            self.seq += 1;
+            chain.push(Version { seq: self.seq, value: None });
+        }
+        self.seq
+    }
+
+    /// Returns the latest value of `key`.
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_at(key, self.seq)
+    }
+
+    /// Returns the value of `key` a snapshot at `seq` sees: the one written last by a write
+    /// with sequence number `seq` or lower, unless it's a tombstone.
+    pub fn get_at<Q: Ord + ?Sized>(&self, key: &Q, seq: u64) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        visible(self.map.get(key)?, seq)
+    }
+}
+
+impl<K, V, const NUM_LEVELS: usize> Default for VersionedMap<K, V, NUM_LEVELS> {
+    fn default() -> Self {
+        VersionedMap::new()
+    }
+}
+
+impl<K: Debug, V: Debug, const NUM_LEVELS: usize> Debug for VersionedMap<K, V, NUM_LEVELS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the entries a snapshot of a `VersionedMap` sees, sorted by key.
+///
+/// Created by [`VersionedMap::iter_at`] and [`VersionedMap::iter`].
+pub struct IterAt<'a, K, V, const NUM_LEVELS: usize> {
+    inner: map::Iter<'a, K, Vec<Version<V>>, NUM_LEVELS>,
+    seq: u64,
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> Iterator for IterAt<'a, K, V, NUM_LEVELS> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let seq = self.seq;
+        self.inner.by_ref().find_map(|(key, chain)| Some((key, visible(chain, seq)?)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> DoubleEndedIterator for IterAt<'a, K, V, NUM_LEVELS> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let seq = self.seq;
+        self.inner.by_ref().rev().find_map(|(key, chain)| Some((key, visible(chain, seq)?)))
+    }
+}
+
+impl<'a, K, V, const NUM_LEVELS: usize> FusedIterator for IterAt<'a, K, V, NUM_LEVELS> {}
+
+#[cfg(test)]
+mod tests {
+    use super::VersionedMap;
+
+    #[test]
+    fn snapshots() {
+        let mut m = VersionedMap::<&str, i32>::new();
+        assert_eq!(m.insert("a", 1), 1);
+        assert_eq!(m.insert("b", 2), 2);
+        let before = m.seq();
+        assert_eq!(m.insert("a", 10), 3);
+        assert_eq!(m.remove("b"), 4);
+        assert_eq!(m.remove("missing"), 4);
+        m.insert("c", 3);
+
+        assert_eq!(m.get("a"), Some(&10));
+        assert_eq!(m.get("b"), None);
+        assert_eq!(m.get_at("a", before), Some(&1));
+        assert_eq!(m.get_at("b", before), Some(&2));
+        assert_eq!(m.get_at("a", 0), None);
+        assert_eq!(m.get_at("c", before), None);
+
+        assert!(m.iter_at(before).eq([(&"a", &1), (&"b", &2)]));
+        assert!(m.iter().eq([(&"a", &10), (&"c", &3)]));
+        assert!(m.iter().rev().eq([(&"c", &3), (&"a", &10)]));
+        assert_eq!(m.key_count(), 3);
+    }
+
+    #[test]
+    fn gc() {
+        let mut m = VersionedMap::<u32, u32>::new();
+        for v in 0..5 {
+            m.insert(1, v);
+        }
+        m.insert(2, 0);
+        let snapshot = m.remove(&2);
+        m.insert(1, 5);
+
+        m.gc(snapshot);
+        // key 2 is gone at every snapshot that's still readable
+        assert_eq!(m.key_count(), 1);
+        assert_eq!(m.get_at(&1, snapshot), Some(&4));
+        assert_eq!(m.get(&1), Some(&5));
+        assert_eq!(m.map.get(&1).unwrap().len(), 2);
+
+        m.gc(m.seq());
+        assert_eq!(m.map.get(&1).unwrap().len(), 1);
+        assert_eq!(m.get(&1), Some(&5));
+    }
+}
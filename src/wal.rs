@@ -0,0 +1,379 @@
+//! A skip list map that logs every change to a file before making it, so it can be rebuilt
+//! after a crash.
+//!
+//! [`DurableSkipMap`] is an in-memory [`SkipListMap`] of byte strings plus a write-ahead log:
+//! every insert and remove first appends a record to the log, then changes the map. Opening
+//! the map again replays the log. This is the memtable of a log-structured storage engine,
+//! minus the flushing to sorted files.
+//!
+//! A record is a tag byte, the key's length as a little-endian `u32`, for inserts the value's
+//! length as another, then the key, the value, and a CRC-32 of everything before it. A crash
+//! in the middle of an append leaves a torn record at the end of the log, which replay drops.
+
+use std::convert::{TryFrom, TryInto};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::map::{Iter, SkipListMap};
+
+const INSERT: u8 = 1;
+const REMOVE: u8 = 2;
+
+/// A sorted map from byte strings to byte strings that logs every change to a file before
+/// making it.
+///
+/// Records are buffered, so a change is only durable once [`sync`](DurableSkipMap::sync) has
+/// returned. Replay rebuilds the map as of the last record that made it to disk intact.
+///
+/// Once writing to the log fails, every later change, sync, and clear fails too, until the map
+/// is reopened: the failed write may have left part of a record in the log, and replay would
+/// drop every record after it.
+pub struct DurableSkipMap<const NUM_LEVELS: usize> {
+    map: SkipListMap<Vec<u8>, Vec<u8>, NUM_LEVELS>,
+    log: Log<File>,
+    // records the log held when it was opened, and appended since
+    records: u64,
+}
+
+impl<const NUM_LEVELS: usize> DurableSkipMap<NUM_LEVELS> {
+    /// Opens the log at `path`, creating it if it doesn't exist, and rebuilds the map by
+    /// replaying it.
+    ///
+    /// A record that's cut off or fails its checksum ends the replay, and is cut from the log
+    /// along with everything after it, so new records don't end up behind the damage.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut map = SkipListMap::new();
+        let mut records = 0;
+        let mut pos = 0;
+        while let Some((record, len)) = Record::decode(&bytes[pos..]) {
+            match record {
+                Record::Insert(key, value) => map.insert(key.to_vec(), value.to_vec()),
+                Record::Remove(key) => map.remove(key),
+            };
+            records += 1;
+            pos += len;
+        }
+        if pos < bytes.len() {
+            file.set_len(pos as u64)?;
+            file.sync_data()?;
+        }
+
+        Ok(DurableSkipMap { map, log: Log::new(file), records })
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of records in the log, which grows with every change, not with the
+    /// number of entries.
+    pub fn log_len(&self) -> u64 {
+        self.records
+    }
+
+    /// Returns the value stored under `key`.
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.map.get(key).map(Vec::as_slice)
+    }
+
+    /// Returns true if the map contains an entry for `key`.
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Logs the insert, then stores `value` under `key` and returns the value it replaced.
+    ///
+    /// Fails if the record can't be written, in which case the map is unchanged.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.append(Record::Insert(key, value))?;
+        Ok(self.map.insert(key.to_vec(), value.to_vec()))
+    }
+
+    /// Logs the removal, then removes the entry for `key` and returns its value. Nothing is
+    /// logged if there's no such entry.
+    ///
+    /// Fails if the record can't be written, in which case the map is unchanged.
+    pub fn remove(&mut self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        if !self.map.contains_key(key) {
+            return Ok(None);
+        }
+        self.append(Record::Remove(key))?;
+        Ok(self.map.remove(key))
+    }
+
+    /// Removes every entry and empties the log, for when its contents have been written out
+    /// somewhere else.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.log.write(|out| {
+            out.flush()?;
+            out.get_ref().set_len(0)?;
+            out.get_ref().sync_data()
+        })?;
+        self.map.clear();
+        self.records = 0;
+        Ok(())
+    }
+
+    /// Writes out buffered records and blocks until they're on disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.log.write(|out| {
+            out.flush()?;
+            out.get_ref().sync_data()
+        })
+    }
+
+    /// Returns an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, Vec<u8>, Vec<u8>, NUM_LEVELS> {
+        self.map.iter()
+    }
+
+    /// Returns the map the log has been applied to.
+    pub fn as_map(&self) -> &SkipListMap<Vec<u8>, Vec<u8>, NUM_LEVELS> {
+        &self.map
+    }
+
+    fn append(&mut self, record: Record<'_>) -> io::Result<()> {
+        let bytes = record.encode()?;
+        self.log.write(|out| out.write_all(&bytes))?;
+        self.records += 1;
+        Ok(())
+    }
+}
+
+impl<const NUM_LEVELS: usize> std::fmt::Debug for DurableSkipMap<NUM_LEVELS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+/// The log file behind a write buffer, which refuses to write anything after a write fails.
+struct Log<W: Write> {
+    out: BufWriter<W>,
+    // the kind of the error the first failed write returned
+    failed: Option<io::ErrorKind>,
+}
+
+impl<W: Write> Log<W> {
+    fn new(out: W) -> Self {
+        Log { out: BufWriter::new(out), failed: None }
+    }
+
+    /// Runs `op` on the buffered log, unless an earlier write failed, and remembers if it fails.
+    fn write<R>(&mut self, op: impl FnOnce(&mut BufWriter<W>) -> io::Result<R>) -> io::Result<R> {
+        if let Some(kind) = self.failed {
+            return Err(io::Error::new(kind, "an earlier write to the log failed, reopen the map to go on"));
+        }
+        op(&mut self.out).inspect_err(|err| self.failed = Some(err.kind()))
+    }
+}
+
+enum Record<'a> {
+    Insert(&'a [u8], &'a [u8]),
+    Remove(&'a [u8]),
+}
+
+impl<'a> Record<'a> {
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        let len = |bytes: &[u8]| {
+            u32::try_from(bytes.len())
+                .map(u32::to_le_bytes)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "key or value longer than u32::MAX bytes"))
+        };
+        let mut out = Vec::new();
+        match *self {
+            Record::Insert(key, value) => {
+                out.push(INSERT);
+                out.extend_from_slice(&len(key)?);
+                out.extend_from_slice(&len(value)?);
+                out.extend_from_slice(key);
+                out.extend_from_slice(value);
+            }
+            Record::Remove(key) => {
+                out.push(REMOVE);
+                out.extend_from_slice(&len(key)?);
+                out.extend_from_slice(key);
+            }
+        }
+        out.extend_from_slice(&crc32(&out).to_le_bytes());
+        Ok(out)
+    }
+
+    /// Returns the record at the start of `bytes` and its length in bytes, or None if it's
+    /// cut off or damaged.
+    fn decode(bytes: &'a [u8]) -> Option<(Self, usize)> {
+        let mut rest = bytes;
+        let record = match take(&mut rest, 1)?[0] {
+            INSERT => {
+                let key_len = take_u32(&mut rest)? as usize;
+                let val_len = take_u32(&mut rest)? as usize;
+                Record::Insert(take(&mut rest, key_len)?, take(&mut rest, val_len)?)
+            }
+            REMOVE => {
+                let key_len = take_u32(&mut rest)? as usize;
+                Record::Remove(take(&mut rest, key_len)?)
+            }
+            _ => return None,
+        };
+        let body = bytes.len() - rest.len();
+        let checksum = take_u32(&mut rest)?;
+        (checksum == crc32(&bytes[..body])).then_some((record, body + 4))
+    }
+}
+
+/// Splits the first `n` bytes off `rest`, if it has that many.
+fn take<'a>(rest: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if rest.len() < n {
+        return None;
+    }
+    let (taken, tail) = rest.split_at(n);
+    *rest = tail;
+    Some(taken)
+}
+
+fn take_u32(rest: &mut &[u8]) -> Option<u32> {
+    take(rest, 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// The CRC-32 used by zlib and Ethernet, a bit at a time. Records are small, and this only
+/// runs when logging or replaying them.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, DurableSkipMap, Log, Record};
+    use std::convert::TryInto;
+    use std::io::{self, Write};
+    use std::path::PathBuf;
+
+    // a file in the temp directory that's removed when the test is done with it
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("skiplist-wal-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn replay() {
+        let file = TempFile::new("replay");
+        {
+            let mut m = DurableSkipMap::<16>::open(&file.0).unwrap();
+            for k in 0..100u32 {
+                assert_eq!(m.insert(&k.to_be_bytes(), k.to_string().as_bytes()).unwrap(), None);
+            }
+            assert_eq!(m.insert(&7u32.to_be_bytes(), b"seven").unwrap(), Some(b"7".to_vec()));
+            assert_eq!(m.remove(&8u32.to_be_bytes()).unwrap(), Some(b"8".to_vec()));
+            assert_eq!(m.remove(b"missing").unwrap(), None);
+            assert_eq!(m.log_len(), 102);
+            m.sync().unwrap();
+        }
+
+        let m = DurableSkipMap::<16>::open(&file.0).unwrap();
+        assert_eq!(m.len(), 99);
+        assert_eq!(m.log_len(), 102);
+        assert_eq!(m.get(&7u32.to_be_bytes()), Some(&b"seven"[..]));
+        assert!(!m.contains_key(&8u32.to_be_bytes()));
+        assert!(m.iter().map(|(k, _)| u32::from_be_bytes(k[..].try_into().unwrap())).eq((0..100).filter(|&k| k != 8)));
+    }
+
+    #[test]
+    fn torn_tail() {
+        let file = TempFile::new("torn_tail");
+        {
+            let mut m = DurableSkipMap::<16>::open(&file.0).unwrap();
+            m.insert(b"a", b"1").unwrap();
+            m.insert(b"b", b"2").unwrap();
+        }
+        // cut the last record short, as if the machine died halfway through writing it
+        let len = std::fs::metadata(&file.0).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&file.0).unwrap().set_len(len - 3).unwrap();
+
+        {
+            let mut m = DurableSkipMap::<16>::open(&file.0).unwrap();
+            assert!(m.iter().map(|(k, v)| (&k[..], &v[..])).eq([(&b"a"[..], &b"1"[..])]));
+            m.insert(b"c", b"3").unwrap();
+        }
+
+        let mut m = DurableSkipMap::<16>::open(&file.0).unwrap();
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get(b"c"), Some(&b"3"[..]));
+
+        m.clear().unwrap();
+        drop(m);
+        assert!(DurableSkipMap::<16>::open(&file.0).unwrap().is_empty());
+    }
+    #[test]
+    fn failed_write_stops_the_log() {
+        // takes `room` bytes, then fails every write like a full disk
+        struct Full {
+            written: Vec<u8>,
+            room: usize,
+        }
+
+        impl Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.room - self.written.len());
+                if n == 0 {
+                    return Err(io::Error::other("disk full"));
+                }
+                self.written.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // too big for the buffer, so it's written straight through
+        let value = [7; 16 << 10];
+        let record = Record::Insert(b"key", &value).encode().unwrap();
+        let mut log = Log::new(Full { written: Vec::new(), room: 24 << 10 });
+        log.write(|out| out.write_all(&record)).unwrap();
+        assert_eq!(log.write(|out| out.write_all(&record)).unwrap_err().kind(), io::ErrorKind::Other);
+
+        // the second record was torn, and nothing gets written after it
+        log.out.get_mut().room = usize::MAX;
+        assert!(log.write(|out| out.write_all(b"more")).is_err());
+        assert!(log.write(|out| out.flush()).is_err());
+        let written = &log.out.get_ref().written;
+        assert_eq!(written.len(), 24 << 10);
+        let (first, len) = Record::decode(written).unwrap();
+        assert!(matches!(first, Record::Insert(b"key", v) if v == &value[..]));
+        assert!(Record::decode(&written[len..]).is_none());
+    }
+}